@@ -0,0 +1,137 @@
+#[macro_use]
+extern crate criterion;
+extern crate pbr;
+
+use criterion::{BatchSize, BenchmarkId, Criterion};
+use pbr::{MultiBar, ProgressBar};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+// Counting wraps the system allocator to count the allocations of a hot
+// path, see `allocations`.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const INCS: u64 = 10_000;
+
+// bar returns a bar on a sink, drawn at every `inc` if `rendering`, or
+// only at the first one.
+fn bar(rendering: bool) -> ProgressBar<io::Sink> {
+    let mut pb = ProgressBar::on(io::sink(), INCS);
+    pb.set_width(Some(80));
+    if rendering {
+        pb.set_max_refresh_rate(None);
+    } else {
+        pb.set_max_refresh_rate(Some(Duration::from_secs(3600)));
+    }
+    pb
+}
+
+fn inc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("inc");
+    for &rendering in &[false, true] {
+        let name = if rendering { "rendering" } else { "counting" };
+        group.bench_function(name, |b| {
+            let mut pb = bar(rendering);
+            b.iter(|| {
+                pb.inc();
+                if pb.add(0) >= INCS {
+                    pb.set(0);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+// multi_bar returns a MultiBar with `bars` bars that moved and finished,
+// so that `listen` draws all of their updates and returns.
+fn multi_bar(bars: usize) -> MultiBar<io::Sink> {
+    let mut mb = MultiBar::on(io::sink());
+    for _ in 0..bars {
+        let mut pb = mb.create_bar(100);
+        pb.set_max_refresh_rate(None);
+        for _ in 0..10 {
+            pb.inc();
+        }
+        pb.finish();
+    }
+    mb
+}
+
+fn multi_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multi_listen");
+    for &bars in &[1, 10, 100] {
+        group.bench_with_input(BenchmarkId::from_parameter(bars), &bars, |b, &bars| {
+            b.iter_batched(|| multi_bar(bars), |mb| mb.listen(), BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+
+    // the frames drawn, from `MultiBar::frame_stats`, show whether a
+    // change of the renderer draws more often, not only slower
+    for &bars in &[1, 10, 100] {
+        let mb = multi_bar(bars);
+        let stats = mb.frame_stats();
+        mb.listen();
+        println!(
+            "multi_listen/{}: {} frames drawn, {} skipped",
+            bars,
+            stats.drawn(),
+            stats.skipped()
+        );
+    }
+}
+
+// allocations returns the allocations per call of `f`, over `n` calls.
+fn allocations<F: FnMut()>(n: usize, mut f: F) -> f64 {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..n {
+        f();
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / n as f64
+}
+
+// check_allocations fails the run if a hot path allocates more than its
+// budget, a regression the timings would only hint at.
+fn check_allocations() {
+    let mut pb = bar(false);
+    pb.inc();
+    let counting = allocations(INCS as usize - 1, || {
+        black_box(pb.inc());
+    });
+    let mut pb = bar(true);
+    let rendering = allocations(INCS as usize, || {
+        black_box(pb.inc());
+    });
+    println!("allocations/inc/counting: {:.2} per call", counting);
+    println!("allocations/inc/rendering: {:.2} per call", rendering);
+    assert!(counting < 0.01, "inc allocates without drawing");
+    assert!(rendering <= 25., "inc allocates {} times per frame", rendering);
+}
+
+criterion_group!(benches, inc, multi_frame);
+
+fn main() {
+    check_allocations();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+}
@@ -0,0 +1,36 @@
+//! Sets a bar format, and draws a bar with it if it's valid: a malformed
+//! format must be an error, and a frame must stay bounded.
+//!
+//! ```sh
+//! cargo +nightly fuzz run format
+//! ```
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pbr::ProgressBar;
+
+fuzz_target!(|data: &[u8]| {
+    let format = match std::str::from_utf8(data) {
+        Ok(f) => f,
+        Err(_) => return,
+    };
+    for &smooth in &[false, true] {
+        let mut out = Vec::new();
+        {
+            let mut pb = ProgressBar::on(&mut out, 100);
+            pb.set_width(Some(80));
+            pb.set_max_refresh_rate(None);
+            pb.set_smooth(smooth);
+            if pb.try_format(format).is_err() {
+                return;
+            }
+            pb.add(42);
+            pb.add(100);
+            pb.finish();
+        }
+        let bound = 64 * (format.len() + 80);
+        for frame in out.split(|&b| b == b'\r') {
+            assert!(frame.len() <= bound, "frame of {} bytes", frame.len());
+        }
+    }
+});
@@ -0,0 +1,36 @@
+//! Parses a template, and draws a bar with it if it's valid: a malformed
+//! template must be an error, and a frame must stay bounded.
+//!
+//! ```sh
+//! cargo +nightly fuzz run template
+//! ```
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pbr::ProgressBar;
+
+fuzz_target!(|data: &[u8]| {
+    let template = match std::str::from_utf8(data) {
+        Ok(t) => t,
+        Err(_) => return,
+    };
+    let mut out = Vec::new();
+    {
+        let mut pb = ProgressBar::on(&mut out, 100);
+        pb.set_width(Some(80));
+        pb.set_max_refresh_rate(None);
+        if pb.set_template(Some(template)).is_err() {
+            return;
+        }
+        pb.message("msg ");
+        pb.add(42);
+        pb.add(100);
+        pb.finish();
+    }
+    // a field is at least 3 bytes and expands to a short value, a fixed bar
+    // is clamped to 1000 cells
+    let bound = 64 * (template.len() + 1000 + 80);
+    for frame in out.split(|&b| b == b'\r') {
+        assert!(frame.len() <= bound, "frame of {} bytes", frame.len());
+    }
+});
@@ -0,0 +1,106 @@
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Adder is a lightweight handle that adds to the counter of a
+/// `ProgressBar`, see `ProgressBar::split_handle`.
+///
+/// It can be cloned and sent to other threads, e.g. one per connection when
+/// a download is fetched in parallel ranges.
+#[derive(Debug, Clone)]
+pub struct Adder {
+    count: Arc<AtomicU64>,
+}
+
+impl Adder {
+    pub(crate) fn new(count: Arc<AtomicU64>) -> Adder {
+        Adder { count }
+    }
+
+    /// Add `n` to the bar.
+    pub fn add(&self, n: u64) {
+        self.count.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Add 1 to the bar.
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Wrap a reader, adding the number of bytes read to the bar.
+    pub fn wrap_read<R: Read>(self, inner: R) -> AdderRead<R> {
+        AdderRead { inner, adder: self }
+    }
+
+    /// Wrap a writer, adding the number of bytes written to the bar.
+    pub fn wrap_write<W: Write>(self, inner: W) -> AdderWrite<W> {
+        AdderWrite { inner, adder: self }
+    }
+}
+
+/// CancelHandle cancels a `ProgressBar` from another thread, see
+/// `ProgressBar::cancel_handle`.
+#[derive(Debug, Clone)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    pub(crate) fn new(cancelled: Arc<AtomicBool>) -> CancelHandle {
+        CancelHandle { cancelled }
+    }
+
+    /// Cancel the bar, a `ProgressBar::sleep` in progress returns.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// AdderRead is a reader that adds the bytes it reads to a bar, see
+/// `Adder::wrap_read`.
+#[derive(Debug)]
+pub struct AdderRead<R> {
+    inner: R,
+    adder: Adder,
+}
+
+impl<R> AdderRead<R> {
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for AdderRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.adder.add(n as u64);
+        Ok(n)
+    }
+}
+
+/// AdderWrite is a writer that adds the bytes it writes to a bar, see
+/// `Adder::wrap_write`.
+#[derive(Debug)]
+pub struct AdderWrite<W> {
+    inner: W,
+    adder: Adder,
+}
+
+impl<W> AdderWrite<W> {
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for AdderWrite<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.adder.add(n as u64);
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
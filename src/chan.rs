@@ -0,0 +1,54 @@
+//! The channel used between a `MultiBar` and its bars.
+//!
+//! `std::sync::mpsc` is used by default, the `crossbeam` feature swaps it
+//! for `crossbeam-channel`, which has a lower latency with many producer
+//! threads.
+
+use std::time::Instant;
+
+#[cfg(not(feature = "crossbeam"))]
+pub use std::sync::mpsc::{channel, Receiver, Sender};
+
+#[cfg(feature = "crossbeam")]
+pub use crossbeam_channel::{unbounded as channel, Receiver, Sender};
+
+/// Result of `recv_deadline`.
+pub enum Recv<T> {
+    Msg(T),
+    Timeout,
+    Closed,
+}
+
+/// Wait for the next message, until `deadline` if any.
+#[cfg(not(feature = "crossbeam"))]
+pub fn recv_deadline<T>(rx: &Receiver<T>, deadline: Option<Instant>) -> Recv<T> {
+    use std::sync::mpsc::RecvTimeoutError;
+    match deadline {
+        Some(deadline) => {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(timeout) {
+                Ok(msg) => Recv::Msg(msg),
+                Err(RecvTimeoutError::Timeout) => Recv::Timeout,
+                Err(RecvTimeoutError::Disconnected) => Recv::Closed,
+            }
+        }
+        None => match rx.recv() {
+            Ok(msg) => Recv::Msg(msg),
+            Err(_) => Recv::Closed,
+        },
+    }
+}
+
+/// Wait for the next message, until `deadline` if any.
+#[cfg(feature = "crossbeam")]
+pub fn recv_deadline<T>(rx: &Receiver<T>, deadline: Option<Instant>) -> Recv<T> {
+    use crossbeam_channel::{at, never};
+    let timer = deadline.map_or_else(never, at);
+    select! {
+        recv(rx) -> msg => match msg {
+            Ok(msg) => Recv::Msg(msg),
+            Err(_) => Recv::Closed,
+        },
+        recv(timer) -> _ => Recv::Timeout,
+    }
+}
@@ -0,0 +1,43 @@
+//! Progress reporting from a child process, see `parent::attach` for the
+//! parent side.
+//!
+//! The child writes lines `PB <fraction> <message>` on its stdout, where
+//! `fraction` is the completed part of the work, between 0 and 1. Any other
+//! output of the child should go to stderr.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use pbr::child;
+//!
+//! let files = ["a", "b", "c", "d"];
+//! for (i, f) in files.iter().enumerate() {
+//!     child::report(i as f64 / files.len() as f64, f).unwrap();
+//!     // ...
+//! }
+//! child::report(1., "done").unwrap();
+//! ```
+
+use std::io::{self, Write};
+
+// Prefix of a progress line.
+pub(crate) const PREFIX: &str = "PB ";
+
+/// Report progress to the parent process, on stdout.
+pub fn report(fraction: f64, msg: &str) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    report_to(&mut out, fraction, msg)
+}
+
+/// Report progress to the parent process, on `w`.
+pub fn report_to<W: Write>(w: &mut W, fraction: f64, msg: &str) -> io::Result<()> {
+    let fraction = if fraction.is_nan() {
+        0.
+    } else {
+        fraction.clamp(0., 1.)
+    };
+    let msg = msg.replace(['\n', '\r'], " ");
+    writeln!(w, "{}{} {}", PREFIX, fraction, msg)?;
+    w.flush()
+}
@@ -0,0 +1,200 @@
+use multi::{MultiBar, Pipe};
+use pb::{ProgressBar, Units};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Stdout, Write};
+use std::sync::Mutex;
+use std::thread;
+
+/// Downloads fetches a list of files with a pool of workers, showing a bar
+/// per file under a bar of the whole transfer.
+///
+/// The fetch itself is up to the caller: a closure gets the name of each
+/// file and a `Transfer` to report the bytes received.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::io;
+/// use pbr::Downloads;
+///
+/// let mut dl = Downloads::new(4);
+/// for (len, url) in files {
+///     dl.add(len, url);
+/// }
+/// let results = dl.run(|url, transfer| {
+///     let mut resp = http_get(url)?;
+///     io::copy(&mut resp, transfer).map(|_| ())
+/// });
+/// ```
+pub struct Downloads<T: Write> {
+    handle: T,
+    workers: usize,
+    entries: Vec<(u64, String)>,
+}
+
+impl Downloads<Stdout> {
+    /// Create a new Downloads with `workers` parallel fetches, drawn to
+    /// stdout.
+    pub fn new(workers: usize) -> Downloads<Stdout> {
+        Downloads::on(io::stdout(), workers)
+    }
+}
+
+impl<T: Write + Send> Downloads<T> {
+    /// Create a new Downloads with `workers` parallel fetches, drawn to
+    /// `handle`.
+    pub fn on(handle: T, workers: usize) -> Downloads<T> {
+        Downloads {
+            handle,
+            workers,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add a file of `len` bytes to fetch.
+    pub fn add<S: Into<String>>(&mut self, len: u64, name: S) {
+        self.entries.push((len, name.into()));
+    }
+
+    /// Fetch all the files with `fetch`, and return their results in the
+    /// order they were added.
+    ///
+    /// The bar of a failed file is finished with a `failed` note.
+    pub fn run<F, E>(self, fetch: F) -> Vec<Result<(), E>>
+    where
+        F: Fn(&str, &mut Transfer) -> Result<(), E> + Sync,
+        E: Send,
+    {
+        let Downloads {
+            handle,
+            workers,
+            entries,
+        } = self;
+        let mut mb = MultiBar::on(handle);
+        let mut total = mb.create_bar(entries.iter().map(|e| e.0).sum());
+        total.set_units(Units::Bytes);
+        total.message("Total ");
+        let total = Mutex::new(total);
+        let bars: Vec<ProgressBar<Pipe>> = entries
+            .iter()
+            .map(|&(len, ref name)| {
+                let mut pb = mb.create_bar(len);
+                pb.set_units(Units::Bytes);
+                pb.message(format!("{} ", name));
+                pb
+            })
+            .collect();
+        let queue = Mutex::new(entries.into_iter().zip(bars).enumerate());
+        let results = Mutex::new(Vec::new());
+
+        thread::scope(|s| {
+            s.spawn(move || mb.listen());
+            thread::scope(|w| {
+                for _ in 0..workers.max(1) {
+                    w.spawn(|| loop {
+                        let next = queue.lock().unwrap().next();
+                        let (i, ((_, name), mut bar)) = match next {
+                            Some(next) => next,
+                            None => break,
+                        };
+                        let res = fetch(
+                            &name,
+                            &mut Transfer {
+                                bar: &mut bar,
+                                total: &total,
+                            },
+                        );
+                        match res {
+                            Ok(()) => bar.finish(),
+                            Err(_) => bar.finish_print(&format!("{}: failed", name)),
+                        }
+                        results.lock().unwrap().push((i, res));
+                    });
+                }
+            });
+            total.into_inner().unwrap().finish();
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|r| r.0);
+        results.into_iter().map(|r| r.1).collect()
+    }
+}
+
+impl<T: Write> fmt::Debug for Downloads<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Downloads")
+            .field("workers", &self.workers)
+            .field("entries", &self.entries)
+            .finish()
+    }
+}
+
+/// Transfer reports the progress of a file fetched by `Downloads::run`,
+/// to its bar and to the bar of the whole transfer.
+///
+/// It's a writer too: the bytes written are counted, and discarded.
+pub struct Transfer<'a> {
+    bar: &'a mut ProgressBar<Pipe>,
+    total: &'a Mutex<ProgressBar<Pipe>>,
+}
+
+impl<'a> Transfer<'a> {
+    /// Add `n` bytes received.
+    pub fn add(&mut self, n: u64) {
+        self.bar.add(n);
+        self.total.lock().unwrap().add(n);
+    }
+
+    /// Set the message of the file bar, e.g. to show a retry.
+    pub fn message<S: Into<Cow<'static, str>>>(&mut self, message: S) {
+        self.bar.message(message);
+    }
+}
+
+impl<'a> Write for Transfer<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.add(buf.len() as u64);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Debug for Transfer<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Transfer").field("bar", &self.bar).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use downloads::Downloads;
+    use std::io::Write;
+    use testing::Output;
+
+    #[test]
+    fn run() {
+        let out = Output::default();
+        let mut dl = Downloads::on(out.clone(), 2);
+        dl.add(300, "a.tar");
+        dl.add(500, "b.tar");
+        dl.add(200, "c.tar");
+        let results = dl.run(|name, transfer| {
+            if name == "b.tar" {
+                transfer.add(100);
+                return Err("connection reset");
+            }
+            let len = if name == "a.tar" { 300 } else { 200 };
+            transfer.write_all(&vec![0; len]).unwrap();
+            Ok(())
+        });
+        assert_eq!(results, [Ok(()), Err("connection reset"), Ok(())]);
+
+        let out = out.text();
+        assert!(out.contains("b.tar: failed"));
+        assert!(out.contains("Total 600 B / 1000 B"), "{}", out);
+    }
+}
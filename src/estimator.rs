@@ -0,0 +1,315 @@
+//! Rate estimators, see `ProgressBar::set_estimator`.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Estimator predicts the rate of a bar, from which its ETA is computed.
+///
+/// It's fed with a sample every time the bar draws.
+pub trait Estimator {
+    /// Record that `current` items were done `elapsed` after the start.
+    fn record(&mut self, current: u64, elapsed: Duration);
+
+    /// Returns the estimated rate, in items per second.
+    fn rate(&self) -> f64;
+
+    /// Forget the samples recorded so far, see `ProgressBar::reset_eta`.
+    /// The next sample may be at an earlier `elapsed`.
+    fn reset(&mut self) {}
+}
+
+/// Returns the time left to do `remaining` items at `rate` items per second,
+/// the ETA shown by the bars. It's zero once nothing remains, and `None` when
+/// the rate can't tell: not positive, not finite, or too slow for a
+/// `Duration`.
+///
+/// # Examples
+///
+/// ```
+/// use pbr::eta;
+/// use std::time::Duration;
+///
+/// assert_eq!(eta(50, 10.), Some(Duration::from_secs(5)));
+/// assert_eq!(eta(0, 10.), Some(Duration::from_secs(0)));
+/// assert_eq!(eta(50, 0.), None);
+/// ```
+pub fn eta(remaining: u64, rate: f64) -> Option<Duration> {
+    if remaining == 0 {
+        return Some(Duration::from_secs(0));
+    }
+    let left = remaining as f64 / rate;
+    if rate > 0. && rate.is_finite() && left < u64::MAX as f64 {
+        Some(Duration::from_secs_f64(left))
+    } else {
+        None
+    }
+}
+
+fn secs(d: Duration) -> f64 {
+    d.as_secs() as f64 + f64::from(d.subsec_nanos()) / 1e9
+}
+
+/// Linear estimates the rate as the average since the start, the default.
+#[derive(Debug, Clone, Default)]
+pub struct Linear {
+    rate: f64,
+}
+
+impl Linear {
+    pub fn new() -> Linear {
+        Linear::default()
+    }
+}
+
+impl Estimator for Linear {
+    fn record(&mut self, current: u64, elapsed: Duration) {
+        if elapsed > Duration::new(0, 0) {
+            self.rate = current as f64 / secs(elapsed);
+        }
+    }
+
+    fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    fn reset(&mut self) {
+        self.rate = 0.;
+    }
+}
+
+/// SlidingWindow estimates the rate over the last `window` of time, to
+/// follow changes of pace.
+#[derive(Debug, Clone)]
+pub struct SlidingWindow {
+    window: Duration,
+    samples: VecDeque<(Duration, u64)>,
+}
+
+impl SlidingWindow {
+    pub fn new(window: Duration) -> SlidingWindow {
+        SlidingWindow {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+}
+
+impl Estimator for SlidingWindow {
+    fn record(&mut self, current: u64, elapsed: Duration) {
+        self.samples.push_back((elapsed, current));
+        // keep one sample older than the window, to span all of it
+        while self.samples.len() > 2 && elapsed - self.samples[1].0 >= self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    fn rate(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(&(t0, n0)), Some(&(t1, n1))) if t1 > t0 => {
+                n1.saturating_sub(n0) as f64 / secs(t1 - t0)
+            }
+            _ => 0.,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// Exponential is an exponential moving average of the rate: a sample
+/// weighs half as much after each `half_life`, so bursts are damped and the
+/// rate settles on a new pace after a few half-lives.
+#[derive(Debug, Clone)]
+pub struct Exponential {
+    half_life: Duration,
+    rate: Option<f64>,
+    last: Option<(Duration, u64)>,
+}
+
+impl Exponential {
+    pub fn new(half_life: Duration) -> Exponential {
+        Exponential {
+            half_life,
+            rate: None,
+            last: None,
+        }
+    }
+}
+
+impl Estimator for Exponential {
+    fn record(&mut self, current: u64, elapsed: Duration) {
+        let (t0, n0) = self.last.unwrap_or((Duration::new(0, 0), 0));
+        if elapsed <= t0 {
+            return;
+        }
+        self.last = Some((elapsed, current));
+        let dt = secs(elapsed - t0);
+        let sample = current.saturating_sub(n0) as f64 / dt;
+        self.rate = Some(match self.rate {
+            None => sample,
+            Some(rate) => {
+                // the weight of the old rate after dt
+                let keep = if self.half_life > Duration::new(0, 0) {
+                    0.5f64.powf(dt / secs(self.half_life))
+                } else {
+                    0.
+                };
+                keep * rate + (1. - keep) * sample
+            }
+        });
+    }
+
+    fn rate(&self) -> f64 {
+        self.rate.unwrap_or(0.)
+    }
+
+    fn reset(&mut self) {
+        self.rate = None;
+        self.last = None;
+    }
+}
+
+/// DoubleExponential smooths the rate and its trend (Holt's linear
+/// method), which behaves like a simple Kalman filter: it follows steady
+/// accelerations and damps bursts.
+///
+/// `alpha` and `beta`, between 0 and 1, are the weights of a new sample in
+/// the rate and in the trend.
+#[derive(Debug, Clone)]
+pub struct DoubleExponential {
+    alpha: f64,
+    beta: f64,
+    level: Option<f64>,
+    trend: f64,
+    last: Option<(Duration, u64)>,
+}
+
+impl DoubleExponential {
+    pub fn new(alpha: f64, beta: f64) -> DoubleExponential {
+        DoubleExponential {
+            alpha,
+            beta,
+            level: None,
+            trend: 0.,
+            last: None,
+        }
+    }
+}
+
+impl Estimator for DoubleExponential {
+    fn record(&mut self, current: u64, elapsed: Duration) {
+        let (t0, n0) = self.last.unwrap_or((Duration::new(0, 0), 0));
+        if elapsed <= t0 {
+            return;
+        }
+        self.last = Some((elapsed, current));
+        let sample = current.saturating_sub(n0) as f64 / secs(elapsed - t0);
+        self.level = Some(match self.level {
+            None => sample,
+            Some(level) => {
+                let next = self.alpha * sample + (1. - self.alpha) * (level + self.trend);
+                self.trend = self.beta * (next - level) + (1. - self.beta) * self.trend;
+                next
+            }
+        });
+    }
+
+    fn rate(&self) -> f64 {
+        self.level.map_or(0., |level| (level + self.trend).max(0.))
+    }
+
+    fn reset(&mut self) {
+        self.level = None;
+        self.trend = 0.;
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use estimator::{eta, DoubleExponential, Estimator, Exponential, Linear, SlidingWindow};
+    use std::time::Duration;
+
+    fn feed<E: Estimator + ?Sized>(e: &mut E) {
+        // 10 items/s for 10s, then 100 items/s for 2s
+        for s in 1..=10 {
+            e.record(s * 10, Duration::from_secs(s));
+        }
+        for s in 11..=12 {
+            e.record(100 + (s - 10) * 100, Duration::from_secs(s));
+        }
+    }
+
+    #[test]
+    fn estimators() {
+        let mut linear = Linear::new();
+        feed(&mut linear);
+        assert_eq!(linear.rate(), 25.);
+
+        let mut window = SlidingWindow::new(Duration::from_secs(2));
+        feed(&mut window);
+        assert_eq!(window.rate(), 100.);
+
+        // 10 for the first 10s, then a weight of 1/2 and 3/4 for 100
+        let mut ema = Exponential::new(Duration::from_secs(1));
+        feed(&mut ema);
+        assert_eq!(ema.rate(), 77.5);
+
+        let mut smooth = DoubleExponential::new(0.3, 0.1);
+        feed(&mut smooth);
+        let rate = smooth.rate();
+        assert!(rate > 25. && rate < 100., "rate: {}", rate);
+    }
+
+    #[test]
+    fn reset() {
+        let estimators: Vec<Box<dyn Estimator>> = vec![
+            Box::new(Linear::new()),
+            Box::new(SlidingWindow::new(Duration::from_secs(2))),
+            Box::new(Exponential::new(Duration::from_secs(1))),
+            Box::new(DoubleExponential::new(0.3, 0.1)),
+        ];
+        for mut e in estimators {
+            feed(&mut *e);
+            let rate = e.rate();
+            e.reset();
+            assert_eq!(e.rate(), 0.);
+            // a second pass, from the start
+            feed(&mut *e);
+            assert_eq!(e.rate(), rate);
+        }
+    }
+
+    #[test]
+    fn eta_invariants() {
+        let secs = |d: Option<Duration>| d.map(|d| d.as_secs_f64());
+        for &rate in &[0.001, 0.5, 1., 10., 1e9] {
+            // finite, and decreasing to zero while the rate holds
+            let mut last = f64::INFINITY;
+            for remaining in (0..=100).rev() {
+                let left = secs(eta(remaining, rate)).expect("finite");
+                assert!(left.is_finite() && left <= last, "{} {}", remaining, rate);
+                last = left;
+            }
+            assert_eq!(last, 0.);
+        }
+        for &rate in &[0., -1., f64::NAN, f64::INFINITY, 1e-300] {
+            assert_eq!(eta(10, rate), None, "{}", rate);
+            assert_eq!(eta(0, rate), Some(Duration::from_secs(0)));
+        }
+
+        // the ETA of an estimator fed at a constant rate
+        let mut linear = Linear::new();
+        let mut last = None;
+        for s in 1..=10 {
+            linear.record(s * 10, Duration::from_secs(s));
+            let left = eta(100 - s * 10, linear.rate());
+            if let Some(last) = last {
+                assert!(left <= last, "{:?} {:?}", left, last);
+            }
+            last = Some(left);
+        }
+        assert_eq!(last, Some(Some(Duration::from_secs(0))));
+    }
+}
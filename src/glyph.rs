@@ -0,0 +1,226 @@
+//! Display width of glyphs, and their ASCII fallbacks.
+//!
+//! Emoji and most CJK glyphs take two terminal cells, combining marks and
+//! ANSI escape sequences none; Nerd Font icons (private use area) are
+//! counted as one cell.
+
+use std::borrow::Cow;
+use unicode_width::UnicodeWidthChar;
+
+// ASCII replacements for the glyphs commonly used as state icons, bar
+// glyphs and spinners, for terminals without the fonts.
+const ASCII_FALLBACK: [(char, &str); 33] = [
+    ('✅', "+"),
+    ('✔', "+"),
+    ('✓', "+"),
+    ('❌', "x"),
+    ('✖', "x"),
+    ('✗', "x"),
+    ('🔄', "~"),
+    ('⏳', "~"),
+    ('⚠', "!"),
+    ('…', "..."),
+    ('↑', "^"),
+    ('↓', "v"),
+    ('→', "-"),
+    ('█', "#"),
+    ('▌', "#"),
+    ('▒', "-"),
+    ('░', "-"),
+    ('━', "="),
+    ('─', "-"),
+    ('╢', "["),
+    ('╟', "]"),
+    ('▏', "|"),
+    ('▕', "|"),
+    ('⠋', "|"),
+    ('⠙', "/"),
+    ('⠹', "-"),
+    ('⠸', "\\"),
+    ('⠼', "|"),
+    ('⠴', "/"),
+    ('⠦', "-"),
+    ('⠧', "\\"),
+    ('⠇', "|"),
+    ('⠏', "/"),
+];
+
+/// Returns the number of terminal cells `s` takes.
+pub fn width(s: &str) -> usize {
+    let mut w = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1B' {
+            skip_escape(&mut chars);
+        } else {
+            w += char_width(c);
+        }
+    }
+    w
+}
+
+/// Returns the longest prefix of `s` that fits in `w` cells, escape
+/// sequences are kept.
+pub fn truncate(s: &str, w: usize) -> &str {
+    let mut used = 0;
+    let mut chars = s.chars();
+    loop {
+        let rest = chars.as_str();
+        let c = match chars.next() {
+            Some(c) => c,
+            None => return s,
+        };
+        if c == '\x1B' {
+            skip_escape(&mut chars);
+            continue;
+        }
+        used += char_width(c);
+        if used > w {
+            return &s[..s.len() - rest.len()];
+        }
+    }
+}
+
+/// Splits `s` in rows of at most `w` cells, escape sequences are kept.
+pub fn wrap(s: &str, w: usize) -> Vec<&str> {
+    let mut rows = Vec::new();
+    let mut rest = s;
+    while width(rest) > w {
+        let row = truncate(rest, w);
+        if row.is_empty() {
+            break;
+        }
+        rows.push(row);
+        rest = &rest[row.len()..];
+    }
+    rows.push(rest);
+    rows
+}
+
+/// Returns `s` without escape sequences and control characters.
+pub fn strip(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1B' {
+            skip_escape(&mut chars);
+        } else if !c.is_control() {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Returns `s` without colors and attributes (SGR sequences), the other
+/// escape sequences and control characters are kept.
+pub fn strip_colors(s: &str) -> Cow<'_, str> {
+    if !s.contains('\x1B') {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find("\x1B[") {
+        out.push_str(&rest[..i]);
+        let seq = &rest[i + 2..];
+        let end = seq
+            .find(|c| ('@'..='~').contains(&c))
+            .map_or(seq.len(), |e| e + 1);
+        if !seq[..end].ends_with('m') {
+            out.push_str(&rest[i..i + 2 + end]);
+        }
+        rest = &seq[end..];
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Replace the glyphs of the fallback table, and Nerd Font icons, with
+/// ASCII characters.
+pub fn to_ascii(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match ASCII_FALLBACK.iter().find(|&&(g, _)| g == c) {
+            Some(&(_, a)) => out.push_str(a),
+            None if is_private_use(c) => out.push('*'),
+            None => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+// skip_escape consumes an escape sequence, the leading ESC already taken.
+fn skip_escape(chars: &mut ::std::str::Chars) {
+    match chars.clone().next() {
+        // CSI: parameters, then a final byte in @..~
+        Some('[') => {
+            chars.next();
+            for c in chars {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+        // two-character sequence, e.g. ESC 7
+        Some(_) => {
+            chars.next();
+        }
+        None => {}
+    }
+}
+
+fn char_width(c: char) -> usize {
+    if is_private_use(c) {
+        1
+    } else {
+        c.width().unwrap_or(0)
+    }
+}
+
+fn is_private_use(c: char) -> bool {
+    matches!(c as u32, 0xE000..=0xF8FF | 0xF0000..=0x10FFFD)
+}
+
+#[cfg(test)]
+mod test {
+    use glyph::{strip, strip_colors, to_ascii, truncate, width, wrap};
+
+    #[test]
+    fn widths() {
+        assert_eq!(width("abc"), 3);
+        assert_eq!(width("✅ done"), 7);
+        assert_eq!(width("\x1B[1mbold\x1B[0m"), 4);
+        assert_eq!(width("\u{f115} dir"), 5);
+        assert_eq!(width("e\u{301}"), 1);
+        assert_eq!(strip("\r\x1B[7m✅ ok\x1B[0m\x1B7"), "✅ ok");
+        assert_eq!(
+            strip_colors("\r\x1B[38;5;208mok\x1B[0m\x1B[1A \x1B[2K"),
+            "\rok\x1B[1A \x1B[2K"
+        );
+    }
+
+    #[test]
+    fn truncation() {
+        assert_eq!(truncate("abc", 2), "ab");
+        assert_eq!(truncate("✅✅", 3), "✅");
+        assert_eq!(truncate("\x1B[1mab\x1B[0m", 1), "\x1B[1ma");
+        assert_eq!(truncate("abc", 5), "abc");
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(wrap("abcdefg", 3), ["abc", "def", "g"]);
+        assert_eq!(wrap("ab✅✅", 3), ["ab", "✅", "✅"]);
+        assert_eq!(wrap("abc", 3), ["abc"]);
+        assert_eq!(wrap("✅", 1), ["✅"]);
+    }
+
+    #[test]
+    fn ascii() {
+        assert_eq!(to_ascii("✅ ok ❌ \u{f115}"), "+ ok x *");
+        assert_eq!(to_ascii("╢▌▌░╟"), "[##-]");
+        assert!(matches!(to_ascii("plain"), ::std::borrow::Cow::Borrowed(_)));
+    }
+}
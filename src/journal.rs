@@ -0,0 +1,144 @@
+//! Append-only progress journal, see `ProgressBar::journal_to`.
+//!
+//! Every change of the bar is a line `<unix time in ms> <op> <n>`, where op
+//! is `total`, `add` or `set`. A last line without a newline was cut by a
+//! crash, it's ignored on replay and dropped before appending again.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug)]
+pub(crate) struct Journal {
+    file: File,
+    // the first failed write, after which nothing is recorded
+    error: Option<io::Error>,
+}
+
+impl Journal {
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> io::Result<Journal> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        // a line cut by a crash would be glued to the next one
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        let complete = buf.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        if complete < buf.len() {
+            file.set_len(complete as u64)?;
+        }
+        Ok(Journal { file, error: None })
+    }
+
+    // record appends a line, in a single write. A failed write is kept, and
+    // stops the journal.
+    pub(crate) fn record(&mut self, op: &str, n: u64) {
+        if self.error.is_some() {
+            return;
+        }
+        let ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        if let Err(e) = self
+            .file
+            .write_all(format!("{} {} {}\n", ms, op, n).as_bytes())
+        {
+            self.error = Some(e);
+        }
+    }
+
+    // error returns the write that stopped the journal, if any.
+    pub(crate) fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    // into_result returns the write that stopped the journal as an error.
+    pub(crate) fn into_result(self) -> io::Result<Journal> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self),
+        }
+    }
+}
+
+// replay returns the total and the current value recorded in the journal.
+pub(crate) fn replay<P: AsRef<Path>>(path: P) -> io::Result<(u64, u64)> {
+    let mut s = String::new();
+    File::open(path)?.read_to_string(&mut s)?;
+    let complete = match s.rfind('\n') {
+        Some(i) => &s[..i],
+        None => "",
+    };
+    let (mut total, mut current) = (0, 0);
+    for (i, line) in complete.lines().enumerate() {
+        let mut fields = line.split(' ').skip(1);
+        let op = fields.next();
+        let n = fields.next().and_then(|n| n.parse::<u64>().ok());
+        match (op, n) {
+            (Some("total"), Some(n)) => total = n,
+            (Some("add"), Some(n)) => current += n,
+            (Some("set"), Some(n)) => current = n,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid journal line {}: {:?}", i + 1, line),
+                ))
+            }
+        }
+    }
+    Ok((total, current))
+}
+
+#[cfg(test)]
+mod test {
+    use journal::{replay, Journal};
+    use std::fs::{remove_file, File, OpenOptions};
+    use std::io::Write;
+    use std::process;
+
+    #[test]
+    fn replay_journal() {
+        let path = ::std::env::temp_dir().join(format!("pbr-journal-{}", process::id()));
+        let _ = remove_file(&path);
+        {
+            let mut j = Journal::open(&path).unwrap();
+            j.record("total", 100);
+            j.record("add", 10);
+            j.record("add", 5);
+        }
+        assert_eq!(replay(&path).unwrap(), (100, 15));
+        {
+            let mut j = Journal::open(&path).unwrap();
+            j.record("set", 40);
+            j.record("add", 2);
+        }
+        // a crash in the middle of a write
+        let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+        f.write_all(b"1 add 1").unwrap();
+        assert_eq!(replay(&path).unwrap(), (100, 42));
+        // resumed after the crash
+        {
+            let mut j = Journal::open(&path).unwrap();
+            j.record("add", 2);
+            assert!(j.error().is_none());
+        }
+        assert_eq!(replay(&path).unwrap(), (100, 44));
+
+        f.write_all(b"0\n1 nope 2\n").unwrap();
+        assert!(replay(&path).is_err());
+
+        // a failed write stops the journal
+        let mut j = Journal {
+            file: File::open(&path).unwrap(),
+            error: None,
+        };
+        j.record("add", 1);
+        assert!(j.error().is_some());
+        assert!(j.into_result().is_err());
+        remove_file(&path).unwrap();
+    }
+}
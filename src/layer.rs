@@ -0,0 +1,242 @@
+//! A `tracing` layer showing the spans as bars, see `ProgressLayer`.
+
+use multi::{MultiBarHandle, Pipe, WeakHandle};
+use pb::{ProgressBar, ZeroTotalPolicy};
+use std::fmt::{self, Write};
+use std::sync::Mutex;
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record};
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// ProgressLayer is a `tracing` layer that shows the spans as bars of a
+/// listening `MultiBar`: a bar is created when a span is entered the first
+/// time, and finishes when the span closes. The events are printed above
+/// the bars, as `LEVEL target: message field=value`. It's enabled with the
+/// `tracing` feature.
+///
+/// A bar is a spinner, unless the span has a `total` field. Its position is
+/// the `pos` field, recorded with `Span::record`.
+///
+/// The layer doesn't keep the `MultiBar` listening, the events are printed
+/// to stderr once it's done.
+///
+/// # Examples
+///
+/// ```no_run
+/// #[macro_use]
+/// extern crate tracing;
+/// extern crate tracing_subscriber;
+/// extern crate pbr;
+///
+/// use pbr::{MultiBar, ProgressLayer};
+/// use tracing_subscriber::prelude::*;
+///
+/// fn main() {
+///     let mb = MultiBar::new();
+///     tracing_subscriber::registry()
+///         .with(ProgressLayer::new(mb.handle()))
+///         .init();
+///     mb.scope(|_| {
+///         let files = ["a.txt", "b.txt"];
+///         let span = info_span!("upload", total = files.len() as u64, pos = 0u64);
+///         let _enter = span.enter();
+///         for (i, f) in files.iter().enumerate() {
+///             info!(file = f, "uploading");
+///             // ...
+///             span.record("pos", i as u64 + 1);
+///         }
+///     });
+/// }
+/// ```
+pub struct ProgressLayer {
+    handle: WeakHandle,
+}
+
+impl ProgressLayer {
+    pub fn new(handle: MultiBarHandle) -> ProgressLayer {
+        ProgressLayer {
+            handle: handle.downgrade(),
+        }
+    }
+}
+
+impl fmt::Debug for ProgressLayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProgressLayer").finish()
+    }
+}
+
+// SpanBar is the bar of a span, kept in the span extensions.
+#[derive(Default)]
+struct SpanBar {
+    total: Option<u64>,
+    pos: Option<u64>,
+    bar: Option<ProgressBar<Pipe>>,
+}
+
+impl SpanBar {
+    // update shows the fields on the bar.
+    fn update(&mut self) {
+        if let Some(ref mut bar) = self.bar {
+            if let Some(total) = self.total {
+                bar.set_total(total);
+            }
+            match self.pos {
+                Some(pos) => {
+                    bar.set(pos);
+                }
+                None => {
+                    bar.tick();
+                }
+            }
+        }
+    }
+}
+
+impl Visit for SpanBar {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "total" => self.total = Some(value),
+            "pos" => self.pos = Some(value),
+            _ => {}
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if value >= 0 {
+            self.record_u64(field, value as u64);
+        }
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn fmt::Debug) {}
+}
+
+// Line formats the fields of an event, the message first.
+#[derive(Default)]
+struct Line {
+    message: String,
+    fields: String,
+}
+
+impl Visit for Line {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+}
+
+impl<S> Layer<S> for ProgressLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes, id: &Id, ctx: Context<S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut bar = SpanBar::default();
+            attrs.record(&mut bar);
+            span.extensions_mut().insert(Mutex::new(bar));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record, ctx: Context<S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(bar) = span.extensions().get::<Mutex<SpanBar>>() {
+                let mut bar = bar.lock().unwrap_or_else(|e| e.into_inner());
+                values.record(&mut *bar);
+                bar.update();
+            }
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        let extensions = span.extensions();
+        let mut bar = match extensions.get::<Mutex<SpanBar>>() {
+            Some(bar) => bar.lock().unwrap_or_else(|e| e.into_inner()),
+            None => return,
+        };
+        if bar.bar.is_none() {
+            let handle = match self.handle.upgrade() {
+                Some(handle) => handle,
+                None => return,
+            };
+            let mut pb = handle.create_bar(0);
+            pb.set_zero_total_policy(ZeroTotalPolicy::Spinner);
+            pb.message(format!("{} ", span.name()));
+            bar.bar = Some(pb);
+        }
+        bar.update();
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<S>) {
+        if let Some(span) = ctx.span(&id) {
+            if let Some(bar) = span.extensions_mut().remove::<Mutex<SpanBar>>() {
+                let bar = bar.into_inner().unwrap_or_else(|e| e.into_inner());
+                if let Some(pb) = bar.bar {
+                    pb.finish();
+                }
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event, _: Context<S>) {
+        let mut line = Line::default();
+        event.record(&mut line);
+        let meta = event.metadata();
+        let text = format!(
+            "{:<5} {}: {}{}",
+            meta.level(),
+            meta.target(),
+            line.message,
+            line.fields
+        );
+        if let Err(text) = self.handle.printer().println(text) {
+            eprintln!("{}", text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use layer::ProgressLayer;
+    use multi::MultiBar;
+    use testing::Output;
+    use tracing;
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn spans_and_events() {
+        let out = Output::default();
+        let mb = MultiBar::on(out.clone());
+        let subscriber = registry().with(ProgressLayer::new(mb.handle()));
+        tracing::subscriber::with_default(subscriber, || {
+            mb.scope(|_| {
+                let span = tracing::info_span!("upload", total = 4u64, pos = 0u64);
+                let _enter = span.enter();
+                tracing::warn!(file = "a.txt", "slow disk");
+                span.record("pos", 3u64);
+            });
+        });
+        let out = out.text();
+        let above = out
+            .find("\rWARN  pbr::layer::test: slow disk file=a.txt\n")
+            .expect("event printed above the bars");
+        assert!(out[above..].contains("upload 3 / 4"), "{:?}", out);
+    }
+}
@@ -104,20 +104,99 @@
 // printfl!(w, "\r{}", out);
 //
 // ```
+// The frame is passed in a single `write()` call on purpose, `Pipe` forwards
+// every call as one message to its `MultiBar` (an empty write clears the line).
 macro_rules! printfl {
    ($w:expr, $($tt:tt)*) => {{
+        #[allow(clippy::unused_io_amount)]
         $w.write(&format!($($tt)*).as_bytes()).ok().expect("write() fail");
         $w.flush().ok().expect("flush() fail");
     }}
 }
 
 extern crate time;
+extern crate unicode_width;
+#[cfg(feature = "crossbeam")]
+#[macro_use]
+extern crate crossbeam_channel;
+#[cfg(feature = "webhook")]
+extern crate ureq;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "futures")]
+extern crate futures;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "opentelemetry")]
+extern crate opentelemetry;
+#[cfg(feature = "log")]
+extern crate log;
+#[cfg(feature = "tracing")]
+extern crate tracing_core;
+#[cfg(feature = "tracing")]
+extern crate tracing_subscriber;
+#[cfg(all(test, feature = "tracing"))]
+extern crate tracing;
+mod adder;
+mod chan;
+pub mod child;
+mod downloads;
+mod estimator;
+mod glyph;
+mod journal;
+#[cfg(feature = "tracing")]
+mod layer;
+#[cfg(feature = "log")]
+mod logger;
 mod tty;
 mod pb;
 mod multi;
-pub use pb::{ProgressBar, Units};
-pub use multi::{MultiBar, Pipe};
+mod nonblocking;
+mod notify;
+#[cfg(feature = "rayon")]
+mod par;
+pub mod parent;
+mod shared;
+mod sink;
+mod status;
+#[cfg(feature = "futures")]
+mod stream;
+mod style;
+mod template;
+pub mod testing;
+mod wrap;
+pub use adder::{Adder, AdderRead, AdderWrite, CancelHandle};
+pub use downloads::{Downloads, Transfer};
+#[cfg(feature = "log")]
+pub use logger::LogBridge;
+#[cfg(feature = "tracing")]
+pub use layer::ProgressLayer;
+pub use estimator::{eta, DoubleExponential, Estimator, Exponential, Linear, SlidingWindow};
+pub use pb::{BarLabel, BytePrefix, Direction, ExactBytes, FinishBehavior, FormatError, Heartbeat, Layout, ProgressBar, Row, Units,
+             UnitsHint, Warmup, WidthSource, ZeroTotalPolicy};
+pub use multi::{Frame, FrameRow, FrameStats, ListenTimeout, MultiBar, MultiBarHandle,
+                Pagination, Pipe, RowState, Scope, StdStream};
+pub use nonblocking::NonBlocking;
+pub use notify::{Milestone, Notifier};
+#[cfg(feature = "webhook")]
+pub use notify::Webhook;
+#[cfg(feature = "opentelemetry")]
+pub use notify::SpanNotifier;
+#[cfg(feature = "rayon")]
+pub use par::{ParProgress, ParallelProgressIterator};
+pub use shared::SharedBar;
+pub use sink::{FnSink, NoProgress, ProgressEvent, ProgressSink};
+pub use status::StatusLine;
+#[cfg(feature = "futures")]
+pub use stream::{PbStream, ProgressStream};
+pub use style::{Color, Paint, Style};
+pub use template::TemplateError;
+pub use wrap::{ProgressReader, ProgressWriter};
+#[cfg(feature = "tokio")]
+pub use wrap::{ProgressAsyncReader, ProgressAsyncWriter};
+use std::fmt;
 use std::io::{Write, Stdout, stdout};
+use std::ops::{Range, RangeInclusive};
 
 pub struct PbIter<T, I>
     where I: Iterator,
@@ -142,7 +221,7 @@ impl<T, I> PbIter<T, I>
     pub fn on(handle: T, iter: I) -> Self {
         let size = iter.size_hint().0;
         PbIter {
-            iter: iter,
+            iter,
             progress_bar: ProgressBar::on(handle, size as u64),
         }
     }
@@ -168,3 +247,153 @@ impl<T, I> Iterator for PbIter<T, I>
         self.iter.size_hint()
     }
 }
+
+/// Adds `progress` and `progress_with` to iterators.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pbr::ProgressIterator;
+///
+/// let files = vec!["a.txt", "b.txt"];
+/// for f in files.iter().progress() {
+///     // ...
+/// #   let _ = f;
+/// }
+/// ```
+pub trait ProgressIterator: Iterator + Sized {
+    /// Wrap the iterator in a `PbIter` on stdout, the bar total is the
+    /// length of the iterator.
+    fn progress(self) -> PbIter<Stdout, Self>
+        where Self: ExactSizeIterator
+    {
+        let total = self.len() as u64;
+        self.progress_with(ProgressBar::new(total))
+    }
+
+    /// Wrap the iterator in a `PbIter` with a configured bar.
+    fn progress_with<T: Write>(self, pb: ProgressBar<T>) -> PbIter<T, Self> {
+        PbIter {
+            iter: self,
+            progress_bar: pb,
+        }
+    }
+}
+
+impl<I: Iterator> ProgressIterator for I {}
+
+/// Create a `PbIter` over a range of integers, the bar total is the
+/// number of values in the range.
+///
+/// # Examples
+///
+/// ```no_run
+/// for i in pbr::range(0..1_000_000) {
+///     // ...
+/// #   let _ = i;
+/// }
+/// ```
+pub fn range<R: ProgressRange>(range: R) -> PbIter<Stdout, R> {
+    let total = range.total();
+    PbIter {
+        iter: range,
+        progress_bar: ProgressBar::new(total),
+    }
+}
+
+/// Ranges accepted by `range`.
+pub trait ProgressRange: Iterator {
+    /// Number of values in the range.
+    fn total(&self) -> u64;
+}
+
+macro_rules! impl_progress_range {
+    ($($t:ty),*) => {$(
+        impl ProgressRange for Range<$t> {
+            fn total(&self) -> u64 {
+                (self.end as i128 - self.start as i128).max(0) as u64
+            }
+        }
+
+        impl ProgressRange for RangeInclusive<$t> {
+            fn total(&self) -> u64 {
+                if self.is_empty() {
+                    return 0;
+                }
+                (*self.end() as i128 - *self.start() as i128 + 1).min(u64::MAX as i128) as u64
+            }
+        }
+    )*}
+}
+
+impl_progress_range!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+/// Like `println!`, but prints above the lines of the listening `MultiBar`
+/// instead of garbling them, see `MultiBarHandle::println_above`. Without a
+/// listening `MultiBar`, it's `println!`.
+///
+/// The text goes to stdout, through the `MultiBar` if it draws there, see
+/// `MultiBar::set_std_stream`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #[macro_use]
+/// extern crate pbr;
+///
+/// use std::thread;
+/// use pbr::MultiBar;
+///
+/// fn main() {
+///     let mut mb = MultiBar::new();
+///     let mut pb = mb.create_bar(100);
+///     thread::spawn(move || {
+///         for i in 0..100 {
+///             if i == 50 {
+///                 pb_println!("halfway there: {}", i);
+///             }
+///             pb.inc();
+///         }
+///         pb.finish();
+///     });
+///     mb.listen();
+/// }
+/// ```
+#[macro_export]
+macro_rules! pb_println {
+    () => {
+        $crate::__print(format_args!(""), false)
+    };
+    ($($arg:tt)*) => {
+        $crate::__print(format_args!($($arg)*), false)
+    };
+}
+
+/// Like `eprintln!`, but prints to stderr above the lines of the listening
+/// `MultiBar`, see `pb_println!`.
+#[macro_export]
+macro_rules! pb_eprintln {
+    () => {
+        $crate::__print(format_args!(""), true)
+    };
+    ($($arg:tt)*) => {
+        $crate::__print(format_args!($($arg)*), true)
+    };
+}
+
+// __print is used by `pb_println!` and `pb_eprintln!`.
+#[doc(hidden)]
+pub fn __print(args: fmt::Arguments, stderr: bool) {
+    let stream = if stderr {
+        StdStream::Stderr
+    } else {
+        StdStream::Stdout
+    };
+    if let Err(text) = multi::println_listening(args.to_string(), stream) {
+        if stderr {
+            eprintln!("{}", text);
+        } else {
+            println!("{}", text);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! A `log` logger that prints above the bars, see `LogBridge`.
+
+use log::{self, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use multi::MultiBarHandle;
+use shared::SharedBar;
+use std::fmt;
+use std::io::Write;
+
+type Print = Box<dyn Fn(String) + Send + Sync>;
+
+/// LogBridge is a `log` logger that prints the records above a bar or the
+/// lines of a `MultiBar`, instead of between two redraws where they would
+/// garble the screen. It's enabled with the `log` feature.
+///
+/// Records are printed as `LEVEL target: message`.
+///
+/// # Examples
+///
+/// ```no_run
+/// #[macro_use]
+/// extern crate log;
+/// extern crate pbr;
+///
+/// use std::thread;
+/// use pbr::{LogBridge, MultiBar};
+///
+/// fn main() {
+///     let mut mb = MultiBar::new();
+///     LogBridge::multi(mb.handle()).init().unwrap();
+///     let mut pb = mb.create_bar(100);
+///     thread::spawn(move || {
+///         for i in 0..100 {
+///             if i == 50 {
+///                 warn!("halfway there");
+///             }
+///             pb.inc();
+///         }
+///         pb.finish();
+///     });
+///     mb.listen();
+/// }
+/// ```
+pub struct LogBridge {
+    print: Print,
+    level: LevelFilter,
+}
+
+impl LogBridge {
+    /// Print above the lines of a listening `MultiBar`, see
+    /// `MultiBarHandle::println_above`. The bridge doesn't keep the
+    /// `MultiBar` listening, the records are printed to stderr once it's
+    /// done.
+    pub fn multi(handle: MultiBarHandle) -> LogBridge {
+        let printer = handle.printer();
+        LogBridge::new(Box::new(move |line| {
+            if let Err(line) = printer.println(line) {
+                eprintln!("{}", line);
+            }
+        }))
+    }
+
+    /// Print above a bar, see `ProgressBar::println`.
+    pub fn bar<T: Write + Send + 'static>(bar: SharedBar<T>) -> LogBridge {
+        LogBridge::new(Box::new(move |line| bar.println(&line)))
+    }
+
+    fn new(print: Print) -> LogBridge {
+        LogBridge {
+            print,
+            level: LevelFilter::Info,
+        }
+    }
+
+    /// Set the most verbose level logged, default is `Info`.
+    pub fn with_level(mut self, level: LevelFilter) -> LogBridge {
+        self.level = level;
+        self
+    }
+
+    /// Set the bridge as the logger of the program.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+impl Log for LogBridge {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        (self.print)(format!(
+            "{:<5} {}: {}",
+            record.level(),
+            record.target(),
+            record.args()
+        ));
+    }
+
+    fn flush(&self) {}
+}
+
+impl fmt::Debug for LogBridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LogBridge")
+            .field("level", &self.level)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::{Level, LevelFilter, Log, Record};
+    use logger::LogBridge;
+    use multi::MultiBar;
+    use pb::ProgressBar;
+    use shared::SharedBar;
+    use testing::Output;
+
+    #[test]
+    fn above_bar() {
+        let out = Output::default();
+        let mut pb = ProgressBar::on(out.clone(), 10);
+        pb.set_width(Some(20));
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.show_percent = false;
+        pb.add(2);
+        let bridge = LogBridge::bar(SharedBar::new(pb)).with_level(LevelFilter::Warn);
+        let log = |level, msg| {
+            bridge.log(
+                &Record::builder()
+                    .args(format_args!("{}", msg))
+                    .level(level)
+                    .target("app")
+                    .build(),
+            )
+        };
+        log(Level::Info, "ignored");
+        log(Level::Warn, "disk almost full");
+        let out = out.text();
+        assert_eq!(
+            out,
+            "\r2 / 10 [=>--------] \rWARN  app: disk almost full\n\r2 / 10 [=>--------] "
+        );
+    }
+
+    #[test]
+    fn multi_done() {
+        let mut mb = MultiBar::on(Output::default());
+        let bridge = LogBridge::multi(mb.handle());
+        let mut pb = mb.create_bar(1);
+        pb.inc();
+        pb.finish();
+        // the bridge doesn't keep the MultiBar listening
+        mb.listen();
+        bridge.log(
+            &Record::builder()
+                .args(format_args!("after the bars"))
+                .level(Level::Info)
+                .build(),
+        );
+    }
+}
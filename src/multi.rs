@@ -1,10 +1,24 @@
-use pb::ProgressBar;
-use std::io::{Result, Stdout, Write};
+use chan::{self, recv_deadline, Receiver, Recv, Sender};
+use glyph;
+use pb::{ProgressBar, ZeroTotalPolicy};
+use shared::SharedBar;
+use std::any::type_name;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::io::{stderr, stdout, IsTerminal, Result, Stdout, Write};
 use std::iter::repeat;
+use std::mem;
+use std::ops::Range;
 use std::str::from_utf8;
-use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
-use tty::move_cursor_up;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+use style::Style;
+use time;
+use tty::{colors_enabled, move_cursor_up, terminal_size, Height, Width};
 
 macro_rules! repeat {
     ($s: expr, $n: expr) => {{
@@ -19,7 +33,27 @@ pub struct MultiBar<T: Write> {
 
     nbars: usize,
 
-    chan: (Sender<WriteMsg>, Receiver<WriteMsg>),
+    // the sender is shared with the bars and handles, see `Printer`
+    chan: (Arc<Sender<Msg>>, Receiver<Msg>),
+
+    style: Option<Style>,
+
+    settings: Settings,
+
+    // messages read by `render_snapshot`, for the renderer
+    pending: Vec<Msg>,
+
+    // lines changed since the last `take_dirty`
+    dirty: BTreeSet<usize>,
+
+    // the next id of the lines created through the handles
+    handle_ids: Arc<AtomicUsize>,
+
+    // the width of the widest verb of the bars, see `ProgressBar::set_verb`
+    verbs: Arc<AtomicUsize>,
+
+    // the bars created by name, see `bar`
+    named: Named,
 
     handle: T,
 }
@@ -66,13 +100,56 @@ impl MultiBar<Stdout> {
     /// mb.listen();
     /// ```
     pub fn new() -> MultiBar<Stdout> {
-        MultiBar::on(::std::io::stdout())
+        let mut mb = MultiBar::on_terminal(stdout());
+        mb.set_std_stream(Some(StdStream::Stdout));
+        mb
+    }
+}
+
+impl Default for MultiBar<Stdout> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Write + IsTerminal> MultiBar<T> {
+    /// Create a MultiBar on a handle that may be a terminal, e.g.
+    /// `stderr()`: colors are drawn only if it is one, see `set_colors`,
+    /// and it takes turns with the other MultiBars on the terminal, see
+    /// `set_shared_terminal`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    /// use std::io::stderr;
+    ///
+    /// let mut mb = MultiBar::on_terminal(stderr());
+    /// let mut pb = mb.create_bar(100);
+    /// // ...
+    /// # pb.finish();
+    /// mb.listen();
+    /// ```
+    pub fn on_terminal(handle: T) -> MultiBar<T> {
+        let terminal = handle.is_terminal();
+        let mut mb = MultiBar::on(handle);
+        mb.set_colors(terminal);
+        mb.set_shared_terminal(terminal);
+        mb
     }
 }
 
 impl<T: Write> MultiBar<T> {
     /// Create a new MultiBar with an arbitrary writer.
     ///
+    /// The writer isn't known to be a terminal, so no colors are drawn,
+    /// see `on_terminal` and `set_colors`.
+    ///
+    /// MultiBars on different writers are independent, e.g. one on stderr
+    /// and one into a file, each keeps track of its own lines. MultiBars
+    /// drawing on the same terminal would overwrite each other, unless they
+    /// take turns, see `set_shared_terminal`.
+    ///
     /// # Examples
     ///
     /// ```ignore
@@ -85,18 +162,27 @@ impl<T: Write> MultiBar<T> {
     /// // ...
     /// ```
     pub fn on(handle: T) -> MultiBar<T> {
+        let (tx, rx) = chan::channel();
         MultiBar {
             nlines: 0,
             nbars: 0,
             lines: Vec::new(),
-            chan: mpsc::channel(),
-            handle: handle,
+            chan: (Arc::new(tx), rx),
+            style: None,
+            settings: Settings::new(),
+            pending: Vec::new(),
+            dirty: BTreeSet::new(),
+            handle_ids: Arc::new(AtomicUsize::new(HANDLE_IDS)),
+            verbs: Arc::new(AtomicUsize::new(0)),
+            named: Named::default(),
+            handle,
         }
     }
 
     /// println used to add text lines between the bars.
     /// for example: you could add a header to your application,
-    /// or text separators between bars.
+    /// or text separators between bars. A line wider than the terminal
+    /// is wrapped over several rows.
     ///
     /// # Examples
     ///
@@ -121,8 +207,9 @@ impl<T: Write> MultiBar<T> {
     /// // ...
     /// mb.listen();
     /// ```
-    pub fn println(&mut self, s: &str) {
-        self.lines.push(s.to_owned());
+    pub fn println<S: Into<Cow<'static, str>>>(&mut self, s: S) {
+        self.lines.push(s.into().into_owned());
+        self.dirty.insert(self.nlines);
         self.nlines += 1;
     }
 
@@ -135,6 +222,8 @@ impl<T: Write> MultiBar<T> {
     /// ProgressBar that finish its work, must call `finish()` (or `finish_print`)
     /// to notify the `MultiBar` about it.
     ///
+    /// To add bars while listening, see `MultiBarHandle::create_bar`.
+    ///
     /// # Examples
     ///
     /// ```ignore
@@ -160,16 +249,395 @@ impl<T: Write> MultiBar<T> {
     pub fn create_bar(&mut self, total: u64) -> ProgressBar<Pipe> {
         self.println("");
         self.nbars += 1;
-        let mut p = ProgressBar::on(
-            Pipe {
-                level: self.nlines - 1,
-                chan: self.chan.0.clone(),
-            },
+        new_bar(
+            self.nlines - 1,
+            &self.chan.0,
+            self.style.as_ref(),
+            &self.verbs,
             total,
-        );
-        p.is_multibar = true;
-        p.add(0);
-        p
+        )
+    }
+
+    /// bar returns the bar named `name`, created on first use, so distant
+    /// parts of an application can update a bar by name instead of passing
+    /// it around. See `MultiBarHandle::bar` to create them while listening.
+    ///
+    /// A named bar is a spinner until its total is set. It must be finished,
+    /// `listen` waits for it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// mb.bar("fetch-shard-07").set_total(100);
+    /// // ...
+    /// mb.bar("fetch-shard-07").inc();
+    /// ```
+    pub fn bar<S: AsRef<str>>(&mut self, name: S) -> SharedBar<Pipe> {
+        let named = self.named.clone();
+        let mut named = named.lock().unwrap_or_else(|e| e.into_inner());
+        named
+            .entry(name.as_ref().to_owned())
+            .or_insert_with(|| named_bar(self.create_bar(0)))
+            .clone()
+    }
+
+    /// set_default_bar_style sets the `Style` applied to the bars created
+    /// afterwards by `create_bar` (or `Scope::bar`). Each bar can still be
+    /// configured on its own.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{MultiBar, Style};
+    ///
+    /// let mut style = Style::new();
+    /// style.format("|#--|").unwrap();
+    ///
+    /// let mut mb = MultiBar::new();
+    /// mb.set_default_bar_style(style);
+    /// let p1 = mb.create_bar(100);
+    /// ```
+    pub fn set_default_bar_style(&mut self, style: Style) {
+        self.style = Some(style);
+    }
+
+    /// set_pagination sets how the lines are paginated when they don't fit
+    /// in the terminal, default is `Pagination::Rotate` every 3 seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{MultiBar, Pagination};
+    ///
+    /// let mut mb = MultiBar::new();
+    /// mb.set_pagination(Pagination::Active);
+    /// ```
+    pub fn set_pagination(&mut self, pagination: Pagination) {
+        self.settings.pagination = pagination;
+    }
+
+    /// handle returns a `MultiBarHandle`, used to control the rendering
+    /// and to add bars while listening.
+    ///
+    /// `listen` doesn't return while a handle is alive, so drop all handles
+    /// once done.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::thread;
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// let handle = mb.handle();
+    ///
+    /// // ...
+    /// // create some bars here
+    /// // ...
+    ///
+    /// thread::spawn(move || mb.listen());
+    ///
+    /// handle.pause();
+    /// // ... ask the user something
+    /// handle.resume();
+    /// drop(handle);
+    /// ```
+    pub fn handle(&self) -> MultiBarHandle {
+        MultiBarHandle {
+            chan: self.chan.0.clone(),
+            ids: self.handle_ids.clone(),
+            style: self.style.clone(),
+            verbs: self.verbs.clone(),
+            named: self.named.clone(),
+        }
+    }
+
+    // set_terminal_size replaces the size of the terminal, e.g. by a
+    // simulated one, see `testing::Terminal`.
+    pub(crate) fn set_terminal_size(&mut self, size: TermSize) {
+        self.settings.size = Some(size);
+    }
+
+    // into_pump renders the changes synchronously, instead of listening in a
+    // thread, see `Pump`.
+    pub(crate) fn into_pump(self) -> Pump<T> {
+        let handle = self.handle();
+        let MultiBar {
+            lines,
+            chan: (tx, rx),
+            settings,
+            pending,
+            handle: w,
+            ..
+        } = self;
+        drop(tx);
+        Pump {
+            renderer: Renderer::new(lines, pending, settings, w),
+            rx,
+            handle,
+        }
+    }
+
+    /// render_snapshot returns the lines of the `MultiBar` as they are now,
+    /// without escape sequences, e.g. to embed the progress in a status
+    /// email or a debug dump.
+    ///
+    /// A `MultiBar` that never listens, e.g. in a server without a
+    /// terminal, can be snapshotted periodically instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::on(Vec::new());
+    /// mb.println("Backups:");
+    /// let mut p1 = mb.create_bar(100);
+    /// p1.inc();
+    /// println!("{}", mb.render_snapshot());
+    /// ```
+    pub fn render_snapshot(&mut self) -> String {
+        self.pump();
+        self.lines
+            .iter()
+            .map(|l| glyph::strip(l))
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.trim_end().to_owned())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// set_frame_observer sets a function called with the state of all the
+    /// lines every time they are drawn, or a bar finishes, e.g. to mirror
+    /// the progress in a GUI window.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut mb = MultiBar::new();
+    /// mb.set_frame_observer(move |frame| {
+    ///     let _ = gui_tx.send(frame.clone());
+    /// });
+    /// ```
+    pub fn set_frame_observer<F>(&mut self, f: F)
+    where
+        F: FnMut(&Frame) + Send + 'static,
+    {
+        self.settings.observer = Some(Box::new(f));
+    }
+
+    /// set_max_fps caps the number of frames drawn per second, 0 for no cap.
+    ///
+    /// Changes arriving faster are coalesced into the next frame, so a big
+    /// dashboard doesn't flood the terminal however often its bars draw,
+    /// see `frame_stats` for the number of frames skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// mb.set_max_fps(20);
+    /// ```
+    pub fn set_max_fps(&mut self, fps: u32) {
+        self.settings.max_fps = fps;
+    }
+
+    /// set_remove_on_finish removes the line of a bar once it finishes,
+    /// keeping only the running bars, and the last finished ones if set with
+    /// `set_keep_finished`. Otherwise a finished bar leaves an empty line,
+    /// which isn't drawn but is kept until the `MultiBar` stops listening.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// mb.set_remove_on_finish(true);
+    /// mb.set_keep_finished(3);
+    /// ```
+    pub fn set_remove_on_finish(&mut self, remove: bool) {
+        self.settings.remove_on_finish = remove;
+    }
+
+    /// set_keep_finished keeps the last line drawn by the `n` last finished
+    /// bars on screen, with `set_remove_on_finish`. Default is 0.
+    pub fn set_keep_finished(&mut self, n: usize) {
+        self.settings.keep_finished = n;
+    }
+
+    /// set_timestamps adds a column on the left with the wall-clock time
+    /// each bar last reported, e.g. to spot the stalled tasks among dozens
+    /// of long ones.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// mb.set_timestamps(true);
+    /// ```
+    pub fn set_timestamps(&mut self, timestamps: bool) {
+        self.settings.timestamps = timestamps;
+    }
+
+    /// set_dim_idle draws dimmed the bars that haven't been updated for
+    /// `after`, until their next update, which draws the eye to the active
+    /// ones in a big `MultiBar`. Default is `None`, no bar is dimmed.
+    ///
+    /// Nothing is dimmed without colors, see `set_colors`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    /// use std::time::Duration;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// mb.set_dim_idle(Some(Duration::from_secs(30)));
+    /// ```
+    pub fn set_dim_idle(&mut self, after: Option<Duration>) {
+        self.settings.dim_idle = after;
+    }
+
+    /// set_colors draws the colors and attributes of the bars, if the
+    /// writer is a terminal, see `ProgressBar::set_colors`. It's detected by
+    /// `new` and `on_terminal`, and off for a writer passed to `on`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    /// use std::io::{stderr, IsTerminal};
+    ///
+    /// let mut mb = MultiBar::on(stderr().lock());
+    /// mb.set_colors(stderr().is_terminal());
+    /// ```
+    pub fn set_colors(&mut self, colors: bool) {
+        self.settings.colors = colors;
+    }
+
+    /// set_shared_terminal makes this MultiBar take turns with the other
+    /// ones on the terminal: while one listens, the others wait for it to
+    /// stop before drawing. It's set by `new` and `on_terminal` when the
+    /// writer is a terminal, and off for a writer passed to `on`.
+    ///
+    /// The terminal behind a writer isn't known, so all the MultiBars set
+    /// so take turns, even on different terminals, e.g. on stdout and on a
+    /// pty of another session.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    /// use std::io::stderr;
+    ///
+    /// // stderr, locked to write faster, is shared with `MultiBar::new()`
+    /// let mut mb = MultiBar::on(stderr().lock());
+    /// mb.set_shared_terminal(true);
+    /// ```
+    pub fn set_shared_terminal(&mut self, shared: bool) {
+        self.settings.shared_terminal = shared;
+    }
+
+    /// set_std_stream tells the standard stream the writer goes to, if any.
+    /// `pb_println!` and `pb_eprintln!` print through the writer when it
+    /// goes to their stream, otherwise they erase the lines, print to their
+    /// stream, and the lines are drawn again below. It's set to stdout by
+    /// `new`, and unknown for a writer passed to `on` or `on_terminal`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{MultiBar, StdStream};
+    /// use std::io::stderr;
+    ///
+    /// let mut mb = MultiBar::on_terminal(stderr());
+    /// mb.set_std_stream(Some(StdStream::Stderr));
+    /// ```
+    pub fn set_std_stream(&mut self, stream: Option<StdStream>) {
+        self.settings.std_stream = stream;
+    }
+
+    /// set_leave_rendered leaves the last frame on screen once the
+    /// `MultiBar` stops listening, with the cursor at the start of the row
+    /// below it, instead of erasing the lines.
+    ///
+    /// The last line of the finished bars is kept, whatever their
+    /// `FinishBehavior`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// mb.set_leave_rendered(true);
+    /// ```
+    pub fn set_leave_rendered(&mut self, leave: bool) {
+        self.settings.leave_rendered = leave;
+    }
+
+    /// frame_stats returns the counts of frames drawn and skipped, which
+    /// can be read from another thread while listening.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let stats = mb.frame_stats();
+    /// thread::spawn(move || mb.listen());
+    /// // ...
+    /// log::debug!("{} frames drawn, {} skipped", stats.drawn(), stats.skipped());
+    /// ```
+    pub fn frame_stats(&self) -> FrameStats {
+        self.settings.stats.clone()
+    }
+
+    /// take_dirty returns the lines changed since the last call, as their
+    /// row and their text without escape sequences, for applications that
+    /// paint the lines themselves in their own render loop instead of
+    /// listening.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut mb = MultiBar::on(std::io::sink());
+    /// let mut pb = mb.create_bar(100);
+    /// // ...
+    /// loop {
+    ///     for (row, text) in mb.take_dirty() {
+    ///         screen.draw_text(0, row, &text);
+    ///     }
+    ///     screen.present();
+    /// }
+    /// ```
+    pub fn take_dirty(&mut self) -> Vec<(usize, String)> {
+        self.pump();
+        let dirty = mem::take(&mut self.dirty);
+        dirty
+            .into_iter()
+            .map(|row| (row, glyph::strip(&self.lines[row]).trim_end().to_owned()))
+            .collect()
+    }
+
+    // pump reads the changes of the bars without listening, the other
+    // messages are kept for the renderer.
+    fn pump(&mut self) {
+        while let Ok(msg) = self.chan.1.try_recv() {
+            match msg {
+                Msg::Line { id, string } => {
+                    self.lines[id] = string;
+                    self.dirty.insert(id);
+                }
+                msg => self.pending.push(msg),
+            }
+        }
     }
 
     /// listen start listen to all bars changes.
@@ -181,6 +649,12 @@ impl<T: Write> MultiBar<T> {
     /// finish.
     /// To ignore blocking, you can run it in a different thread.
     ///
+    /// A bar dropped without `finish()` is marked as aborted, or removed if
+    /// it was never drawn, listen doesn't wait for it.
+    ///
+    /// Once done, the lines are erased and the cursor is left at the start
+    /// of the first one, or below the last one with `set_leave_rendered`.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -200,65 +674,1254 @@ impl<T: Write> MultiBar<T> {
     ///
     /// // ...
     /// ```
-    pub fn listen(mut self) {
-        drop(self.chan.0);
-
-        let mut nlines = 0;
-        let mut nblank_lines = 0;
-        let mut max_width = 0;
-        while let Ok(msg) = self.chan.1.recv() {
-            self.lines[msg.level] = msg.string;
-
-            // and draw
-            let mut out = String::new();
-            if nlines + nblank_lines > 0 {
-                out += &move_cursor_up(nlines + nblank_lines);
-            }
-
-            let mut new_nlines = 0;
-            for l in self.lines.iter() {
-                if l.len() > 0 {
-                    max_width = max_width.max(l.len());
-                    out.push_str(&format!("\r{}\n", l));
-                    new_nlines += 1;
-                }
-            }
+    pub fn listen(self) {
+        let MultiBar {
+            lines,
+            chan: (tx, rx),
+            settings,
+            pending,
+            handle,
+            ..
+        } = self;
+        Printer::listen(&tx, settings.std_stream);
+        drop(tx);
+        let _ = Renderer::new(lines, pending, settings, handle).run(rx, None);
+    }
 
-            nblank_lines = nlines - new_nlines.min(nlines);
-            nlines = new_nlines;
+    /// listen_with_timeout is like `listen`, but returns an error if no bar
+    /// made any change for `timeout`.
+    ///
+    /// This way, a crashed worker that never calls `finish()` (and keeps its
+    /// bar alive) can't hang the program. The error lists the unfinished
+    /// bars.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    ///
+    /// // ...
+    /// // create some bars here
+    /// // ...
+    ///
+    /// if let Err(e) = mb.listen_with_timeout(Duration::from_secs(60)) {
+    ///     eprintln!("{}", e);
+    /// }
+    /// ```
+    pub fn listen_with_timeout(
+        self,
+        timeout: Duration,
+    ) -> ::std::result::Result<(), ListenTimeout> {
+        let MultiBar {
+            lines,
+            chan: (tx, rx),
+            settings,
+            pending,
+            handle,
+            ..
+        } = self;
+        Printer::listen(&tx, settings.std_stream);
+        drop(tx);
+        Renderer::new(lines, pending, settings, handle).run(rx, Some(timeout))
+    }
 
-            for _ in 0..nblank_lines {
-                out.push_str(&format!("\r\r{}\n", repeat!(" ", max_width - 1)));
-            }
+    /// scope runs `f` while listening to all bars changes, and returns its
+    /// result.
+    ///
+    /// Bars and text lines are created in `f` through the `Scope`. Once `f`
+    /// returns, or panics, listening ends even if some bars didn't call `finish()`,
+    /// or are still alive somewhere else. Such bars don't draw anymore.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::thread;
+    /// use pbr::MultiBar;
+    ///
+    /// let mb = MultiBar::new();
+    /// mb.scope(|scope| {
+    ///     scope.println("Application header:");
+    ///     let mut p1 = scope.bar(100);
+    ///     let mut p2 = scope.bar(200);
+    ///     let t = thread::spawn(move || {
+    ///         for _ in 0..200 {
+    ///             p2.inc();
+    ///         }
+    ///     });
+    ///     for _ in 0..100 {
+    ///         p1.inc();
+    ///     }
+    ///     t.join().unwrap();
+    /// });
+    /// ```
+    pub fn scope<F, R>(self, f: F) -> R
+    where
+        F: FnOnce(&mut Scope) -> R,
+        T: Send,
+    {
+        let MultiBar {
+            nlines,
+            lines,
+            chan: (tx, rx),
+            style,
+            settings,
+            pending,
+            verbs,
+            handle,
+            ..
+        } = self;
+        Printer::listen(&tx, settings.std_stream);
 
-            printfl!(self.handle, "{}", out);
-        }
+        thread::scope(|s| {
+            s.spawn(move || Renderer::new(lines, pending, settings, handle).run(rx, None));
 
-        if nlines > 0 {
-            let mut out = String::new();
-            out += &move_cursor_up(nlines);
-            for _ in 0..nlines {
-                out.push_str(&format!("\r{}\n", repeat!(" ", max_width - 1)));
-            }
-            printfl!(self.handle, "{}", out);
-            printfl!(self.handle, "{}", move_cursor_up(nlines));
-        }
+            let mut scope = Scope {
+                nlines,
+                chan: tx,
+                style,
+                verbs,
+            };
+            // dropping the scope stops the renderer, also if `f` panics
+            f(&mut scope)
+        })
     }
 }
 
-pub struct Pipe {
-    level: usize,
-    chan: Sender<WriteMsg>,
+impl<T: Write> fmt::Debug for MultiBar<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MultiBar")
+            .field("lines", &self.lines.len())
+            .field("bars", &self.nbars)
+            .field("target", &type_name::<T>())
+            .finish()
+    }
+}
+
+// Display a short summary, e.g. `3 bars, 5 lines`.
+impl<T: Write> fmt::Display for MultiBar<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} bars, {} lines", self.nbars, self.lines.len())
+    }
+}
+
+/// Scope is used to create bars and text lines while the `MultiBar` is
+/// listening, see `MultiBar::scope`.
+pub struct Scope {
+    nlines: usize,
+    chan: Arc<Sender<Msg>>,
+    style: Option<Style>,
+    verbs: Arc<AtomicUsize>,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let _ = self.chan.send(Msg::Stop);
+    }
+}
+
+impl Scope {
+    /// println adds a text line after the last line of the `MultiBar`.
+    pub fn println<S: Into<Cow<'static, str>>>(&mut self, s: S) {
+        let _ = self.chan.send(Msg::Line {
+            id: self.nlines,
+            string: s.into().into_owned(),
+        });
+        self.nlines += 1;
+    }
+
+    /// bar creates new `ProgressBar` after the last line of the `MultiBar`,
+    /// see `MultiBar::create_bar`.
+    pub fn bar(&mut self, total: u64) -> ProgressBar<Pipe> {
+        self.nlines += 1;
+        new_bar(
+            self.nlines - 1,
+            &self.chan,
+            self.style.as_ref(),
+            &self.verbs,
+            total,
+        )
+    }
+}
+
+/// MultiBarHandle controls the rendering of a listening `MultiBar`, see
+/// `MultiBar::handle`.
+#[derive(Clone)]
+pub struct MultiBarHandle {
+    chan: Arc<Sender<Msg>>,
+    ids: Arc<AtomicUsize>,
+    style: Option<Style>,
+    verbs: Arc<AtomicUsize>,
+    named: Named,
+}
+
+// The bars of a MultiBar created by name.
+type Named = Arc<Mutex<HashMap<String, SharedBar<Pipe>>>>;
+
+// First id of the lines created through a `MultiBarHandle`, the ids below
+// are the levels of the lines created by the `MultiBar` itself.
+const HANDLE_IDS: usize = usize::MAX / 2;
+
+impl MultiBarHandle {
+    /// create_bar creates a new `ProgressBar`, even while the `MultiBar` is
+    /// listening, e.g. for tasks discovered along the way. It's drawn after
+    /// the lines created by the `MultiBar`, with the default bar style set
+    /// when the handle was created.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut mb = MultiBar::new();
+    /// let handle = mb.handle();
+    /// thread::spawn(move || {
+    ///     while let Some(task) = tasks.next() {
+    ///         let mut pb = handle.create_bar(task.len());
+    ///         task.run(&mut pb);
+    ///         pb.finish();
+    ///     }
+    /// });
+    /// mb.listen();
+    /// ```
+    pub fn create_bar(&self, total: u64) -> ProgressBar<Pipe> {
+        let id = self.ids.fetch_add(1, Ordering::Relaxed);
+        new_bar(id, &self.chan, self.style.as_ref(), &self.verbs, total)
+    }
+
+    /// bar returns the bar named `name`, created on first use, see
+    /// `MultiBar::bar`.
+    pub fn bar<S: AsRef<str>>(&self, name: S) -> SharedBar<Pipe> {
+        let mut named = self.named.lock().unwrap_or_else(|e| e.into_inner());
+        named
+            .entry(name.as_ref().to_owned())
+            .or_insert_with(|| named_bar(self.create_bar(0)))
+            .clone()
+    }
+
+    /// pause stops drawing, bars changes are still received and shown
+    /// after `resume`.
+    pub fn pause(&self) {
+        let _ = self.chan.send(Msg::Pause);
+    }
+
+    /// resume draws again after `pause`.
+    pub fn resume(&self) {
+        let _ = self.chan.send(Msg::Resume);
+    }
+
+    /// clear erases the lines of the `MultiBar` from the screen, until the
+    /// next change.
+    pub fn clear(&self) {
+        let _ = self.chan.send(Msg::Clear);
+    }
+
+    /// println_above prints `s` above the lines of the `MultiBar`, e.g. a
+    /// log line: the lines are erased, `s` is printed, and the lines are
+    /// drawn again below it.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let handle = mb.handle();
+    /// thread::spawn(move || mb.listen());
+    /// handle.println_above("warning: retrying the download");
+    /// ```
+    pub fn println_above<S: Into<Cow<'static, str>>>(&self, s: S) {
+        let _ = self.chan.send(Msg::Print {
+            text: s.into().into_owned(),
+            to: None,
+        });
+    }
+
+    // printer prints above the lines of the `MultiBar`, without keeping it
+    // listening.
+    #[cfg(feature = "log")]
+    pub(crate) fn printer(&self) -> Printer {
+        Printer(Arc::downgrade(&self.chan))
+    }
+
+    // downgrade returns a handle that doesn't keep the `MultiBar`
+    // listening.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn downgrade(&self) -> WeakHandle {
+        WeakHandle {
+            chan: Arc::downgrade(&self.chan),
+            ids: self.ids.clone(),
+            style: self.style.clone(),
+            verbs: self.verbs.clone(),
+            named: self.named.clone(),
+        }
+    }
+
+    /// redraw draws all the lines again.
+    pub fn redraw(&self) {
+        let _ = self.chan.send(Msg::Redraw);
+    }
+
+    /// resized notifies the `MultiBar` that the terminal was resized.
+    pub fn resized(&self) {
+        let _ = self.chan.send(Msg::Resize);
+    }
+}
+
+// WeakHandle is a MultiBarHandle that doesn't keep the MultiBar listening,
+// for handles kept in a global, e.g. by `ProgressLayer`.
+#[cfg(feature = "tracing")]
+pub(crate) struct WeakHandle {
+    chan: Weak<Sender<Msg>>,
+    ids: Arc<AtomicUsize>,
+    style: Option<Style>,
+    verbs: Arc<AtomicUsize>,
+    named: Named,
+}
+
+#[cfg(feature = "tracing")]
+impl WeakHandle {
+    // upgrade returns the handle, unless the MultiBar is done.
+    pub(crate) fn upgrade(&self) -> Option<MultiBarHandle> {
+        self.chan.upgrade().map(|chan| MultiBarHandle {
+            chan,
+            ids: self.ids.clone(),
+            style: self.style.clone(),
+            verbs: self.verbs.clone(),
+            named: self.named.clone(),
+        })
+    }
+
+    pub(crate) fn printer(&self) -> Printer {
+        Printer(self.chan.clone())
+    }
+}
+
+// named_bar shares a bar created by name, a spinner until its total is set.
+fn named_bar(mut pb: ProgressBar<Pipe>) -> SharedBar<Pipe> {
+    pb.set_zero_total_policy(ZeroTotalPolicy::Spinner);
+    SharedBar::new(pb)
+}
+
+// new_bar creates a bar for the line `id`, its verb is aligned on `verbs`.
+fn new_bar(
+    id: usize,
+    chan: &Arc<Sender<Msg>>,
+    style: Option<&Style>,
+    verbs: &Arc<AtomicUsize>,
+    total: u64,
+) -> ProgressBar<Pipe> {
+    let _ = chan.send(Msg::Bar { id });
+    let mut p = ProgressBar::on(
+        Pipe {
+            id,
+            chan: Sender::clone(chan),
+        },
+        total,
+    );
+    p.multibar = Some(Link {
+        id,
+        chan: chan.clone(),
+    });
+    p.verb_width = Some(verbs.clone());
+    // the colors are left out by the renderer, see `MultiBar::set_colors`
+    p.set_colors(true);
+    if let Some(style) = style {
+        p.set_style(style);
+    }
+    p.add(0);
+    p
+}
+
+/// Frame is the state of the lines of a `MultiBar`, passed to the frame
+/// observer, see `MultiBar::set_frame_observer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub rows: Vec<FrameRow>,
+}
+
+/// FrameRow is a line of a `Frame`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameRow {
+    /// The text of the line, without escape sequences.
+    pub text: String,
+    pub state: RowState,
+    /// The alert over the line, see `ProgressBar::alert`.
+    pub alert: Option<String>,
+}
+
+/// RowState is the state of a line of a `MultiBar`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowState {
+    /// A text line, added with `println`.
+    Text,
+    /// The line of a running bar.
+    Running,
+    /// The line of a finished bar.
+    Finished,
+    /// The line of a bar dropped without finishing.
+    Aborted,
+}
+
+type Observer = Box<dyn FnMut(&Frame) + Send>;
+
+/// FrameStats counts the frames drawn by a `MultiBar`, and those skipped
+/// because of `MultiBar::set_max_fps`.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    drawn: Arc<AtomicU64>,
+    skipped: Arc<AtomicU64>,
+}
+
+impl FrameStats {
+    /// Number of frames drawn.
+    pub fn drawn(&self) -> u64 {
+        self.drawn.load(Ordering::Relaxed)
+    }
+
+    /// Number of frames skipped, their changes were drawn in a later one.
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+}
+
+/// StdStream is a standard output stream, see `MultiBar::set_std_stream`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdStream {
+    Stdout,
+    Stderr,
+}
+
+/// Pagination of the lines of a `MultiBar` taller than the terminal, which
+/// would otherwise scroll the terminal history away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pagination {
+    /// Draw all the lines.
+    Off,
+    /// Show one page at a time, and the next one after the given duration.
+    Rotate(Duration),
+    /// Show the page of the first unfinished bar.
+    Active,
+}
+
+/// ListenTimeout is returned by `MultiBar::listen_with_timeout`, when no bar
+/// made any change for too long.
+#[derive(Debug)]
+pub struct ListenTimeout {
+    timeout: Duration,
+    unfinished: Vec<usize>,
+}
+
+impl ListenTimeout {
+    /// unfinished returns the levels (line numbers in the `MultiBar`) of the
+    /// bars that didn't finish.
+    pub fn unfinished(&self) -> &[usize] {
+        &self.unfinished
+    }
+}
+
+impl fmt::Display for ListenTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "no progress for {:?}, unfinished bars at lines:",
+            self.timeout
+        )?;
+        for level in &self.unfinished {
+            write!(f, " {}", level)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ListenTimeout {}
+
+impl ProgressBar<Pipe> {
+    /// alert overlays a highlighted alert row over the line of this bar,
+    /// until `clear_alert` is called or the `timeout` expires. The other
+    /// lines of the `MultiBar` are not disturbed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// let mut pb = mb.create_bar(100);
+    /// pb.alert("disk nearly full — pausing writes", None);
+    /// // ...
+    /// pb.clear_alert();
+    /// pb.alert("retrying", Some(Duration::from_secs(5)));
+    /// ```
+    pub fn alert<S: Into<Cow<'static, str>>>(&mut self, text: S, timeout: Option<Duration>) {
+        if let Some(ref link) = self.multibar {
+            let _ = link.chan.send(Msg::Alert {
+                id: link.id,
+                text: text.into().into_owned(),
+                until: timeout.map(|t| Instant::now() + t),
+            });
+        }
+    }
+
+    /// clear_alert acknowledges the alert of this bar, and draws the bar
+    /// again.
+    pub fn clear_alert(&mut self) {
+        if let Some(ref link) = self.multibar {
+            let _ = link.chan.send(Msg::ClearAlert { id: link.id });
+        }
+    }
+}
+
+// Link ties a ProgressBar to its MultiBar, to notify it about the bar state.
+pub(crate) struct Link {
+    id: usize,
+    chan: Arc<Sender<Msg>>,
+}
+
+impl Link {
+    pub(crate) fn println_above(&self, text: String) {
+        let _ = self.chan.send(Msg::Print { text, to: None });
+    }
+    pub(crate) fn finish(&self) {
+        let _ = self.chan.send(Msg::Finish { id: self.id });
+    }
+    pub(crate) fn abandon(&self) {
+        let _ = self.chan.send(Msg::Abandon {
+            id: self.id,
+            mark: true,
+        });
+    }
+    // fail ends the bar like `abandon`, its last frame shows the failure.
+    pub(crate) fn fail(&self) {
+        let _ = self.chan.send(Msg::Abandon {
+            id: self.id,
+            mark: false,
+        });
+    }
+}
+
+// The sender of the MultiBar listening last, for `pb_println!`, and the
+// standard stream it draws on, if known.
+static LISTENING: Mutex<(Weak<Sender<Msg>>, Option<StdStream>)> = Mutex::new((Weak::new(), None));
+
+// Printer prints above the lines of a MultiBar. It holds the sender weakly:
+// `listen` returns once the bars and handles are gone, even if a printer is
+// kept in a global, e.g. by `LogBridge`.
+pub(crate) struct Printer(Weak<Sender<Msg>>);
+
+impl Printer {
+    // listen makes `tx`, drawing on `stream`, the MultiBar printed above by
+    // `pb_println!`.
+    fn listen(tx: &Arc<Sender<Msg>>, stream: Option<StdStream>) {
+        *LISTENING.lock().unwrap_or_else(|e| e.into_inner()) = (Arc::downgrade(tx), stream);
+    }
+
+    // println prints `text` above the lines, or gives it back if the
+    // MultiBar isn't listening anymore.
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    pub(crate) fn println(&self, text: String) -> ::std::result::Result<(), String> {
+        self.print(text, None)
+    }
+
+    // print prints `text` above the lines, through the writer or to the
+    // standard stream `to`.
+    fn print(&self, text: String, to: Option<StdStream>) -> ::std::result::Result<(), String> {
+        match self.0.upgrade() {
+            Some(chan) => chan.send(Msg::Print { text, to }).map_err(|e| match e.0 {
+                Msg::Print { text, .. } => text,
+                _ => unreachable!(),
+            }),
+            None => Err(text),
+        }
+    }
+}
+
+// println_listening prints `text` to `stream` above the lines of the
+// MultiBar listening, or gives it back if there's none.
+pub(crate) fn println_listening(
+    text: String,
+    stream: StdStream,
+) -> ::std::result::Result<(), String> {
+    let (chan, drawn_on) = LISTENING.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let to = if drawn_on == Some(stream) {
+        None
+    } else {
+        Some(stream)
+    };
+    Printer(chan).print(text, to)
+}
+
+// Pump renders the changes of a MultiBar when asked to, so that a test
+// sees every frame at a known point, see `testing::Script`.
+pub(crate) struct Pump<T: Write> {
+    renderer: Renderer<T>,
+    rx: Receiver<Msg>,
+    handle: MultiBarHandle,
+}
+
+impl<T: Write> Pump<T> {
+    // println adds a text line after the last line.
+    pub(crate) fn println(&self, s: &str) {
+        let id = self.handle.ids.fetch_add(1, Ordering::Relaxed);
+        let _ = self.handle.chan.send(Msg::Line {
+            id,
+            string: s.to_owned(),
+        });
+    }
+
+    pub(crate) fn handle(&self) -> &MultiBarHandle {
+        &self.handle
+    }
+
+    // step renders the changes received so far.
+    pub(crate) fn step(&mut self) {
+        while let Ok(msg) = self.rx.try_recv() {
+            match self.renderer.apply(msg) {
+                Step::Draw if !self.renderer.paused => self.renderer.draw(),
+                Step::Draw | Step::Skip => {}
+                Step::Stop => break,
+            }
+        }
+    }
+}
+
+// Line is a line of a MultiBar, a bar is identified by the id of its line.
+struct Line {
+    id: usize,
+    text: String,
+    state: RowState,
+    alert: Option<Alert>,
+    // the time of the last write, with timestamps
+    stamp: String,
+    updated: Instant,
+}
+
+impl Line {
+    // display returns the alert over the line, if any, highlighted and as
+    // wide as the line. A bar idle for `dim_idle` is dimmed. Without colors,
+    // the escape sequences of the line are left out, idle bars aren't dimmed
+    // and the alert is marked with a `!` instead.
+    fn display(&self, dim_idle: Option<Duration>, colors: bool) -> Cow<'_, str> {
+        match self.alert {
+            Some(ref alert) => {
+                let pad = glyph::width(&self.text).saturating_sub(glyph::width(&alert.text));
+                if colors {
+                    Cow::Owned(format!("\x1B[7m{}{}\x1B[0m", alert.text, repeat!(" ", pad)))
+                } else {
+                    let pad = pad.saturating_sub(2);
+                    Cow::Owned(format!("! {}{}", alert.text, repeat!(" ", pad)))
+                }
+            }
+            None if self.idle(dim_idle) && colors => Cow::Owned(format!(
+                "\x1B[2m{}\x1B[0m",
+                glyph::strip(&self.text).trim_start_matches('\r')
+            )),
+            None if colors => Cow::Borrowed(&self.text),
+            None => glyph::strip_colors(&self.text),
+        }
+    }
+
+    // idle tells whether the line is a running bar not updated for `after`.
+    fn idle(&self, after: Option<Duration>) -> bool {
+        self.state == RowState::Running && after.is_some_and(|a| self.updated.elapsed() >= a)
+    }
+}
+
+struct Alert {
+    text: String,
+    until: Option<Instant>,
+}
+
+// Settings of a MultiBar, used by its renderer.
+struct Settings {
+    pagination: Pagination,
+    observer: Option<Observer>,
+    max_fps: u32,
+    stats: FrameStats,
+    remove_on_finish: bool,
+    keep_finished: usize,
+    timestamps: bool,
+    dim_idle: Option<Duration>,
+    leave_rendered: bool,
+    size: Option<TermSize>,
+    colors: bool,
+    shared_terminal: bool,
+    std_stream: Option<StdStream>,
+}
+
+// TermSize returns the size of the terminal, see `tty::terminal_size`.
+pub(crate) type TermSize = Box<dyn Fn() -> Option<(Width, Height)> + Send>;
+
+impl Settings {
+    fn new() -> Settings {
+        Settings {
+            pagination: Pagination::Rotate(Duration::from_secs(3)),
+            observer: None,
+            max_fps: 0,
+            stats: FrameStats::default(),
+            remove_on_finish: false,
+            keep_finished: 0,
+            timestamps: false,
+            dim_idle: None,
+            leave_rendered: false,
+            size: None,
+            colors: false,
+            shared_terminal: false,
+            std_stream: None,
+        }
+    }
+}
+
+// Taken by the renderer of a MultiBar drawing on a shared terminal, see
+// `MultiBar::set_shared_terminal`.
+static TERMINAL_TURN: Mutex<()> = Mutex::new(());
+
+// Renderer draws the lines of a MultiBar, and keeps track of the
+// lines that are currently on the screen.
+struct Renderer<T: Write> {
+    lines: Vec<Line>,
+    // the level (index in `lines`) of each line id
+    levels: HashMap<usize, usize>,
+    unfinished: Vec<usize>,
+    paused: bool,
+    nlines: usize,
+    nblank_lines: usize,
+    max_width: usize,
+    pagination: Pagination,
+    page: usize,
+    page_since: Instant,
+    observer: Option<Observer>,
+    // the shortest time between two frames, from the max fps
+    min_interval: Option<Duration>,
+    last_draw: Option<Instant>,
+    // a frame was skipped, and is drawn once `min_interval` passed
+    owed: bool,
+    stats: FrameStats,
+    remove_on_finish: bool,
+    keep_finished: usize,
+    // the ids of the finished lines kept, the oldest first
+    finished: VecDeque<usize>,
+    timestamps: bool,
+    dim_idle: Option<Duration>,
+    leave_rendered: bool,
+    size: Option<TermSize>,
+    // the writer shows colors, and they aren't turned off
+    colors: bool,
+    shared_terminal: bool,
+    handle: T,
+}
+
+impl<T: Write> Renderer<T> {
+    // new takes the lines created before listening, their ids are their
+    // levels, and the messages already read by `MultiBar::render_snapshot`.
+    fn new(lines: Vec<String>, pending: Vec<Msg>, settings: Settings, handle: T) -> Renderer<T> {
+        let mut r = Renderer {
+            levels: (0..lines.len()).map(|id| (id, id)).collect(),
+            lines: lines
+                .into_iter()
+                .enumerate()
+                .map(|(id, text)| Line {
+                    id,
+                    text,
+                    state: RowState::Text,
+                    alert: None,
+                    stamp: String::new(),
+                    updated: Instant::now(),
+                })
+                .collect(),
+            unfinished: Vec::new(),
+            paused: false,
+            nlines: 0,
+            nblank_lines: 0,
+            max_width: 0,
+            pagination: settings.pagination,
+            page: 0,
+            page_since: Instant::now(),
+            observer: settings.observer,
+            min_interval: match settings.max_fps {
+                0 => None,
+                fps => Some(Duration::from_secs(1) / fps),
+            },
+            last_draw: None,
+            owed: false,
+            stats: settings.stats,
+            remove_on_finish: settings.remove_on_finish,
+            keep_finished: settings.keep_finished,
+            finished: VecDeque::new(),
+            timestamps: settings.timestamps,
+            dim_idle: settings.dim_idle,
+            leave_rendered: settings.leave_rendered,
+            size: settings.size,
+            colors: settings.colors && colors_enabled(),
+            shared_terminal: settings.shared_terminal,
+            handle,
+        };
+        for msg in pending {
+            if let Step::Stop = r.apply(msg) {
+                break;
+            }
+        }
+        r
+    }
+
+    // run draws on every message, until all bars are dropped or a stop
+    // message is received. With a timeout, it gives up if no message arrives
+    // in time.
+    fn run(
+        mut self,
+        rx: Receiver<Msg>,
+        timeout: Option<Duration>,
+    ) -> ::std::result::Result<(), ListenTimeout> {
+        // hold the turn of the terminal while drawing
+        let _turn = if self.shared_terminal {
+            Some(TERMINAL_TURN.lock().unwrap_or_else(|e| e.into_inner()))
+        } else {
+            None
+        };
+        let mut deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let wake = [
+                deadline,
+                self.next_alert_expiry(),
+                self.next_frame(),
+                self.next_idle(),
+            ]
+            .iter()
+            .flatten()
+            .min()
+            .cloned();
+            let msg = match recv_deadline(&rx, wake) {
+                Recv::Msg(msg) => msg,
+                Recv::Closed => break,
+                Recv::Timeout if deadline.is_none_or(|d| Instant::now() < d) => {
+                    self.expire_alerts();
+                    if !self.paused {
+                        self.draw();
+                    }
+                    continue;
+                }
+                Recv::Timeout => {
+                    self.end();
+                    let unfinished = &self.unfinished;
+                    return Err(ListenTimeout {
+                        timeout: timeout.unwrap(),
+                        unfinished: self
+                            .lines
+                            .iter()
+                            .enumerate()
+                            .filter(|&(_, l)| unfinished.contains(&l.id))
+                            .map(|(level, _)| level)
+                            .collect(),
+                    });
+                }
+            };
+            deadline = timeout.map(|t| Instant::now() + t);
+            match self.apply(msg) {
+                Step::Draw if !self.paused => self.draw_capped(),
+                Step::Draw | Step::Skip => {}
+                Step::Stop => break,
+            }
+        }
+        self.end();
+        Ok(())
+    }
+
+    // apply applies a message to the lines, and tells whether to draw them.
+    fn apply(&mut self, msg: Msg) -> Step {
+        match msg {
+            Msg::Line { id, string } => self.set_line(id, string),
+            Msg::Bar { id } => {
+                let level = self.level(id);
+                self.lines[level].state = RowState::Running;
+                self.unfinished.push(id);
+                return Step::Skip;
+            }
+            Msg::Finish { id } => {
+                self.unfinished.retain(|&i| i != id);
+                let level = self.level(id);
+                self.lines[level].state = RowState::Finished;
+                if self.remove_on_finish {
+                    self.finished.push_back(id);
+                    while self.finished.len() > self.keep_finished {
+                        let id = self.finished.pop_front().unwrap();
+                        self.remove(id);
+                    }
+                    return Step::Draw;
+                }
+                if self.lines[level].alert.take().is_none() {
+                    self.observe();
+                    return Step::Skip;
+                }
+            }
+            Msg::Alert { id, text, until } => {
+                let level = self.level(id);
+                self.lines[level].alert = Some(Alert { text, until });
+            }
+            Msg::ClearAlert { id } => {
+                let level = self.level(id);
+                self.lines[level].alert = None;
+            }
+            Msg::Abandon { id, mark } => {
+                self.unfinished.retain(|&i| i != id);
+                let level = self.level(id);
+                // a bar dropped before it was ever drawn, e.g. by a worker
+                // that bailed out early, leaves no line.
+                if self.lines[level].text.is_empty() {
+                    self.remove(id);
+                    return Step::Draw;
+                }
+                self.lines[level].state = RowState::Aborted;
+                if mark {
+                    self.lines[level].text = mark_aborted(&self.lines[level].text);
+                }
+            }
+            Msg::Print { text, to: None } => {
+                self.clear();
+                let mut out = String::new();
+                for line in text.lines() {
+                    out.push_str(&format!("\r{}\n", line));
+                }
+                printfl!(self.handle, "{}", out);
+            }
+            Msg::Print {
+                text,
+                to: Some(stream),
+            } => {
+                self.clear();
+                // nobody to report a failed print to, like a failed frame
+                let _ = match stream {
+                    StdStream::Stdout => writeln!(stdout(), "{}", text),
+                    StdStream::Stderr => writeln!(stderr(), "{}", text),
+                };
+            }
+            Msg::Pause => self.paused = true,
+            Msg::Resume => self.paused = false,
+            Msg::Clear => {
+                self.clear();
+                return Step::Skip;
+            }
+            Msg::Redraw => {}
+            Msg::Resize => {
+                self.max_width = 0;
+            }
+            Msg::Stop => return Step::Stop,
+        }
+        Step::Draw
+    }
+
+    // level returns the level of the line `id`, a new line is added after the
+    // last one if it doesn't exist yet.
+    fn level(&mut self, id: usize) -> usize {
+        if let Some(&level) = self.levels.get(&id) {
+            return level;
+        }
+        self.lines.push(Line {
+            id,
+            text: String::new(),
+            state: RowState::Text,
+            alert: None,
+            stamp: String::new(),
+            updated: Instant::now(),
+        });
+        self.levels.insert(id, self.lines.len() - 1);
+        self.lines.len() - 1
+    }
+
+    // next_frame returns when a skipped frame is due.
+    fn next_frame(&self) -> Option<Instant> {
+        match (self.owed && !self.paused, self.min_interval, self.last_draw) {
+            (true, Some(min), Some(last)) => Some(last + min),
+            _ => None,
+        }
+    }
+
+    // draw_capped draws, unless the last frame was drawn less than
+    // `min_interval` ago.
+    fn draw_capped(&mut self) {
+        if let (Some(min), Some(last)) = (self.min_interval, self.last_draw) {
+            if last.elapsed() < min {
+                self.owed = true;
+                self.stats.skipped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        self.draw();
+    }
+
+    fn next_alert_expiry(&self) -> Option<Instant> {
+        self.lines
+            .iter()
+            .filter_map(|l| l.alert.as_ref().and_then(|a| a.until))
+            .min()
+    }
+
+    // next_idle returns when the next bar gets idle, to dim it.
+    fn next_idle(&self) -> Option<Instant> {
+        let after = self.dim_idle?;
+        let now = Instant::now();
+        self.lines
+            .iter()
+            .filter(|l| l.state == RowState::Running)
+            .map(|l| l.updated + after)
+            .filter(|&t| t > now)
+            .min()
+    }
+
+    fn expire_alerts(&mut self) {
+        let now = Instant::now();
+        for l in &mut self.lines {
+            if l.alert
+                .as_ref()
+                .and_then(|a| a.until)
+                .is_some_and(|u| u <= now)
+            {
+                l.alert = None;
+            }
+        }
+    }
+
+    fn set_line(&mut self, id: usize, string: String) {
+        // the last write of a finished bar clears its line, it's ignored
+        // once the line is removed or kept, or to leave it rendered.
+        if self.remove_on_finish {
+            match self.levels.get(&id) {
+                None if string.is_empty() => return,
+                Some(&level) if self.lines[level].state == RowState::Finished => return,
+                _ => {}
+            }
+        }
+        let finished = |&level: &usize| self.lines[level].state == RowState::Finished;
+        if self.leave_rendered && string.is_empty() && self.levels.get(&id).is_some_and(finished) {
+            return;
+        }
+        let level = self.level(id);
+        self.lines[level].text = string;
+        self.lines[level].updated = Instant::now();
+        if self.timestamps {
+            self.lines[level].stamp = stamp(&time::now());
+        }
+    }
+
+    // remove removes the line `id`.
+    fn remove(&mut self, id: usize) {
+        let level = match self.levels.remove(&id) {
+            Some(level) => level,
+            None => return,
+        };
+        self.lines.remove(level);
+        for l in self.levels.values_mut() {
+            if *l > level {
+                *l -= 1;
+            }
+        }
+    }
+
+    fn draw(&mut self) {
+        let mut out = String::new();
+        if self.nlines + self.nblank_lines > 0 {
+            out += &move_cursor_up(self.nlines + self.nblank_lines);
+        }
+
+        let visible: Vec<&Line> = self.lines.iter().filter(|l| !l.text.is_empty()).collect();
+        let active = visible.iter().position(|l| self.unfinished.contains(&l.id));
+        let height = self.terminal_size().map(|(_, Height(h))| h as usize);
+        let (range, footer) = paginate(
+            self.pagination,
+            &mut self.page,
+            &mut self.page_since,
+            visible.len(),
+            height,
+            active,
+        );
+
+        // text lines wider than the terminal are wrapped here, a line
+        // wrapped by the terminal would throw off the cursor moves.
+        let term_width = self.terminal_size().map(|(Width(w), _)| w as usize);
+        let column = if self.timestamps { STAMP_WIDTH + 1 } else { 0 };
+        let mut rows = Vec::new();
+        for l in &visible[range] {
+            let text = l.display(self.dim_idle, self.colors);
+            // the timestamps column is blank for text lines
+            let stamp = match l.state {
+                RowState::Text => "",
+                _ => &l.stamp,
+            };
+            let stamp = format!("{:1$}", stamp, column);
+            match term_width {
+                Some(w) if l.state == RowState::Text && glyph::width(&text) + column > w => {
+                    rows.extend(
+                        glyph::wrap(&text, w.saturating_sub(column).max(1))
+                            .into_iter()
+                            .map(|r| Cow::Owned(format!("{}{}", stamp, r))),
+                    );
+                }
+                _ if column > 0 => {
+                    rows.push(Cow::Owned(format!(
+                        "{}{}",
+                        stamp,
+                        text.trim_start_matches('\r')
+                    )));
+                }
+                _ => rows.push(text),
+            }
+        }
+        rows.extend(footer.map(Cow::Owned));
+
+        let mut new_nlines = 0;
+        for text in rows {
+            self.max_width = self.max_width.max(text.len());
+            out.push_str(&format!("\r{}\n", text));
+            new_nlines += 1;
+        }
+
+        self.nblank_lines = self.nlines - new_nlines.min(self.nlines);
+        self.nlines = new_nlines;
+
+        for _ in 0..self.nblank_lines {
+            out.push_str(&format!(
+                "\r\r{}\n",
+                repeat!(" ", self.max_width.saturating_sub(1))
+            ));
+        }
+
+        printfl!(self.handle, "{}", out);
+        self.last_draw = Some(Instant::now());
+        self.owed = false;
+        self.stats.drawn.fetch_add(1, Ordering::Relaxed);
+        self.observe();
+    }
+
+    // observe passes the state of the lines to the frame observer.
+    fn observe(&mut self) {
+        let observer = match self.observer {
+            Some(ref mut o) => o,
+            None => return,
+        };
+        let frame = Frame {
+            rows: self
+                .lines
+                .iter()
+                .map(|l| FrameRow {
+                    text: glyph::strip(&l.text).trim_end().to_owned(),
+                    state: l.state,
+                    alert: l.alert.as_ref().map(|a| a.text.clone()),
+                })
+                .collect(),
+        };
+        observer(&frame);
+    }
+
+    // terminal_size returns the size of the terminal, or the one set in the
+    // settings.
+    fn terminal_size(&self) -> Option<(Width, Height)> {
+        match self.size {
+            Some(ref size) => size(),
+            None => terminal_size(),
+        }
+    }
+
+    // end erases the lines once done listening, or draws them a last time
+    // and leaves the cursor at the start of the row below the last one.
+    fn end(&mut self) {
+        if !self.leave_rendered {
+            return self.clear();
+        }
+        if !self.paused {
+            self.draw();
+        }
+        if self.nblank_lines > 0 {
+            printfl!(self.handle, "{}", move_cursor_up(self.nblank_lines));
+        }
+        self.nlines = 0;
+        self.nblank_lines = 0;
+    }
+
+    // clear erases the drawn lines, and leaves the cursor at the first one.
+    fn clear(&mut self) {
+        let n = self.nlines + self.nblank_lines;
+        if n > 0 {
+            let mut out = String::new();
+            out += &move_cursor_up(n);
+            for _ in 0..n {
+                out.push_str(&format!(
+                    "\r{}\n",
+                    repeat!(" ", self.max_width.saturating_sub(1))
+                ));
+            }
+            printfl!(self.handle, "{}", out);
+            printfl!(self.handle, "{}", move_cursor_up(n));
+        }
+        self.nlines = 0;
+        self.nblank_lines = 0;
+    }
+}
+
+// Width of a timestamp, see `MultiBar::set_timestamps`.
+const STAMP_WIDTH: usize = 8;
+
+// stamp formats the time of day of `tm`, as `HH:MM:SS`.
+fn stamp(tm: &time::Tm) -> String {
+    format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+// What to do after a message, see `Renderer::apply`.
+enum Step {
+    Draw,
+    Skip,
+    Stop,
+}
+
+// paginate returns the range of the `n` lines to draw, and a footer when
+// they don't fit in the terminal `height`. One row is left for the cursor.
+fn paginate(
+    pagination: Pagination,
+    page: &mut usize,
+    page_since: &mut Instant,
+    n: usize,
+    height: Option<usize>,
+    active: Option<usize>,
+) -> (Range<usize>, Option<String>) {
+    let per_page = match (pagination, height) {
+        (Pagination::Off, _) | (_, None) => return (0..n, None),
+        (_, Some(h)) if n < h => return (0..n, None),
+        (_, Some(h)) => h.saturating_sub(2).max(1),
+    };
+    let npages = n.div_ceil(per_page);
+    match pagination {
+        Pagination::Rotate(every) if page_since.elapsed() >= every => {
+            *page += 1;
+            *page_since = Instant::now();
+        }
+        Pagination::Active => *page = active.unwrap_or(0) / per_page,
+        _ => {}
+    }
+    *page %= npages;
+    let start = *page * per_page;
+    let footer = format!("-- page {}/{} --", *page + 1, npages);
+    (start..(start + per_page).min(n), Some(footer))
+}
+
+// mark_aborted appends an "aborted" mark to the last frame of a bar, without
+// making it wider. The colors are left out, so that a cut doesn't leave one
+// running into the mark.
+fn mark_aborted(line: &str) -> String {
+    const MARK: &str = " (aborted)";
+    let line = glyph::strip_colors(line);
+    let keep = glyph::width(&line).saturating_sub(MARK.len());
+    glyph::truncate(line.trim_end(), keep).to_owned() + MARK
+}
+
+pub struct Pipe {
+    id: usize,
+    chan: Sender<Msg>,
 }
 
 impl Write for Pipe {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
         let s = from_utf8(buf).unwrap().to_owned();
-        self.chan
-            .send(WriteMsg {
-                level: self.level,
-                string: s,
-            }).unwrap();
+        // the MultiBar may have stopped listening already, see `MultiBar::scope`.
+        let _ = self.chan.send(Msg::Line {
+            id: self.id,
+            string: s,
+        });
         Ok(1)
     }
 
@@ -267,9 +1930,672 @@ impl Write for Pipe {
     }
 }
 
-// WriteMsg is the message format used to communicate
+// Msg is the message format used to communicate
 // between MultiBar and its bars
-struct WriteMsg {
-    level: usize,
-    string: String,
+enum Msg {
+    // write `string` to the line `id`
+    Line {
+        id: usize,
+        string: String,
+    },
+    // a bar was created for the line `id`
+    Bar {
+        id: usize,
+    },
+    // the bar of the line `id` finished
+    Finish {
+        id: usize,
+    },
+    // the bar of the line `id` was dropped without finishing, its line is
+    // marked as aborted if `mark`
+    Abandon {
+        id: usize,
+        mark: bool,
+    },
+    // overlay an alert over the line `id`, until acknowledged or `until`
+    Alert {
+        id: usize,
+        text: String,
+        until: Option<Instant>,
+    },
+    ClearAlert {
+        id: usize,
+    },
+    // print `text` above the lines, through the writer, or to the standard
+    // stream `to`
+    Print {
+        text: String,
+        to: Option<StdStream>,
+    },
+    // control messages, see `MultiBarHandle`
+    Pause,
+    Resume,
+    Clear,
+    Redraw,
+    Resize,
+    // stop listening, even if some bars are still alive
+    Stop,
+}
+
+#[cfg(test)]
+mod test {
+    use chan;
+    use multi::{
+        mark_aborted, paginate, stamp, Msg, MultiBar, Pagination, Renderer, Settings, STAMP_WIDTH,
+    };
+    use std::time::{Duration, Instant};
+    use style::Style;
+    use testing::{Env, Output, Terminal};
+
+    #[test]
+    fn scope_panic() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::mpsc;
+        use std::thread;
+        let (done, wait) = mpsc::channel::<()>();
+        let mut t = None;
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            MultiBar::on(Vec::new()).scope(|s| {
+                let pb = s.bar(10);
+                t = Some(thread::spawn(move || {
+                    let _pb = pb;
+                    let _ = wait.recv();
+                }));
+                panic!("job failed");
+            })
+        }));
+        assert!(res.is_err(), "the panic goes on once the bars are stopped");
+        drop(done);
+        t.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn aborted() {
+        let line = "\r1 / 10 [=>--]          ";
+        assert_eq!(mark_aborted(line), "\r1 / 10 [=>--] (aborted)");
+        let line = "\r1 / 10 [=>--]";
+        assert_eq!(mark_aborted(line), "\r1 / (aborted)");
+        let line = "\r\x1B[1;32mCopying\x1B[0m 1 / 10 [\x1B[32m=>\x1B[0m--]          ";
+        assert_eq!(mark_aborted(line), "\rCopying 1 / 10 [=>--] (aborted)");
+        let line = "\r\x1B[1;32mCopying\x1B[0m 1 / 10 [\x1B[32m=>\x1B[0m--]";
+        assert_eq!(mark_aborted(line), "\rCopying 1 / (aborted)");
+    }
+
+    #[test]
+    fn failed() {
+        let term = Terminal::new(40, 5);
+        let mut mb = term.multibar();
+        mb.set_leave_rendered(true);
+        for error in &["disk full", ""] {
+            let mut pb = mb.create_bar(10);
+            pb.show_speed = false;
+            pb.show_time_left = false;
+            pb.add(4);
+            if error.is_empty() {
+                drop(pb);
+            } else {
+                pb.finish_with_error(error);
+            }
+        }
+        mb.listen();
+        // a failed bar isn't marked as aborted, it shows its error
+        assert_eq!(
+            term.screen(),
+            "4 / 10 [===>-----] 40.00 % ✗ disk full\n\
+             4 / 10 [========>------------] (aborted)"
+        );
+    }
+
+    #[test]
+    fn verbs() {
+        let mut mb = MultiBar::on(Vec::new());
+        let bars: Vec<_> = [("Downloading", "serde "), ("Compiling", "pbr ")]
+            .iter()
+            .map(|&(verb, msg)| {
+                let mut pb = mb.create_bar(10);
+                pb.set_template(Some("{verb}{msg}{pos}")).unwrap();
+                pb.set_verb(verb);
+                pb.message(msg);
+                pb
+            })
+            .collect();
+        for mut pb in bars {
+            pb.inc();
+        }
+        assert_eq!(
+            mb.render_snapshot(),
+            "Downloading serde 1\n  Compiling pbr 1"
+        );
+    }
+
+    #[test]
+    fn named_bars() {
+        let mut mb = MultiBar::on(Vec::new());
+        mb.bar("b").set_total(4);
+        mb.bar(String::from("a")).set_total(2);
+        mb.bar("b").message("b ");
+        mb.bar("b").inc();
+        let handle = mb.handle();
+        handle.bar("a").message("a ");
+        handle.bar("a").inc();
+        let snapshot = mb.render_snapshot();
+        let lines: Vec<_> = snapshot.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("b 1 / 4"), "{:?}", lines);
+        assert!(lines[1].starts_with("a 1 / 2"), "{:?}", lines);
+    }
+
+    #[test]
+    fn render_snapshot() {
+        let mut mb = MultiBar::on(Vec::new());
+        mb.println("Backups:");
+        let mut p1 = mb.create_bar(10);
+        let _p2 = mb.create_bar(10);
+        p1.set_width(Some(30));
+        p1.show_speed = false;
+        p1.show_time_left = false;
+        p1.add(5);
+        p1.alert("disk full", None);
+        let snapshot = mb.render_snapshot();
+        let lines: Vec<&str> = snapshot.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "Backups:");
+        assert_eq!(lines[1], "5 / 10 [=====>-----] 50.00 %");
+        assert!(lines[2].starts_with("0 / 10 ["), "{:?}", lines[2]);
+
+        // the messages read by the snapshot are kept for listen
+        assert_eq!(mb.pending.len(), 3);
+    }
+
+    #[test]
+    fn shared_terminal() {
+        use std::thread;
+
+        let out = Output::default();
+        let mut first = MultiBar::on(out.clone());
+        first.set_shared_terminal(true);
+        let mut pb = first.create_bar(1);
+        let first = thread::spawn(move || first.listen());
+        thread::sleep(Duration::from_millis(50));
+        let mut second = MultiBar::on(out.clone());
+        second.set_shared_terminal(true);
+        second.set_leave_rendered(true);
+        second.println("second");
+        let second = thread::spawn(move || second.listen());
+        thread::sleep(Duration::from_millis(50));
+        assert!(!out.text().contains("second"), "should wait for its turn");
+        pb.inc();
+        pb.finish();
+        first.join().unwrap();
+        second.join().unwrap();
+        let out = out.text();
+        let done = out.find("1 / 1").expect("first drawn");
+        assert!(out[done..].contains("second"), "{:?}", out);
+    }
+
+    #[test]
+    fn independent() {
+        use std::thread;
+
+        let outs = [Output::default(), Output::default()];
+        let listeners: Vec<_> = outs
+            .iter()
+            .enumerate()
+            .map(|(i, out)| {
+                let mut mb = MultiBar::on(out.clone());
+                mb.println(format!("multibar {}", i));
+                let mut pb = mb.create_bar(100);
+                pb.message(format!("bar {} ", i));
+                let t = thread::spawn(move || {
+                    for _ in 0..100 {
+                        pb.inc();
+                    }
+                    pb.finish();
+                });
+                (thread::spawn(move || mb.listen()), t)
+            })
+            .collect();
+        for (listener, t) in listeners {
+            t.join().unwrap();
+            listener.join().unwrap();
+        }
+        for (i, out) in outs.iter().enumerate() {
+            let out = out.text();
+            assert!(out.contains(&format!("multibar {}", i)));
+            assert!(out.contains(&format!("bar {} 100 / 100", i)));
+            assert!(!out.contains(&format!("bar {}", 1 - i)));
+        }
+    }
+
+    #[test]
+    fn take_dirty() {
+        let mut mb = MultiBar::on(Vec::new());
+        mb.println("header");
+        let mut p1 = mb.create_bar(10);
+        let mut p2 = mb.create_bar(10);
+        for p in [&mut p1, &mut p2] {
+            p.set_width(Some(30));
+            p.show_speed = false;
+            p.show_time_left = false;
+        }
+        let rows: Vec<usize> = mb.take_dirty().into_iter().map(|d| d.0).collect();
+        assert_eq!(rows, [0, 1, 2]);
+        assert!(mb.take_dirty().is_empty());
+        p2.add(5);
+        assert_eq!(
+            mb.take_dirty(),
+            [(2, "5 / 10 [=====>-----] 50.00 %".to_owned())]
+        );
+    }
+
+    #[test]
+    fn frame_observer() {
+        use multi::{Frame, FrameRow, RowState};
+        use std::sync::{Arc, Mutex};
+        let frames = Arc::new(Mutex::new(Vec::<Frame>::new()));
+        let mut mb = MultiBar::on(Vec::new());
+        let log = frames.clone();
+        mb.set_frame_observer(move |f| log.lock().unwrap().push(f.clone()));
+        mb.println("header");
+        let mut p1 = mb.create_bar(10);
+        let p2 = mb.create_bar(10);
+        p1.set_width(Some(30));
+        p1.show_speed = false;
+        p1.show_time_left = false;
+        p1.finish();
+        drop(p2);
+        mb.listen();
+
+        let frames = frames.lock().unwrap();
+        let row = |text: &str, state| FrameRow {
+            text: text.to_owned(),
+            state,
+            alert: None,
+        };
+        let done = row("10 / 10 [=========] 100.00 %", RowState::Running);
+        assert!(frames.iter().any(|f| f.rows[1] == done));
+        let last = frames.last().unwrap();
+        assert_eq!(last.rows[0], row("header", RowState::Text));
+        assert_eq!(last.rows[1].state, RowState::Finished);
+        assert_eq!(last.rows[2].state, RowState::Aborted);
+        assert!(last.rows[2].text.ends_with("(aborted)"));
+    }
+
+    #[test]
+    fn max_fps() {
+        let mut mb = MultiBar::on(Vec::new());
+        mb.set_max_fps(1);
+        let stats = mb.frame_stats();
+        let mut pb = mb.create_bar(100);
+        pb.set_max_refresh_rate(None);
+        for _ in 0..100 {
+            pb.inc();
+        }
+        pb.finish();
+        mb.listen();
+        assert_eq!(stats.drawn(), 1);
+        assert!(stats.skipped() >= 100, "skipped: {}", stats.skipped());
+    }
+
+    #[test]
+    fn handle_create_bar() {
+        use multi::{Frame, RowState};
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        let frames = Arc::new(Mutex::new(Vec::<Frame>::new()));
+        let mut mb = MultiBar::on(Vec::new());
+        let log = frames.clone();
+        mb.set_frame_observer(move |f| log.lock().unwrap().push(f.clone()));
+        mb.println("header");
+        let handle = mb.handle();
+        let listener = thread::spawn(move || mb.listen());
+        for _ in 0..2 {
+            let mut pb = handle.create_bar(10);
+            pb.add(5);
+            pb.finish();
+        }
+        drop(handle);
+        listener.join().unwrap();
+
+        let frames = frames.lock().unwrap();
+        let last = frames.last().unwrap();
+        let states: Vec<RowState> = last.rows.iter().map(|r| r.state).collect();
+        assert_eq!(
+            states,
+            [RowState::Text, RowState::Finished, RowState::Finished]
+        );
+        assert_eq!(last.rows[0].text, "header");
+    }
+
+    #[test]
+    fn remove_on_finish() {
+        use multi::{Frame, RowState};
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        let frames = Arc::new(Mutex::new(Vec::<Frame>::new()));
+        let mut mb = MultiBar::on(Vec::new());
+        let log = frames.clone();
+        mb.set_frame_observer(move |f| log.lock().unwrap().push(f.clone()));
+        mb.set_remove_on_finish(true);
+        mb.set_keep_finished(1);
+        let bars: Vec<_> = (0..4)
+            .map(|i| {
+                let mut pb = mb.create_bar(10);
+                pb.message(format!("bar {} ", i));
+                pb
+            })
+            .collect();
+        let listener = thread::spawn(move || mb.listen());
+        let mut bars = bars.into_iter();
+        bars.next().unwrap().finish();
+        bars.next().unwrap().finish();
+        let last = bars.next().unwrap();
+        let mut running = bars.next().unwrap();
+        running.inc();
+
+        let wait_rows = |n| {
+            for _ in 0..100 {
+                if frames.lock().unwrap().last().map(|f| f.rows.len()) == Some(n) {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            panic!("no frame with {} rows", n);
+        };
+        wait_rows(3);
+        {
+            let frames = frames.lock().unwrap();
+            let rows = &frames.last().unwrap().rows;
+            assert_eq!(rows[0].state, RowState::Finished);
+            assert!(rows[0].text.starts_with("bar 1 10 / 10"));
+            assert_eq!(rows[1].state, RowState::Running);
+        }
+        last.finish();
+        running.finish();
+        listener.join().unwrap();
+        let frames = frames.lock().unwrap();
+        let rows = &frames.last().unwrap().rows;
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].text.starts_with("bar 3 10 / 10"));
+    }
+
+    #[test]
+    fn default_bar_style() {
+        let mut style = Style::new();
+        style.format("|#--|").unwrap();
+        style.show_speed = false;
+        style.set_thousands_separator(Some(','));
+        let mut mb = MultiBar::on(Vec::new());
+        mb.set_default_bar_style(style);
+        let p1 = mb.create_bar(10);
+        let mut p2 = mb.create_bar(10);
+        p2.show_speed = true;
+        assert!(!p1.show_speed);
+        assert!(p2.show_speed);
+        assert!(format!("{:?}", p1).contains("format: \"|#--|\""));
+        let mut p3 = mb.create_bar(12_345_678);
+        p3.add(1_234_567);
+        assert!(mb.render_snapshot().contains("1,234,567 / 12,345,678"));
+    }
+
+    #[test]
+    fn pagination() {
+        let (mut page, mut since) = (0, Instant::now());
+        let mut p = |pagination, n, height, active| {
+            paginate(pagination, &mut page, &mut since, n, height, active)
+        };
+        assert_eq!(p(Pagination::Active, 10, Some(24), None), (0..10, None));
+        assert_eq!(p(Pagination::Active, 10, None, None), (0..10, None));
+        assert_eq!(p(Pagination::Off, 30, Some(10), None), (0..30, None));
+        assert_eq!(
+            p(Pagination::Active, 30, Some(10), Some(20)),
+            (16..24, Some("-- page 3/4 --".to_owned()))
+        );
+        let every = Duration::from_secs(60);
+        assert_eq!(p(Pagination::Rotate(every), 30, Some(10), None).0, 16..24);
+        assert_eq!(
+            p(
+                Pagination::Rotate(Duration::from_secs(0)),
+                30,
+                Some(10),
+                None
+            ),
+            (24..30, Some("-- page 4/4 --".to_owned()))
+        );
+    }
+
+    #[test]
+    fn alert() {
+        let draw = |colors: bool| {
+            let (tx, rx) = chan::channel();
+            let send = |msg| tx.send(msg).unwrap();
+            send(Msg::Bar { id: 0 });
+            send(Msg::Line {
+                id: 0,
+                string: "\r1 / 2".to_owned(),
+            });
+            send(Msg::Alert {
+                id: 0,
+                text: "disk full".to_owned(),
+                until: Some(Instant::now() + Duration::from_millis(20)),
+            });
+            let stop = tx.clone();
+            let t = ::std::thread::spawn(move || {
+                ::std::thread::sleep(Duration::from_millis(100));
+                stop.send(Msg::Stop).unwrap();
+            });
+            let mut out = Vec::new();
+            let settings = Settings {
+                pagination: Pagination::Off,
+                colors,
+                ..Settings::new()
+            };
+            Renderer::new(vec![], vec![], settings, &mut out)
+                .run(rx, None)
+                .unwrap();
+            t.join().unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let _env = Env::colors(true);
+        let out = draw(true);
+        let alert = out
+            .find("\x1B[7mdisk full\x1B[0m")
+            .expect("should draw the alert");
+        assert!(
+            out[alert..].contains("1 / 2"),
+            "should draw the bar after the timeout"
+        );
+        // not a terminal
+        let out = draw(false);
+        assert!(out.contains("! disk full"), "{:?}", out);
+        assert!(!out.contains("\x1B[7m"), "{:?}", out);
+    }
+
+    #[test]
+    fn dim_idle() {
+        let (tx, rx) = chan::channel();
+        tx.send(Msg::Bar { id: 0 }).unwrap();
+        tx.send(Msg::Line {
+            id: 0,
+            string: "\r1 / 2".to_owned(),
+        })
+        .unwrap();
+        let t = ::std::thread::spawn(move || {
+            ::std::thread::sleep(Duration::from_millis(100));
+            tx.send(Msg::Line {
+                id: 0,
+                string: "\r2 / 2".to_owned(),
+            })
+            .unwrap();
+            tx.send(Msg::Stop).unwrap();
+        });
+        let _env = Env::colors(true);
+        let mut out = Vec::new();
+        let settings = Settings {
+            pagination: Pagination::Off,
+            dim_idle: Some(Duration::from_millis(20)),
+            colors: true,
+            ..Settings::new()
+        };
+        Renderer::new(vec![], vec![], settings, &mut out)
+            .run(rx, None)
+            .unwrap();
+        t.join().unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let dimmed = out
+            .find("\r\x1B[2m1 / 2\x1B[0m\n")
+            .expect("should dim the idle bar");
+        assert!(out[dimmed..].contains("\r\r2 / 2\n"), "{:?}", out);
+    }
+
+    #[test]
+    fn colors() {
+        let draw = |colors: bool| {
+            let (tx, rx) = chan::channel();
+            tx.send(Msg::Bar { id: 0 }).unwrap();
+            tx.send(Msg::Line {
+                id: 0,
+                string: "\r1 / 2 [\x1B[32m=>\x1B[0m-]".to_owned(),
+            })
+            .unwrap();
+            tx.send(Msg::Stop).unwrap();
+            let mut out = Vec::new();
+            let settings = Settings {
+                pagination: Pagination::Off,
+                colors,
+                ..Settings::new()
+            };
+            Renderer::new(vec![], vec![], settings, &mut out)
+                .run(rx, None)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let _env = Env::colors(true);
+        assert!(draw(true).contains("\r\r1 / 2 [\x1B[32m=>\x1B[0m-]\n"));
+        // not a terminal
+        assert!(draw(false).contains("\r\r1 / 2 [=>-]\n"));
+    }
+
+    #[test]
+    fn never_started() {
+        let msgs = vec![
+            Msg::Bar { id: 0 },
+            Msg::Bar { id: 1 },
+            Msg::Line {
+                id: 1,
+                string: "\r1 / 2 [=>--]          ".to_owned(),
+            },
+            Msg::Abandon { id: 0, mark: true },
+            Msg::Abandon { id: 1, mark: true },
+        ];
+        let r = Renderer::new(vec![], msgs, Settings::new(), Vec::new());
+        assert_eq!(r.lines.len(), 1, "should remove the bar never drawn");
+        assert_eq!(r.lines[0].id, 1);
+        assert_eq!(r.lines[0].text, "\r1 / 2 [=>--] (aborted)");
+        assert!(r.unfinished.is_empty());
+    }
+
+    #[test]
+    fn println_above() {
+        let (tx, rx) = chan::channel();
+        let msgs = vec![
+            Msg::Line {
+                id: 0,
+                string: "\r1 / 2".to_owned(),
+            },
+            Msg::Print {
+                text: "log".to_owned(),
+                to: None,
+            },
+            Msg::Stop,
+        ];
+        for msg in msgs {
+            tx.send(msg).unwrap();
+        }
+        let mut out = Vec::new();
+        let settings = Settings {
+            pagination: Pagination::Off,
+            leave_rendered: true,
+            ..Settings::new()
+        };
+        Renderer::new(vec![], vec![], settings, &mut out)
+            .run(rx, None)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        // the bar is erased, the text takes its row and the bar is drawn
+        // below
+        let erased = "\r\r1 / 2\n\x1B[1A\r     \n\x1B[1A\rlog\n\r\r1 / 2\n";
+        assert!(out.starts_with(erased), "{:?}", out);
+    }
+
+    #[test]
+    fn leave_rendered() {
+        let (tx, rx) = chan::channel();
+        let msgs = vec![
+            Msg::Bar { id: 0 },
+            Msg::Line {
+                id: 0,
+                string: "\r2 / 2".to_owned(),
+            },
+            Msg::Finish { id: 0 },
+            Msg::Line {
+                id: 0,
+                string: String::new(),
+            },
+            Msg::Stop,
+        ];
+        for msg in msgs {
+            tx.send(msg).unwrap();
+        }
+        let mut out = Vec::new();
+        let settings = Settings {
+            pagination: Pagination::Off,
+            leave_rendered: true,
+            ..Settings::new()
+        };
+        Renderer::new(vec![], vec![], settings, &mut out)
+            .run(rx, None)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with("\r\r2 / 2\n"), "{:?}", out);
+    }
+
+    #[test]
+    fn timestamps() {
+        let mut tm = ::time::empty_tm();
+        tm.tm_hour = 9;
+        tm.tm_min = 5;
+        tm.tm_sec = 30;
+        assert_eq!(stamp(&tm), "09:05:30");
+
+        let (tx, rx) = chan::channel();
+        tx.send(Msg::Bar { id: 1 }).unwrap();
+        tx.send(Msg::Line {
+            id: 1,
+            string: "\r1 / 2".to_owned(),
+        })
+        .unwrap();
+        tx.send(Msg::Stop).unwrap();
+        let mut out = Vec::new();
+        let settings = Settings {
+            pagination: Pagination::Off,
+            timestamps: true,
+            ..Settings::new()
+        };
+        Renderer::new(vec!["Tasks:".to_owned()], vec![], settings, &mut out)
+            .run(rx, None)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\r         Tasks:\n"), "{:?}", out);
+        let bar = out.find(" 1 / 2\n").expect("should draw the bar");
+        let stamp = &out[bar - STAMP_WIDTH..bar];
+        assert!(
+            stamp.chars().enumerate().all(|(i, c)| match i {
+                2 | 5 => c == ':',
+                _ => c.is_ascii_digit(),
+            }),
+            "{:?}",
+            stamp
+        );
+    }
 }
@@ -0,0 +1,148 @@
+//! A writer that doesn't block the bar on a slow terminal, see
+//! `NonBlocking`.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+/// NonBlocking writes to `inner` from a dedicated thread, so that `inc`
+/// never waits on the display: a terminal stopped with Ctrl-S, or a pipe
+/// with a slow reader.
+///
+/// Writes go through a queue of `queue` writes. A redraw of the bar that
+/// finds the queue full is held back, and dropped if a newer redraw comes
+/// first. Other writes, such as `println` or the final newline, wait for
+/// room in the queue so they are not lost.
+///
+/// Dropping the writer, e.g. when the bar finishes, waits for the queued
+/// writes.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::io::stderr;
+/// use pbr::{NonBlocking, ProgressBar};
+///
+/// let mut pb = ProgressBar::on(NonBlocking::new(stderr(), 4), 1000);
+/// for _ in 0..1000 {
+///     pb.inc();
+/// }
+/// pb.finish();
+/// ```
+pub struct NonBlocking {
+    tx: Option<SyncSender<Vec<u8>>>,
+    writer: Option<JoinHandle<()>>,
+    // the last redraw, held back while the queue is full
+    pending: Option<Vec<u8>>,
+}
+
+impl NonBlocking {
+    pub fn new<W: Write + Send + 'static>(mut inner: W, queue: usize) -> NonBlocking {
+        let (tx, rx) = sync_channel::<Vec<u8>>(queue.max(1));
+        let writer = thread::spawn(move || {
+            for buf in rx {
+                // there is nobody to report the error to, the bar goes on
+                let _ = inner.write_all(&buf).and_then(|_| inner.flush());
+            }
+        });
+        NonBlocking {
+            tx: Some(tx),
+            writer: Some(writer),
+            pending: None,
+        }
+    }
+
+    // send queues `buf`, after the held back redraw, waiting for room.
+    fn send(&mut self, buf: Vec<u8>) -> io::Result<()> {
+        let tx = match self.tx {
+            Some(ref tx) => tx,
+            None => return Ok(()),
+        };
+        let pending = self.pending.take();
+        pending
+            .into_iter()
+            .chain(Some(buf))
+            .try_for_each(|buf| tx.send(buf))
+            .map_err(|_| stopped())
+    }
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "the writer thread stopped")
+}
+
+impl Write for NonBlocking {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // a redraw is a single write starting with `\r`, see `printfl!`,
+        // a line printed over the bar ends with a newline
+        if !buf.starts_with(b"\r") || buf.contains(&b'\n') {
+            self.send(buf.to_vec())?;
+            return Ok(buf.len());
+        }
+        let tx = match self.tx {
+            Some(ref tx) => tx,
+            None => return Ok(buf.len()),
+        };
+        // a newer redraw replaces the held back one
+        self.pending = None;
+        match tx.try_send(buf.to_vec()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(frame)) => self.pending = Some(frame),
+            Err(TrySendError::Disconnected(_)) => return Err(stopped()),
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for NonBlocking {
+    fn drop(&mut self) {
+        if let Some(frame) = self.pending.take() {
+            let _ = self.send(frame);
+        }
+        self.tx = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl fmt::Debug for NonBlocking {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NonBlocking")
+            .field("pending", &self.pending.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nonblocking::NonBlocking;
+    use pb::ProgressBar;
+    use testing::Output;
+
+    #[test]
+    fn drops_frames() {
+        // a terminal that doesn't take any output while the test holds its
+        // lock
+        let term = Output::default();
+        let stalled = term.lock();
+        let mut pb = ProgressBar::on(NonBlocking::new(term.clone(), 2), 1000);
+        pb.set_width(Some(40));
+        pb.set_max_refresh_rate(None);
+        for _ in 0..1000 {
+            pb.inc();
+        }
+        assert_eq!(pb.add(0), 1000);
+        drop(stalled);
+        pb.finish();
+        let out = term.text();
+        assert!(out.len() < 100 * 40, "frames should be dropped");
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.starts_with("1000 / 1000 "), "{:?}", last);
+    }
+}
@@ -0,0 +1,316 @@
+//! Remote notifications of the progress of a bar, see
+//! `ProgressBar::set_notifier`.
+
+/// Milestone of a bar, reported to a `Notifier`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Milestone {
+    /// The bar passed 25, 50 or 75 percent.
+    Percent(u8),
+    /// The bar finished.
+    Finished,
+    /// The bar was dropped without finishing, e.g. on an error.
+    Abandoned,
+}
+
+/// Notifier is told about the milestones of a bar, e.g. to follow a long
+/// unattended job remotely.
+pub trait Notifier {
+    /// Called on `milestone`, with the last line of the bar as plain text.
+    fn notify(&mut self, milestone: Milestone, line: &str);
+}
+
+#[cfg(feature = "webhook")]
+pub use self::webhook::Webhook;
+
+#[cfg(feature = "opentelemetry")]
+pub use self::otel::SpanNotifier;
+
+#[cfg(feature = "webhook")]
+mod webhook {
+    use super::{Milestone, Notifier};
+    use std::fmt;
+    use std::sync::mpsc::{sync_channel, SyncSender};
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+    use ureq::AgentBuilder;
+
+    // Time given to a post.
+    const TIMEOUT: Duration = Duration::from_secs(10);
+    // Milestones waiting for the posting thread.
+    const QUEUE: usize = 4;
+
+    /// Webhook posts the milestones of a bar to a webhook URL, as JSON with
+    /// a `text` field, which Slack incoming webhooks accept.
+    ///
+    /// The posts are made from a dedicated thread, so the job doesn't wait
+    /// on a slow webhook. A failed post is ignored, and a milestone that
+    /// finds the queue full is dropped. Dropping the webhook, e.g. when the
+    /// bar finishes, waits for the queued posts.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, Webhook};
+    ///
+    /// let mut pb = ProgressBar::new(1000);
+    /// pb.message("nightly backup ");
+    /// pb.set_notifier(Some(Box::new(Webhook::new(
+    ///     "https://hooks.slack.com/services/T000/B000/XXXX",
+    /// ))));
+    /// ```
+    pub struct Webhook {
+        url: String,
+        tx: Option<SyncSender<String>>,
+        poster: Option<JoinHandle<()>>,
+    }
+
+    impl Webhook {
+        pub fn new<S: Into<String>>(url: S) -> Webhook {
+            let url = url.into();
+            let (tx, rx) = sync_channel::<String>(QUEUE);
+            let agent = AgentBuilder::new().timeout(TIMEOUT).build();
+            let target = url.clone();
+            let poster = thread::spawn(move || {
+                for body in rx {
+                    // there is nobody to report the error to, the job goes on
+                    let _ = agent
+                        .post(&target)
+                        .set("Content-Type", "application/json")
+                        .send_string(&body);
+                }
+            });
+            Webhook {
+                url,
+                tx: Some(tx),
+                poster: Some(poster),
+            }
+        }
+    }
+
+    impl Drop for Webhook {
+        fn drop(&mut self) {
+            self.tx = None;
+            if let Some(poster) = self.poster.take() {
+                let _ = poster.join();
+            }
+        }
+    }
+
+    impl fmt::Debug for Webhook {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("Webhook").field("url", &self.url).finish()
+        }
+    }
+
+    impl Notifier for Webhook {
+        fn notify(&mut self, milestone: Milestone, line: &str) {
+            let text = match milestone {
+                Milestone::Percent(p) => format!("{}%: `{}`", p, line),
+                Milestone::Finished => format!("finished: `{}`", line),
+                Milestone::Abandoned => format!("aborted: `{}`", line),
+            };
+            if let Some(ref tx) = self.tx {
+                let _ = tx.try_send(format!("{{\"text\":{}}}", json_string(&text)));
+            }
+        }
+    }
+
+    // json_string quotes `s` as a JSON string.
+    fn json_string(s: &str) -> String {
+        let mut out = String::with_capacity(s.len() + 2);
+        out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+        out
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::{json_string, Webhook};
+        use notify::{Milestone, Notifier};
+        use std::mem;
+        use std::net::TcpListener;
+        use std::time::{Duration, Instant};
+
+        #[test]
+        fn slow_webhook() {
+            // a server that never answers
+            let server = TcpListener::bind("127.0.0.1:0").unwrap();
+            let mut hook = Webhook::new(format!("http://{}/", server.local_addr().unwrap()));
+            let start = Instant::now();
+            for _ in 0..10 {
+                hook.notify(Milestone::Percent(25), "1 / 4");
+            }
+            hook.notify(Milestone::Finished, "4 / 4");
+            assert!(start.elapsed() < Duration::from_secs(1), "should not wait");
+            // dropping it would wait for the posts to time out
+            mem::forget(hook);
+        }
+
+        #[test]
+        fn json() {
+            assert_eq!(
+                json_string("a \"b\" \\ ✅\n\t"),
+                "\"a \\\"b\\\" \\\\ ✅\\n\\u0009\""
+            );
+        }
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
+mod otel {
+    use super::{Milestone, Notifier};
+    use opentelemetry::global::{self, BoxedSpan};
+    use opentelemetry::trace::{Span, Status, Tracer};
+    use opentelemetry::KeyValue;
+    use std::borrow::Cow;
+    use std::fmt;
+
+    /// SpanNotifier records the progress of a bar on an OpenTelemetry span:
+    /// the span starts with the notifier, the milestones are added as
+    /// events with the percent as an attribute, and the span ends when the
+    /// bar finishes, with an error status if it's dropped without finishing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, SpanNotifier};
+    ///
+    /// let mut pb = ProgressBar::new(1000);
+    /// pb.set_notifier(Some(Box::new(SpanNotifier::new("nightly backup"))));
+    /// ```
+    pub struct SpanNotifier<S: Span = BoxedSpan> {
+        span: S,
+    }
+
+    impl SpanNotifier {
+        /// Start a span named `name` with the global tracer.
+        pub fn new<N: Into<Cow<'static, str>>>(name: N) -> SpanNotifier {
+            SpanNotifier::with_span(global::tracer("pbr").start(name))
+        }
+    }
+
+    impl<S: Span> SpanNotifier<S> {
+        /// Record on a span already started, e.g. a child of the span of
+        /// the job.
+        pub fn with_span(span: S) -> SpanNotifier<S> {
+            SpanNotifier { span }
+        }
+    }
+
+    impl<S: Span> fmt::Debug for SpanNotifier<S> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("SpanNotifier")
+                .field("span", self.span.span_context())
+                .finish()
+        }
+    }
+
+    impl<S: Span> Notifier for SpanNotifier<S> {
+        fn notify(&mut self, milestone: Milestone, line: &str) {
+            let line = KeyValue::new("pbr.line", line.to_owned());
+            match milestone {
+                Milestone::Percent(p) => {
+                    self.span
+                        .set_attribute(KeyValue::new("pbr.percent", i64::from(p)));
+                    self.span.add_event(
+                        "progress",
+                        vec![KeyValue::new("pbr.percent", i64::from(p)), line],
+                    );
+                }
+                Milestone::Finished => {
+                    self.span.set_attribute(KeyValue::new("pbr.percent", 100));
+                    self.span.add_event("finished", vec![line]);
+                    self.span.set_status(Status::Ok);
+                    self.span.end();
+                }
+                Milestone::Abandoned => {
+                    self.span.add_event("aborted", vec![line]);
+                    self.span.set_status(Status::error("aborted"));
+                    self.span.end();
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::SpanNotifier;
+        use notify::{Milestone, Notifier};
+        use opentelemetry::trace::{Span, SpanContext, Status};
+        use opentelemetry::KeyValue;
+        use std::borrow::Cow;
+        use std::time::SystemTime;
+
+        // Recorder is a span that records its events.
+        struct Recorder {
+            events: Vec<String>,
+            status: Status,
+            ended: bool,
+            context: SpanContext,
+        }
+
+        impl Default for Recorder {
+            fn default() -> Recorder {
+                Recorder {
+                    events: Vec::new(),
+                    status: Status::Unset,
+                    ended: false,
+                    context: SpanContext::empty_context(),
+                }
+            }
+        }
+
+        impl Span for Recorder {
+            fn add_event_with_timestamp<T>(&mut self, name: T, _: SystemTime, attrs: Vec<KeyValue>)
+            where
+                T: Into<Cow<'static, str>>,
+            {
+                let attrs: Vec<_> = attrs.iter().map(|kv| kv.value.to_string()).collect();
+                self.events
+                    .push(format!("{} {}", name.into(), attrs.join(" ")));
+            }
+            fn span_context(&self) -> &SpanContext {
+                &self.context
+            }
+            fn is_recording(&self) -> bool {
+                !self.ended
+            }
+            fn set_attribute(&mut self, _: KeyValue) {}
+            fn set_status(&mut self, status: Status) {
+                self.status = status;
+            }
+            fn update_name<T>(&mut self, _: T)
+            where
+                T: Into<Cow<'static, str>>,
+            {
+            }
+            fn add_link(&mut self, _: SpanContext, _: Vec<KeyValue>) {}
+            fn end_with_timestamp(&mut self, _: SystemTime) {
+                self.ended = true;
+            }
+        }
+
+        #[test]
+        fn span() {
+            let mut n = SpanNotifier::with_span(Recorder::default());
+            n.notify(Milestone::Percent(25), "25 / 100");
+            n.notify(Milestone::Finished, "100 / 100");
+            assert_eq!(
+                n.span.events,
+                ["progress 25 25 / 100", "finished 100 / 100"]
+            );
+            assert_eq!(n.span.status, Status::Ok);
+            assert!(n.span.ended);
+            assert!(format!("{:?}", n).starts_with("SpanNotifier"));
+        }
+    }
+}
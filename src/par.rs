@@ -0,0 +1,94 @@
+//! Progress of a rayon `ParallelIterator`, see `ParallelProgressIterator`.
+
+use pb::ProgressBar;
+use rayon::iter::plumbing::UnindexedConsumer;
+use rayon::iter::ParallelIterator;
+use shared::SharedBar;
+use std::io::{Stdout, Write};
+
+/// Adds `progress_count` and `progress_with` to parallel iterators, the
+/// rayon version of `ProgressIterator`. Requires the `rayon` feature.
+///
+/// The items are counted on a `SharedBar`, so the threads don't wait for
+/// each other to move the bar.
+///
+/// # Examples
+///
+/// ```ignore
+/// use pbr::ParallelProgressIterator;
+/// use rayon::prelude::*;
+///
+/// let sums: Vec<u64> = files
+///     .par_iter()
+///     .progress_count(files.len() as u64)
+///     .map(checksum)
+///     .collect();
+/// ```
+pub trait ParallelProgressIterator: ParallelIterator {
+    /// Wrap the iterator in a `ParProgress` with a bar of `total` items on
+    /// stdout.
+    fn progress_count(self, total: u64) -> ParProgress<Stdout, Self> {
+        self.progress_with(ProgressBar::new(total))
+    }
+
+    /// Wrap the iterator in a `ParProgress` with a configured bar.
+    fn progress_with<T: Write + Send>(self, pb: ProgressBar<T>) -> ParProgress<T, Self> {
+        ParProgress {
+            iter: self,
+            bar: SharedBar::new(pb),
+        }
+    }
+}
+
+impl<I: ParallelIterator> ParallelProgressIterator for I {}
+
+/// ParProgress is a parallel iterator with a bar, which is incremented for
+/// every item and finished once the iterator is driven to the end, see
+/// `ParallelProgressIterator`.
+#[derive(Debug)]
+pub struct ParProgress<T: Write, I> {
+    iter: I,
+    bar: SharedBar<T>,
+}
+
+impl<T, I> ParallelIterator for ParProgress<T, I>
+where
+    T: Write + Send,
+    I: ParallelIterator,
+{
+    type Item = I::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let bar = self.bar;
+        let res = self.iter.inspect(|_| bar.inc()).drive_unindexed(consumer);
+        bar.finish();
+        res
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.iter.opt_len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use par::ParallelProgressIterator;
+    use pb::ProgressBar;
+    use rayon::prelude::*;
+
+    #[test]
+    fn progress_with() {
+        let mut out = Vec::new();
+        let sum: u64 = (0..1000u64)
+            .into_par_iter()
+            .progress_with(ProgressBar::on(&mut out, 1000))
+            .map(|i| i * 2)
+            .sum();
+        assert_eq!(sum, 999_000);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("1000 / 1000"), "{:?}", out);
+    }
+}
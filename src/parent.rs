@@ -0,0 +1,101 @@
+//! Progress of a child process, reported with `child::report`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::process::{Command, Stdio};
+//! use pbr::parent;
+//!
+//! let mut child = Command::new("./build-step")
+//!     .stdout(Stdio::piped())
+//!     .spawn()
+//!     .unwrap();
+//! parent::attach(child.stdout.take().unwrap()).unwrap();
+//! child.wait().unwrap();
+//! ```
+
+use child::PREFIX;
+use pb::ProgressBar;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+// Total of the bar drawn by `attach`, the resolution of the fraction.
+const TOTAL: u64 = 1000;
+
+/// Draw the progress reported by a child on its stdout, `child`, with a bar
+/// on stdout, until the child closes its stdout.
+///
+/// Lines of `child` that are not progress reports are ignored.
+pub fn attach<R: Read>(child: R) -> io::Result<()> {
+    let mut pb = ProgressBar::new(TOTAL);
+    pb.show_counter = false;
+    pb.show_speed = false;
+    attach_to(child, &mut pb)?;
+    pb.finish();
+    Ok(())
+}
+
+/// Like `attach`, but updates `pb`, e.g. a bar of a `MultiBar`. The
+/// fraction reported by the child is scaled to the total of `pb`.
+pub fn attach_to<R: Read, T: Write>(child: R, pb: &mut ProgressBar<T>) -> io::Result<()> {
+    for line in BufReader::new(child).lines() {
+        let line = line?;
+        if let Some((fraction, msg)) = parse(&line) {
+            if msg.is_empty() {
+                pb.message("");
+            } else {
+                pb.message(format!("{} ", msg));
+            }
+            pb.set((fraction * pb.total as f64).round() as u64);
+        }
+    }
+    Ok(())
+}
+
+// parse returns the fraction and the message of a progress line.
+fn parse(line: &str) -> Option<(f64, &str)> {
+    let rest = line.strip_prefix(PREFIX)?;
+    let (fraction, msg) = match rest.find(' ') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    let fraction = fraction.parse::<f64>().ok()?;
+    if fraction.is_nan() {
+        return None;
+    }
+    Some((fraction.clamp(0., 1.), msg))
+}
+
+#[cfg(test)]
+mod test {
+    use child::report_to;
+    use parent::{attach_to, parse};
+    use pb::ProgressBar;
+
+    #[test]
+    fn protocol() {
+        let mut out = Vec::new();
+        report_to(&mut out, 0.42, "compiling\nfoo").unwrap();
+        assert_eq!(out, b"PB 0.42 compiling foo\n");
+        assert_eq!(
+            parse("PB 0.42 compiling foo"),
+            Some((0.42, "compiling foo"))
+        );
+        assert_eq!(parse("PB 2"), Some((1., "")));
+        assert_eq!(parse("PB x msg"), None);
+        assert_eq!(parse("warning: unused"), None);
+
+        report_to(&mut out, 0.9, "linking").unwrap();
+        out.extend_from_slice(b"some other output\n");
+        let mut drawn = Vec::new();
+        let mut pb = ProgressBar::on(&mut drawn, 200);
+        attach_to(&out[..], &mut pb).unwrap();
+        assert_eq!(pb.fraction(), 0.9);
+        drop(pb);
+        let drawn = String::from_utf8(drawn).unwrap();
+        assert!(drawn
+            .rsplit('\r')
+            .next()
+            .unwrap()
+            .starts_with("linking 180 / 200"));
+    }
+}
@@ -1,9 +1,31 @@
+use adder::{Adder, CancelHandle};
+use estimator::{eta, Estimator};
+use glyph::{self, to_ascii, truncate};
+use journal::{replay, Journal};
+use multi::Link;
+use notify::{Milestone, Notifier};
+use shared::SharedBar;
+use std::any::type_name;
+use std::borrow::Cow;
+use std::cmp::Ordering as Trend;
+use std::error::Error;
+use std::fmt;
 use std::io::Stdout;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Read, Write};
 use std::iter::repeat;
-use std::time::Duration;
+use std::mem;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use style::{Color, Paint, Style};
+use template::{Chunk, Template, TemplateError};
 use time::{self, SteadyTime};
-use tty::{terminal_size, Width};
+use tty::{colors_enabled, draw_at_row, terminal_size, Height, Width};
+#[cfg(feature = "tokio")]
+use wrap::{ProgressAsyncReader, ProgressAsyncWriter};
+use wrap::{ProgressReader, ProgressWriter};
 
 macro_rules! kb_fmt {
     ($n: ident) => {{
@@ -24,23 +46,221 @@ macro_rules! repeat {
     }};
 }
 
-const FORMAT: &'static str = "[=>-]";
-const TICK_FORMAT: &'static str = "\\|/-";
+const FORMAT: &str = "[=>-]";
+const TICK_FORMAT: &str = "\\|/-";
 const NANOS_PER_SEC: u32 = 1_000_000_000;
+const JOB_TICK_FORMAT: &str = "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏";
+const JOB_BAR_SIZE: usize = 20;
+// How often `sleep` ticks, unless the max refresh rate is set.
+const SLEEP_TICK: Duration = Duration::from_millis(100);
+// Where the progress should be, see `set_expected_duration`.
+const SCHEDULE_MARKER: &str = "|";
+// The partial blocks of a smooth bar, one to eight eighths of a cell.
+const SMOOTH_BLOCKS: [&str; 8] = ["▏", "▎", "▍", "▌", "▋", "▊", "▉", "█"];
+// Smallest total scaled by `Units::Auto`.
+const AUTO_UNITS_MIN: u64 = 10_000;
+// The total of a bar of unknown size, see `unbounded`.
+const UNBOUNDED: u64 = u64::MAX;
+const FALLBACK_WIDTH: usize = 80;
+const WIDTH_PROBE_SECS: i64 = 1;
+// Percents at which an adaptive heartbeat prints a line.
+const ADAPTIVE_HEARTBEAT: [f64; 12] = [1., 2., 5., 10., 25., 50., 75., 90., 95., 98., 99., 100.];
+// The rate is compared with the one this long ago, see `show_trend`.
+const TREND_WINDOW_SECS: i64 = 10;
+// Smallest change of the rate shown as a trend, as a fraction of the rate.
+const TREND_THRESHOLD: f64 = 0.05;
 
 // Output type format, indicate which format wil be used in
-// the speed box.
-#[derive(Debug)]
+// the speed box. `Auto` picks a format from the magnitude of the total:
+// plain numbers, thousands (`k`) or millions (`M`) of items, or bytes
+// with the `Bytes` hint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Units {
     Default,
     Bytes,
+    Auto(Option<UnitsHint>),
+}
+
+// What the values of a bar with `Units::Auto` count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitsHint {
+    Items,
+    Bytes,
+}
+
+// Prefixes of sizes in bytes, see `ProgressBar::set_byte_prefix`. `Legacy`
+// counts in powers of 1024 with SI names (`KB`, `MB`), `Binary` in powers
+// of 1024 with IEC names (`KiB`, `MiB`) and `Decimal` in powers of 1000
+// (`kB`, `MB`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BytePrefix {
+    Legacy,
+    Binary,
+    Decimal,
+}
+
+// When sizes in bytes are also shown exactly, see
+// `ProgressBar::set_exact_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExactBytes {
+    Never,
+    Always,
+    OnFinish,
+}
+
+// What is left of the bar when it finishes, see
+// `ProgressBar::set_finish_behavior`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinishBehavior {
+    // the last frame stays
+    Keep,
+    // the line is erased
+    Clear,
+    // the line is replaced with the text
+    ReplaceWith(String),
+    // the line is replaced with the message, the total, the time taken and
+    // the average speed
+    Summary,
+}
+
+// Text drawn inside the bar, centered, see `ProgressBar::set_bar_label`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarLabel {
+    Off,
+    Percent,
+    Counter,
+}
+
+// Direction in which the bar moves, `Fill` starts empty and fills up as
+// work completes, `Drain` starts full and empties, with the counter showing
+// the remaining items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Fill,
+    Drain,
+}
+
+// Terminal row a bar can be pinned to, counted from the top or from the
+// bottom of the terminal, starting at 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Row {
+    Top(u16),
+    Bottom(u16),
+}
+
+// Layout of the drawn line. `Bar` is the default line, `Job` is a cargo-like
+// row with a spinner, a bold verb, the message and a compact bar with the
+// counter on the right, e.g. ` ⠹ Compiling   serde v1.0  [=====>   ] 34 / 120`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layout {
+    Bar,
+    Job(Cow<'static, str>),
+}
+
+// How a bar with a total of 0 is drawn. `Complete` draws it as done,
+// `Spinner` as a bar of unknown size with a spinner instead of the bar and
+// percent, `Error` draws an error instead of the bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZeroTotalPolicy {
+    Complete,
+    Spinner,
+    Error,
+}
+
+// When a bar in milestone-line mode prints a line. `Percent` prints a line
+// every given percent of progress, `Adaptive` prints lines more often at the
+// start and near the end than in the middle (1, 2, 5, 10, 25, 50, 75, 90,
+// 95, 98, 99 and 100%).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Heartbeat {
+    Percent(f64),
+    Adaptive,
+}
+
+// Early part of a job left out of the rate and the ETA, e.g. JIT warmup or
+// cache priming, see `ProgressBar::set_warmup`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warmup {
+    Time(Duration),
+    Items(u64),
+}
+
+// Where the width of the bar came from, see `ProgressBar::width_source`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WidthSource {
+    // set with `set_width`
+    Configured,
+    // size of the terminal
+    Terminal,
+    // the `COLUMNS` environment variable
+    Columns,
+    // set with `set_default_width`
+    Default,
+    // 80 columns
+    Fallback,
+}
+
+// Shared is the state of a bar shared with its views, see `clone_view`.
+#[derive(Debug)]
+struct Shared {
+    current: AtomicU64,
+    total: AtomicU64,
 }
 
 pub struct ProgressBar<T: Write> {
-    start_time: SteadyTime,
+    start_time: Option<SteadyTime>,
+    marked_start: Option<SteadyTime>,
+    // the time spent in the phases before this one, see `chain`
+    chained: Duration,
+    // the time the elapsed clock stopped at, see `pause`
+    paused_at: Option<SteadyTime>,
+    ttfb: Option<time::Duration>,
+    // the rate at the start of the trend window, and the trend of the
+    // previous window, see `show_trend`
+    trend_base: Option<(SteadyTime, f64)>,
+    trend: Option<Trend>,
     units: Units,
+    exact_bytes: ExactBytes,
+    byte_prefix: BytePrefix,
+    separator: Option<char>,
+    unit_label: Cow<'static, str>,
+    direction: Direction,
+    bar_label: BarLabel,
+    finish_behavior: Option<FinishBehavior>,
+    // the error the bar failed with, see `finish_with_error`
+    failure: Option<String>,
+    zero_total: ZeroTotalPolicy,
+    layout: Layout,
+    template: Option<Template>,
+    ascii: bool,
+    // the writer shows colors, see `set_colors`
+    colors: bool,
+    smooth: bool,
+    block: Option<u8>,
+    pub(crate) bar_paint: Option<Paint>,
+    pub(crate) percent_paint: Option<Paint>,
+    pub(crate) message_paint: Option<Paint>,
+    pub(crate) verb_paint: Option<Paint>,
     pub total: u64,
     current: u64,
+    skipped: u64,
+    inflight: u64,
+    adder: Option<Arc<AtomicU64>>,
+    // the state shared with views, and the total when it was last synced
+    view: Option<(Arc<Shared>, u64)>,
+    cancelled: Option<Arc<AtomicBool>>,
+    estimator: Option<Box<dyn Estimator + Send>>,
+    warmup: Option<Warmup>,
+    expected_duration: Option<Duration>,
+    warmup_end: Option<(Duration, u64)>,
+    // the elapsed time and the position the rate is measured from, see
+    // `reset_eta`
+    rate_origin: Option<(Duration, u64)>,
+    journal: Option<Journal>,
+    notifier: Option<Box<dyn Notifier + Send>>,
+    // the last quarter notified, and the last line drawn
+    notified: u64,
+    notify_line: String,
     bar_start: String,
     bar_current: String,
     bar_current_n: String,
@@ -49,25 +269,48 @@ pub struct ProgressBar<T: Write> {
     tick: Vec<String>,
     tick_state: usize,
     width: Option<usize>,
-    message: String,
-    last_refresh_time: SteadyTime,
+    default_width: Option<usize>,
+    width_probe: Option<(usize, WidthSource, SteadyTime)>,
+    message: Cow<'static, str>,
+    verb: Cow<'static, str>,
+    // the width the verb is aligned to, shared by the bars of a MultiBar
+    pub(crate) verb_width: Option<Arc<AtomicUsize>>,
+    last_refresh_time: Option<SteadyTime>,
     max_refresh_rate: Option<time::Duration>,
-    pub(crate) is_multibar: bool,
+    show_after: Option<time::Duration>,
+    percent_step: Option<f64>,
+    last_visible: Option<(u64, i64, u64, usize)>,
+    is_drawn: bool,
+    last_frame: String,
+    pinned_row: Option<Row>,
+    milestone_mode: bool,
+    heartbeat: Heartbeat,
+    last_milestone: Option<u64>,
+    pub(crate) multibar: Option<Link>,
     pub(crate) is_finish: bool,
     pub is_visible: bool,
     pub show_bar: bool,
     pub show_speed: bool,
     pub show_percent: bool,
     pub show_counter: bool,
+    pub show_remaining: bool,
     pub show_time_left: bool,
     pub show_tick: bool,
     pub show_message: bool,
-    handle: T,
+    pub show_ttfb: bool,
+    /// Show an arrow after the speed, `↑`, `↓` or `→`, whether the rate
+    /// went up, down or stayed within 5% over the last 10 seconds, e.g. to
+    /// notice a transfer slowing down. Also the `trend` template field.
+    pub show_trend: bool,
+    handle: Option<T>,
 }
 
 impl ProgressBar<Stdout> {
     /// Create a new ProgressBar with default configuration.
     ///
+    /// If STDOUT is not a tty (e.g. piped to `less` or `tee`), the bar
+    /// switches to milestone-line mode, see `assume_pager`.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -84,8 +327,120 @@ impl ProgressBar<Stdout> {
     /// }
     /// ```
     pub fn new(total: u64) -> ProgressBar<Stdout> {
-        let handle = ::std::io::stdout();
-        ProgressBar::on(handle, total)
+        ProgressBar::on_terminal(::std::io::stdout(), total)
+    }
+
+    /// Create a spinner, a bar of unknown size that shows a tick animation,
+    /// the counter and the elapsed time. It turns into a bar once its total
+    /// is set with `set_total`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, Units};
+    ///
+    /// let mut pb = ProgressBar::new_spinner();
+    /// pb.set_units(Units::Bytes);
+    /// // ...
+    /// # let content_length = 1 << 20;
+    /// pb.set_total(content_length);
+    /// ```
+    pub fn new_spinner() -> ProgressBar<Stdout> {
+        let mut pb = ProgressBar::new(0);
+        pb.set_zero_total_policy(ZeroTotalPolicy::Spinner);
+        pb
+    }
+
+    /// Create a bar of unknown size, drawn as a spinner with the counter,
+    /// the rate and the elapsed time, but no percent, bar or ETA. Its total
+    /// is `u64::MAX`, which is unbounded for any bar, e.g. one created with
+    /// `MultiBar::create_bar(u64::MAX)`.
+    ///
+    /// Unlike the total of a `new_spinner`, `finish` doesn't move the
+    /// counter to it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// # let lines: Vec<String> = vec![];
+    /// let mut pb = ProgressBar::unbounded();
+    /// for _ in lines {
+    ///     pb.inc();
+    /// }
+    /// pb.finish();
+    /// ```
+    pub fn unbounded() -> ProgressBar<Stdout> {
+        ProgressBar::new(UNBOUNDED)
+    }
+
+    /// Create a ProgressBar from a journal written by `journal_to`, e.g.
+    /// after a crash, with the total and the current value it recorded.
+    /// The journal keeps being appended to.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::from_journal("copy.journal").unwrap();
+    /// pb.inc();
+    /// ```
+    pub fn from_journal<P: AsRef<Path>>(path: P) -> io::Result<ProgressBar<Stdout>> {
+        let (total, current) = replay(&path)?;
+        let mut pb = ProgressBar::new(total);
+        pb.current = current;
+        pb.journal = Some(Journal::open(path)?);
+        Ok(pb)
+    }
+}
+
+impl<T: Write + IsTerminal> ProgressBar<T> {
+    /// Create a ProgressBar on a handle that may be a terminal, e.g.
+    /// `stderr()`: colors are drawn only if it is one, see `set_colors`.
+    /// Otherwise (e.g. piped to `less` or `tee`), the bar switches to
+    /// milestone-line mode, see `assume_pager`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io::stderr;
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::on_terminal(stderr(), 100);
+    /// pb.inc();
+    /// ```
+    pub fn on_terminal(handle: T, total: u64) -> ProgressBar<T> {
+        let colors = handle.is_terminal();
+        let mut pb = ProgressBar::on(handle, total);
+        pb.set_colors(colors);
+        pb.detect_pager();
+        pb
+    }
+
+    /// Detect if the writer isn't a terminal, e.g. piped to a pager or
+    /// redirected to a file, and if so, switch to milestone-line mode.
+    /// Returns `true` if it isn't a terminal.
+    ///
+    /// It's done by `new` and `on_terminal`, use it again after changing
+    /// the writer's destination, e.g. a `dup2` of its file descriptor.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io::stderr;
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::on(stderr(), 100);
+    /// pb.detect_pager();
+    /// ```
+    pub fn detect_pager(&mut self) -> bool {
+        let terminal = self.handle.as_ref().is_some_and(|h| h.is_terminal());
+        if !terminal {
+            self.assume_pager();
+        }
+        !terminal
     }
 }
 
@@ -93,6 +448,9 @@ impl<T: Write> ProgressBar<T> {
     /// Create a new ProgressBar with default configuration but
     /// pass an arbitrary writer.
     ///
+    /// The writer isn't known to be a terminal, so no colors are drawn,
+    /// see `on_terminal` and `set_colors`.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -110,21 +468,86 @@ impl<T: Write> ProgressBar<T> {
     /// }
     /// ```
     pub fn on(handle: T, total: u64) -> ProgressBar<T> {
-        let mut pb = ProgressBar {
-            total: total,
+        let mut pb = ProgressBar::const_new();
+        pb.init(handle, total);
+        pb
+    }
+
+    /// Create an unconfigured ProgressBar in a const context, its writer
+    /// and total are supplied later with `init`.
+    ///
+    /// Nothing is drawn until `init` is called.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io::{stdout, Stdout};
+    /// use std::sync::Mutex;
+    /// use pbr::ProgressBar;
+    ///
+    /// static PB: Mutex<ProgressBar<Stdout>> = Mutex::new(ProgressBar::const_new());
+    ///
+    /// PB.lock().unwrap().init(stdout(), 100);
+    /// PB.lock().unwrap().inc();
+    /// ```
+    pub const fn const_new() -> ProgressBar<T> {
+        ProgressBar {
+            total: 0,
             current: 0,
-            start_time: SteadyTime::now(),
+            skipped: 0,
+            inflight: 0,
+            adder: None,
+            view: None,
+            cancelled: None,
+            estimator: None,
+            warmup: None,
+            expected_duration: None,
+            warmup_end: None,
+            rate_origin: None,
+            journal: None,
+            notifier: None,
+            notified: 0,
+            notify_line: String::new(),
+            start_time: None,
+            marked_start: None,
+            chained: Duration::new(0, 0),
+            paused_at: None,
+            ttfb: None,
+            trend_base: None,
+            trend: None,
             units: Units::Default,
+            exact_bytes: ExactBytes::Never,
+            byte_prefix: BytePrefix::Legacy,
+            separator: None,
+            unit_label: Cow::Borrowed(""),
+            direction: Direction::Fill,
+            bar_label: BarLabel::Off,
+            finish_behavior: None,
+            failure: None,
+            zero_total: ZeroTotalPolicy::Complete,
+            layout: Layout::Bar,
+            template: None,
+            ascii: false,
+            colors: false,
+            smooth: false,
+            block: None,
+            bar_paint: None,
+            percent_paint: None,
+            message_paint: None,
+            verb_paint: None,
             is_finish: false,
-            is_multibar: false,
+            multibar: None,
             is_visible: true,
             show_bar: true,
             show_speed: true,
             show_percent: true,
             show_counter: true,
+            show_remaining: false,
             show_time_left: true,
             show_tick: false,
             show_message: true,
+            show_ttfb: false,
+            show_trend: false,
             bar_start: String::new(),
             bar_current: String::new(),
             bar_current_n: String::new(),
@@ -133,14 +556,41 @@ impl<T: Write> ProgressBar<T> {
             tick: Vec::new(),
             tick_state: 0,
             width: None,
-            message: String::new(),
-            last_refresh_time: SteadyTime::now(),
+            default_width: None,
+            width_probe: None,
+            message: Cow::Borrowed(""),
+            verb: Cow::Borrowed(""),
+            verb_width: None,
+            last_refresh_time: None,
             max_refresh_rate: None,
-            handle: handle,
-        };
-        pb.format(FORMAT);
-        pb.tick_format(TICK_FORMAT);
-        pb
+            show_after: None,
+            percent_step: None,
+            last_visible: None,
+            is_drawn: false,
+            last_frame: String::new(),
+            pinned_row: None,
+            milestone_mode: false,
+            heartbeat: Heartbeat::Percent(10.),
+            last_milestone: None,
+            handle: None,
+        }
+    }
+
+    /// Supply the writer and the total of a bar created by `const_new`, and
+    /// start its clock.
+    ///
+    /// The default format and tick format are set, unless they were already
+    /// configured.
+    pub fn init(&mut self, handle: T, total: u64) {
+        self.handle = Some(handle);
+        self.total = total;
+        self.start_time = Some(SteadyTime::now());
+        if self.bar_current.is_empty() {
+            self.try_format(FORMAT).expect("invalid default format");
+        }
+        if self.tick.is_empty() {
+            self.tick_format(TICK_FORMAT);
+        }
     }
 
     /// Set units, default is simple numbers
@@ -158,310 +608,2421 @@ impl<T: Write> ProgressBar<T> {
         self.units = u;
     }
 
-    /// Set custom format to the drawing bar, default is `[=>-]`
+    /// Set what the bar counts, e.g. `"files"` or `"rows"`: the label
+    /// follows the counter and the speed, `1.2k / 50.0k rows 1.2k rows/s`.
+    /// Counts are scaled to thousands or millions as with `Units::Auto`,
+    /// unless the units are `Bytes`. Default is no label.
     ///
     /// # Examples
     ///
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(...);
-    /// pb.format("[=>_]");
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(50_000);
+    /// pb.set_unit_label("rows");
     /// ```
-    pub fn format(&mut self, fmt: &str) {
-        if fmt.len() >= 5 {
-            let v: Vec<&str> = fmt.split("").collect();
-            self.bar_start = v[1].to_owned();
-            self.bar_current = v[2].to_owned();
-            self.bar_current_n = v[3].to_owned();
-            self.bar_remain = v[4].to_owned();
-            self.bar_end = v[5].to_owned();
+    pub fn set_unit_label<S: Into<Cow<'static, str>>>(&mut self, label: S) {
+        self.unit_label = label.into();
+    }
+
+    // unit_suffix returns the unit label with a leading space, or nothing.
+    fn unit_suffix(&self) -> String {
+        if self.unit_label.is_empty() || self.units() == Units::Bytes {
+            String::new()
+        } else {
+            format!(" {}", self.unit_label)
         }
     }
 
-    /// Set message to display in the prefix, call with "" to stop printing a message.
-    ///
-    /// All newlines are replaced with spaces.
+    /// Set a separator between groups of three digits in counts, e.g.
+    /// `Some(',')` for `1,234,567`, or `Some('.')` or `Some(' ')` as used
+    /// in other locales. Default is `None`, plain numbers.
     ///
     /// # Examples
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(20);
     ///
-    /// for x in 0..20 {
-    ///    match x {
-    ///       0 => pb.message("Doing 1st Quarter"),
-    ///       5 => pb.message("Doing 2nd Quarter"),
-    ///       10 => pb.message("Doing 3rd Quarter"),
-    ///       15 => pb.message("Doing 4th Quarter"),
-    ///    }
-    ///    pb.inc().
-    /// }
+    /// ```no_run
+    /// use pbr::ProgressBar;
     ///
+    /// let mut pb = ProgressBar::new(250_000_000);
+    /// pb.set_thousands_separator(Some(','));
     /// ```
-    pub fn message(&mut self, message: &str) {
-        self.message = message.to_owned().replace("\n", " ").replace("\r", " ")
+    pub fn set_thousands_separator(&mut self, sep: Option<char>) {
+        self.separator = sep;
     }
 
-    /// Set tick format for the progressBar, default is \\|/-
+    // count formats a count of items, with the thousands separator if any.
+    fn count(&self, n: u64) -> String {
+        match self.separator {
+            Some(sep) => group_digits(n, sep),
+            None => n.to_string(),
+        }
+    }
+
+    // counter formats `n` out of the total as in the counter box, with the
+    // unit label after the total: `3 / 10 files`.
+    fn counter(&self, n: u64) -> String {
+        let (c, t) = (n as f64, self.total as f64);
+        match self.units() {
+            Units::Default => format!(
+                "{} / {}{}",
+                self.count(n),
+                self.count(self.total),
+                self.unit_suffix()
+            ),
+            Units::Bytes => format!("{} / {}", self.bytes(n), self.bytes(self.total)),
+            Units::Auto(_) => format!("{} / {}{}", scaled(c, t), scaled(t, t), self.unit_suffix()),
+        }
+    }
+
+    // amount formats `n` alone as in the counter box of a spinner: `3 files`.
+    fn amount(&self, n: u64) -> String {
+        let (c, t) = (n as f64, self.total as f64);
+        match self.units() {
+            Units::Default => format!("{}{}", self.count(n), self.unit_suffix()),
+            Units::Bytes => self.bytes(n),
+            Units::Auto(_) => format!("{}{}", scaled(c, t), self.unit_suffix()),
+        }
+    }
+
+    /// Set when sizes in bytes are shown exactly too, next to the humanized
+    /// size: `1.40 GB (1 503 238 553 B)`. Default is `ExactBytes::Never`.
     ///
-    /// Format is not limited to 4 characters, any string can
-    /// be used as a tick format (the tick will successively
-    /// take the value of each char but won't loop backwards).
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ExactBytes, ProgressBar, Units};
     ///
+    /// let mut pb = ProgressBar::new(1_503_238_553);
+    /// pb.set_units(Units::Bytes);
+    /// pb.set_exact_bytes(ExactBytes::OnFinish);
+    /// ```
+    pub fn set_exact_bytes(&mut self, exact: ExactBytes) {
+        self.exact_bytes = exact;
+    }
+
+    /// Set the prefixes of sizes in bytes, in the counter and the speed,
+    /// e.g. `BytePrefix::Decimal` to match tools that report SI sizes.
+    /// Default is `BytePrefix::Legacy`.
     ///
     /// # Examples
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(...);
-    /// pb.tick_format("▀▐▄▌")
+    ///
+    /// ```no_run
+    /// use pbr::{BytePrefix, ProgressBar, Units};
+    ///
+    /// let mut pb = ProgressBar::new(1_500_000_000);
+    /// pb.set_units(Units::Bytes);
+    /// pb.set_byte_prefix(BytePrefix::Decimal);
     /// ```
-    pub fn tick_format(&mut self, tick_fmt: &str) {
-        if tick_fmt != TICK_FORMAT {
-            self.show_tick = true;
+    pub fn set_byte_prefix(&mut self, prefix: BytePrefix) {
+        self.byte_prefix = prefix;
+    }
+
+    // size formats a size or a speed in bytes with the byte prefix.
+    fn size(&self, n: f64) -> String {
+        let (base, names) = match self.byte_prefix {
+            BytePrefix::Legacy => return kb_fmt!(n),
+            BytePrefix::Binary => (1024f64, ["KiB", "MiB", "GiB", "TiB"]),
+            BytePrefix::Decimal => (1000f64, ["kB", "MB", "GB", "TB"]),
+        };
+        for (i, name) in names.iter().enumerate().rev() {
+            let unit = base.powi(i as i32 + 1);
+            if n >= unit {
+                return format!("{:.*} {}", 2, n / unit, name);
+            }
         }
-        self.tick = tick_fmt
-            .split("")
-            .map(|x| x.to_owned())
-            .filter(|x| x != "")
-            .collect();
+        format!("{:.*} B", 0, n)
     }
 
-    /// Set width, or `None` for default.
+    // bytes formats a size in bytes, humanized, and exactly if enabled.
+    fn bytes(&self, n: u64) -> String {
+        let h = n as f64;
+        let exact = match self.exact_bytes {
+            ExactBytes::Never => false,
+            ExactBytes::Always => true,
+            ExactBytes::OnFinish => self.is_done(),
+        };
+        if exact {
+            let sep = self.separator.unwrap_or(' ');
+            format!("{} ({} B)", self.size(h), group_digits(n, sep))
+        } else {
+            self.size(h)
+        }
+    }
+
+    // is_done returns whether the bar finished, or reached its total.
+    fn is_done(&self) -> bool {
+        self.is_finish || (self.total > 0 && self.current >= self.total)
+    }
+
+    // units resolves `Units::Auto`, which is left only for totals scaled to
+    // thousands or millions.
+    fn units(&self) -> Units {
+        match self.units {
+            Units::Auto(Some(UnitsHint::Bytes)) => Units::Bytes,
+            Units::Auto(_) if self.total < AUTO_UNITS_MIN || self.is_unbounded() => Units::Default,
+            Units::Default
+                if !self.unit_label.is_empty()
+                    && self.total >= AUTO_UNITS_MIN
+                    && !self.is_unbounded() =>
+            {
+                Units::Auto(Some(UnitsHint::Items))
+            }
+            u => u,
+        }
+    }
+
+    /// Set the direction of the bar, default is `Direction::Fill`.
+    ///
+    /// With `Direction::Drain` the bar starts full and empties as work
+    /// completes, and the counter shows the remaining items.
     ///
     /// # Examples
     ///
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(...);
-    /// pb.set_width(Some(80));
+    /// ```no_run
+    /// use pbr::{Direction, ProgressBar};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_direction(Direction::Drain);
     /// ```
-    pub fn set_width(&mut self, w: Option<usize>) {
-        self.width = w;
+    pub fn set_direction(&mut self, d: Direction) {
+        self.direction = d;
     }
 
-    /// Set max refresh rate, above which the progress bar will not redraw, or `None` for none.
+    /// Set the text drawn inside the bar, centered over the track, default
+    /// is `BarLabel::Off`.
+    ///
+    /// The label replaces the percent (or the counter) next to the bar, to
+    /// save room on narrow terminals, and is shown in inverse video over the
+    /// filled part when colors are drawn. It's left out when the bar is too
+    /// short for it.
     ///
     /// # Examples
     ///
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(...);
-    /// pb.set_max_refresh_rate(Some(Duration::from_millis(100)));
+    /// ```no_run
+    /// use pbr::{BarLabel, ProgressBar};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_bar_label(BarLabel::Percent);
     /// ```
-    pub fn set_max_refresh_rate(&mut self, w: Option<Duration>) {
-        self.max_refresh_rate = w.map(time::Duration::from_std).map(Result::unwrap);
-        if let Some(dur) = self.max_refresh_rate {
-            self.last_refresh_time = self.last_refresh_time - dur;
-        }
+    pub fn set_bar_label(&mut self, label: BarLabel) {
+        self.bar_label = label;
     }
 
-    /// Update progress bar even though no progress are made
-    /// Useful to see if a program is bricked or just
-    /// not doing any progress.
+    /// Set what is left of the bar when it finishes, default is
+    /// `FinishBehavior::Keep`.
     ///
-    /// tick is not needed with add or inc
-    /// as performed operation take place
-    /// in draw function.
+    /// Once set, it also applies when the bar is dropped without `finish()`,
+    /// instead of being marked as abandoned.
     ///
     /// # Examples
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(...);
-    /// pb.inc();
-    /// for _ in ... {
-    ///    ...do something
-    ///    pb.tick();
-    /// }
-    /// pb.finish();
+    ///
+    /// ```no_run
+    /// use pbr::{FinishBehavior, ProgressBar};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_finish_behavior(FinishBehavior::Summary);
     /// ```
-    pub fn tick(&mut self) {
-        self.tick_state = (self.tick_state + 1) % self.tick.len();
-        if self.current <= self.total {
-            self.draw()
+    pub fn set_finish_behavior(&mut self, behavior: FinishBehavior) {
+        self.finish_behavior = Some(behavior);
+    }
+
+    /// Set how the bar is drawn when its total is 0, default is
+    /// `ZeroTotalPolicy::Complete`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, ZeroTotalPolicy};
+    ///
+    /// # let inputs: Vec<u8> = vec![];
+    /// let mut pb = ProgressBar::new(inputs.len() as u64);
+    /// pb.set_zero_total_policy(ZeroTotalPolicy::Spinner);
+    /// ```
+    pub fn set_zero_total_policy(&mut self, policy: ZeroTotalPolicy) {
+        self.zero_total = policy;
+    }
+
+    /// Set the total, e.g. once the size of a download is known, which
+    /// turns a spinner into a bar.
+    pub fn set_total(&mut self, total: u64) {
+        self.total = total;
+        if let Some(ref mut j) = self.journal {
+            j.record("total", total);
         }
+        self.last_visible = None;
+        self.draw();
     }
 
-    /// Add to current value
+    /// Set the `Estimator` of the rate, from which the speed and the ETA
+    /// are computed, or `None` for the average rate since the start.
+    /// `Exponential` or `SlidingWindow` keep the ETA steady with bursty
+    /// jobs.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use pbr::ProgressBar;
+    /// use std::time::Duration;
+    /// use pbr::{Exponential, ProgressBar, SlidingWindow};
     ///
-    /// let mut pb = ProgressBar::new(10);
-    /// pb.add(5);
-    /// pb.finish();
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_estimator(Some(Box::new(SlidingWindow::new(Duration::from_secs(10)))));
+    /// pb.set_estimator(Some(Box::new(Exponential::new(Duration::from_secs(5)))));
     /// ```
-    pub fn add(&mut self, i: u64) -> u64 {
-        self.current += i;
-        self.tick();
-        self.current
+    pub fn set_estimator(&mut self, estimator: Option<Box<dyn Estimator + Send>>) {
+        self.estimator = estimator;
     }
 
-    /// Manually set the current value of the bar
+    /// Leave the first seconds or items of the job out of the rate and the
+    /// ETA, so a slow start doesn't skew them for the rest of the job.
+    /// Until the warmup is over, the rate is the average since the start.
     ///
     /// # Examples
+    ///
     /// ```no_run
-    /// use pbr::ProgressBar;
+    /// use std::time::Duration;
+    /// use pbr::{ProgressBar, Warmup};
     ///
-    /// let mut pb = ProgressBar::new(10);
-    /// pb.set(8);
-    /// pb.finish();
-    pub fn set(&mut self, i: u64) -> u64 {
-        self.current = i;
-        self.tick();
-        self.current
+    /// let mut pb = ProgressBar::new(10_000);
+    /// pb.set_warmup(Some(Warmup::Time(Duration::from_secs(5))));
+    /// ```
+    pub fn set_warmup(&mut self, warmup: Option<Warmup>) {
+        self.warmup = warmup;
+        self.warmup_end = None;
     }
 
-    /// Increment current value
-    pub fn inc(&mut self) -> u64 {
-        self.add(1)
+    /// Set a `Notifier` told when the bar passes 25, 50 and 75 percent, and
+    /// when it finishes or is dropped without finishing.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_notifier(Some(Box::new(Webhook::new(url))));
+    /// ```
+    pub fn set_notifier(&mut self, notifier: Option<Box<dyn Notifier + Send>>) {
+        self.notifier = notifier;
+    }
+
+    /// Set how long the job is expected to take: a `|` marker on the bar
+    /// shows where the progress should be to be on schedule, so it's
+    /// visible at a glance whether the job is ahead or behind.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(500);
+    /// pb.set_expected_duration(Some(Duration::from_secs(15 * 60)));
+    /// ```
+    pub fn set_expected_duration(&mut self, d: Option<Duration>) {
+        self.expected_duration = d;
+    }
+
+    /// Set the layout of the drawn line, default is `Layout::Bar`.
+    ///
+    /// `Layout::Job` also switches to a braille spinner, use `tick_format`
+    /// afterwards to change it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{Layout, ProgressBar};
+    ///
+    /// let mut pb = ProgressBar::new(120);
+    /// pb.set_layout(Layout::Job("Compiling".into()));
+    /// pb.message("serde v1.0");
+    /// ```
+    pub fn set_layout(&mut self, layout: Layout) {
+        if let Layout::Job(_) = layout {
+            self.tick_format(JOB_TICK_FORMAT);
+        }
+        self.layout = layout;
+    }
+
+    /// Set a template for the drawn line, or `None` for the layout set with
+    /// `set_layout`.
+    ///
+    /// Fields are written `{name}`: `verb` (see `set_verb`), `msg` (or
+    /// `prefix`), `pos`, `total`,
+    /// `bytes` and `total_bytes` (`pos` and `total` in bytes, whatever the
+    /// units), `percent`, `eta`, `eta_avg` (the ETA at the average rate
+    /// since the start, which differs from `eta` with an `Estimator` that
+    /// follows the recent rate), `speed`, `trend` (see `show_trend`),
+    /// `elapsed`, `ttfb` (time to first byte), `tick`, `finished`,
+    /// `skipped`, `inflight` (see `begin_item`)
+    /// and `bar`, which fills
+    /// the rest of the line, or has a fixed width with `{bar:40}`.
+    /// A conditional section, `{?name:body}`, is only
+    /// rendered if the field is known: the message is not empty, the ETA is
+    /// known, the total is not unbounded, the bar is finished... `{{` and
+    /// `}}` are literal braces.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_template(Some("{?msg:{msg}: }{pos}/{total} {bar}{?eta: eta {eta}}{?finished:done!}"))
+    ///     .unwrap();
+    /// pb.set_template(Some("{prefix} {bar:40} {percent} {bytes}/{total_bytes} ({eta})"))
+    ///     .unwrap();
+    /// ```
+    pub fn set_template(&mut self, template: Option<&str>) -> Result<(), TemplateError> {
+        self.template = match template {
+            Some(t) => Some(Template::parse(t)?),
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Draw glyphs (state icons, bar glyphs, spinners, Nerd Font icons)
+    /// with ASCII fallbacks, for terminals without the fonts.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.format("╢▌▌░╟");
+    /// pb.set_ascii(true); // drawn as `[##-]`
+    /// ```
+    pub fn set_ascii(&mut self, ascii: bool) {
+        self.ascii = ascii;
+    }
+
+    /// Draw the colors and attributes of the style, if the writer is a
+    /// terminal. It's detected by `new` and `on_terminal`, and off for a
+    /// writer passed to `on`. `NO_COLOR` and a dumb terminal turn them off
+    /// whatever this setting.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io::{stderr, IsTerminal};
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::on(stderr().lock(), 100);
+    /// pb.set_colors(stderr().is_terminal());
+    /// ```
+    pub fn set_colors(&mut self, colors: bool) {
+        self.colors = colors;
+    }
+
+    /// Draw the bar with full and partial Unicode blocks, `█▉▊▋▌▍▎▏`, which
+    /// move by eighths of a cell, so a bar with a huge total doesn't look
+    /// frozen. The glyphs of the format are used in ASCII mode.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(1_000_000);
+    /// pb.set_smooth(true); // drawn as `[███▍      ]`
+    /// ```
+    pub fn set_smooth(&mut self, smooth: bool) {
+        self.smooth = smooth;
+    }
+
+    /// Draw the filled part of the bar as blank cells on a background of
+    /// `color` (an ANSI 256 colors index), instead of the bar glyphs.
+    ///
+    /// Falls back to the glyphs when colors are turned off, by `NO_COLOR`,
+    /// `TERM=dumb` or milestone-line mode.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_block_style(Some(33)); // blue
+    /// ```
+    pub fn set_block_style(&mut self, color: Option<u8>) {
+        self.block = color;
+    }
+
+    /// Pin the bar to a fixed terminal row, or `None` to draw it on the
+    /// current line (the default).
+    ///
+    /// A pinned bar is drawn with absolute cursor addressing and the cursor
+    /// is put back after each frame, so the program can keep printing
+    /// normally. It has no effect on bars of a MultiBar, or in milestone
+    /// mode.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, Row};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.pin_to_row(Some(Row::Bottom(0)));
+    /// ```
+    pub fn pin_to_row(&mut self, row: Option<Row>) {
+        self.pinned_row = row;
+        self.last_frame.clear();
+    }
+
+    /// Set custom format to the drawing bar, default is `[=>-]`
+    ///
+    /// The format is either exactly 5 glyphs: start cap, fill, tip, remain
+    /// and end cap, or the same 5 segments separated by commas, where the
+    /// caps and the tip can be empty or longer than one glyph. The fill and
+    /// remain segments are always a single glyph. Segments may be colored
+    /// with escape sequences. An invalid format is ignored, see
+    /// `try_format` to get the error.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.format("[=>_]");
+    /// pb.format("▕,█,▓▒,░,▏");
+    /// pb.format("⟦ ,=,==>,-, ⟧");
+    /// ```
+    pub fn format(&mut self, fmt: &str) {
+        let _ = self.try_format(fmt);
+    }
+
+    /// Set custom format to the drawing bar like `format`, or return why
+    /// it's invalid, leaving the format unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{FormatError, ProgressBar};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// assert_eq!(pb.try_format("[=>-"), Err(FormatError::Segments(4)));
+    /// ```
+    pub fn try_format(&mut self, fmt: &str) -> Result<(), FormatError> {
+        let mut v = parse_format(fmt)?.into_iter();
+        self.bar_start = v.next().unwrap();
+        self.bar_current = v.next().unwrap();
+        self.bar_current_n = v.next().unwrap();
+        self.bar_remain = v.next().unwrap();
+        self.bar_end = v.next().unwrap();
+        Ok(())
+    }
+
+    /// Set message to display in the prefix, call with "" to stop printing a message.
+    ///
+    /// All newlines are replaced with spaces. A `&'static str` is used
+    /// as is, without allocating.
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(20);
+    ///
+    /// for x in 0..20 {
+    ///    match x {
+    ///       0 => pb.message("Doing 1st Quarter"),
+    ///       5 => pb.message("Doing 2nd Quarter"),
+    ///       10 => pb.message("Doing 3rd Quarter"),
+    ///       15 => pb.message("Doing 4th Quarter"),
+    ///    }
+    ///    pb.inc().
+    /// }
+    ///
+    /// ```
+    pub fn message<S: Into<Cow<'static, str>>>(&mut self, message: S) {
+        let message = message.into();
+        self.message = if message.contains(['\n', '\r']) {
+            Cow::Owned(message.replace("\n", " ").replace("\r", " "))
+        } else {
+            message
+        };
+        self.last_visible = None;
+    }
+
+    /// Set a verb drawn before the message, right-aligned like the
+    /// `Compiling` and `Downloading` of cargo, call with "" for none.
+    ///
+    /// The verbs of the bars of a `MultiBar` are aligned to the widest one.
+    /// The verb is bold green, see `Style::paint_verb`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// let mut p1 = mb.create_bar(100);
+    /// p1.set_verb("Downloading");
+    /// p1.message("serde ");
+    /// let mut p2 = mb.create_bar(100);
+    /// p2.set_verb("Compiling");
+    /// p2.message("pbr ");
+    /// ```
+    pub fn set_verb<S: Into<Cow<'static, str>>>(&mut self, verb: S) {
+        self.verb = verb.into();
+        if let Some(ref w) = self.verb_width {
+            w.fetch_max(glyph::width(&self.verb), Ordering::Relaxed);
+        }
+        self.last_visible = None;
+    }
+
+    /// Set tick format for the progressBar, default is \\|/-
+    ///
+    /// Format is not limited to 4 characters, any string can
+    /// be used as a tick format (the tick will successively
+    /// take the value of each char but won't loop backwards).
+    ///
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.tick_format("▀▐▄▌")
+    /// ```
+    pub fn tick_format(&mut self, tick_fmt: &str) {
+        if tick_fmt != TICK_FORMAT {
+            self.show_tick = true;
+        }
+        self.tick = tick_fmt
+            .split("")
+            .map(|x| x.to_owned())
+            .filter(|x| !x.is_empty())
+            .collect();
+    }
+
+    /// Set width, or `None` for default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_width(Some(80));
+    /// ```
+    pub fn set_width(&mut self, w: Option<usize>) {
+        self.width = w;
+    }
+
+    /// Set the width used when it can't be determined from the terminal or
+    /// the `COLUMNS` environment variable, or `None` for 80 columns.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_default_width(Some(120));
+    /// ```
+    pub fn set_default_width(&mut self, w: Option<usize>) {
+        self.default_width = w;
+        self.width_probe = None;
+    }
+
+    /// Returns where the width of the bar comes from.
+    ///
+    /// The width is, in order, the one set with `set_width`, the terminal
+    /// size, the `COLUMNS` environment variable, the one set with
+    /// `set_default_width`, or 80 columns. When the terminal size is not
+    /// available, it is probed again every second, in case a terminal
+    /// appears later.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, WidthSource};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// if pb.width_source() == WidthSource::Fallback {
+    ///     pb.set_width(Some(100));
+    /// }
+    /// ```
+    pub fn width_source(&mut self) -> WidthSource {
+        self.probe_width().1
+    }
+
+    /// Apply a `Style`, replacing the settings it holds.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, Style};
+    ///
+    /// let mut style = Style::new();
+    /// style.show_speed = false;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_style(&style);
+    /// ```
+    pub fn set_style(&mut self, style: &Style) {
+        style.apply(self);
+    }
+
+    /// Set max refresh rate, above which the progress bar will not redraw, or `None` for none.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_max_refresh_rate(Some(Duration::from_millis(100)));
+    /// ```
+    pub fn set_max_refresh_rate(&mut self, w: Option<Duration>) {
+        self.max_refresh_rate = w.map(time::Duration::from_std).map(Result::unwrap);
+    }
+
+    /// Set a delay before the bar is drawn for the first time, or `None` to
+    /// draw it right away.
+    ///
+    /// Operations that finish within the delay won't print anything, the slow
+    /// ones are rendered as usual (the elapsed time and speed still count
+    /// from the creation of the bar).
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_show_after(Some(Duration::from_millis(500)));
+    /// ```
+    pub fn set_show_after(&mut self, d: Option<Duration>) {
+        self.show_after = d.map(time::Duration::from_std).map(Result::unwrap);
+    }
+
+    /// Set the percent step, under which the bar is not redrawn, or `None` to
+    /// redraw on every update.
+    ///
+    /// With a step, the bar is redrawn only when the percent moves to another
+    /// step, or the displayed speed or time left change noticeably. Useful for
+    /// huge totals, where thousands of `inc()`s don't change anything visible.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_percent_step(Some(0.5));
+    /// ```
+    pub fn set_percent_step(&mut self, step: Option<f64>) {
+        self.percent_step = step;
+        self.last_visible = None;
+    }
+
+    /// Assume the output goes to a pager or a log file, where redrawing the
+    /// bar in place is useless.
+    ///
+    /// In this milestone-line mode, the bar is printed on its own line every
+    /// time the progress passes another 10%, see `set_heartbeat`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.assume_pager();
+    /// ```
+    pub fn assume_pager(&mut self) {
+        self.milestone_mode = true;
+    }
+
+    /// Set when a line is printed in milestone-line mode, default is
+    /// `Heartbeat::Percent(10.)`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{Heartbeat, ProgressBar};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_heartbeat(Heartbeat::Adaptive);
+    /// ```
+    pub fn set_heartbeat(&mut self, heartbeat: Heartbeat) {
+        self.heartbeat = heartbeat;
+        self.last_milestone = None;
+    }
+
+    /// Update progress bar even though no progress are made
+    /// Useful to see if a program is bricked or just
+    /// not doing any progress.
+    ///
+    /// tick advances the spinner and redraws the elapsed time and the
+    /// speed, the position doesn't change. It is not needed with add or
+    /// inc, which tick too.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    /// # fn poll() -> bool { true }
+    ///
+    /// let mut pb = ProgressBar::new(1);
+    /// pb.show_tick = true;
+    /// // waiting on a remote job
+    /// while !poll() {
+    ///     pb.tick();
+    /// }
+    /// pb.inc();
+    /// pb.finish();
+    /// ```
+    pub fn tick(&mut self) {
+        self.tick_state = (self.tick_state + 1) % self.tick.len().max(1);
+        if self.current <= self.total || self.spinner() {
+            self.draw()
+        }
+    }
+
+    /// Add to current value
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.add(5);
+    /// pb.finish();
+    /// ```
+    pub fn add(&mut self, i: u64) -> u64 {
+        self.current += i;
+        if let Some((ref shared, _)) = self.view {
+            shared.current.fetch_add(i, Ordering::Relaxed);
+        }
+        if let Some(ref mut j) = self.journal {
+            j.record("add", i);
+        }
+        self.record_ttfb();
+        self.tick();
+        self.current
+    }
+
+    /// Add `n` skipped items, e.g. files already up to date: they count as
+    /// progress, but are shown apart from the processed items, dimmed in
+    /// the bar and as `(n skipped)` after the counter.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(files.len() as u64);
+    /// for f in files {
+    ///     if is_synced(f) {
+    ///         pb.inc_skipped(1);
+    ///     } else {
+    ///         sync(f);
+    ///         pb.inc();
+    ///     }
+    /// }
+    /// ```
+    pub fn inc_skipped(&mut self, n: u64) -> u64 {
+        self.skipped += n;
+        self.add(n)
+    }
+
+    /// Returns the number of skipped items, see `inc_skipped`.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Record that an item was started, it's in flight until `end_item`.
+    /// Items in flight are shown by the `inflight` template field, e.g. to
+    /// see the concurrency of a pipeline; the progress is still counted
+    /// with `inc` and `add`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(jobs.len() as u64);
+    /// pb.set_template(Some("{pos}/{total} {bar}{?inflight: {inflight} running}"))
+    ///     .unwrap();
+    /// for job in jobs {
+    ///     pb.begin_item();
+    ///     job.run();
+    ///     pb.end_item();
+    ///     pb.inc();
+    /// }
+    /// ```
+    pub fn begin_item(&mut self) -> u64 {
+        self.inflight += 1;
+        self.draw();
+        self.inflight
+    }
+
+    /// Record that an item started with `begin_item` ended.
+    pub fn end_item(&mut self) -> u64 {
+        self.inflight = self.inflight.saturating_sub(1);
+        self.draw();
+        self.inflight
+    }
+
+    /// Returns the number of items in flight, see `begin_item`.
+    pub fn inflight(&self) -> u64 {
+        self.inflight
+    }
+
+    /// Manually set the current value of the bar
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.set(8);
+    /// pb.finish();
+    pub fn set(&mut self, i: u64) -> u64 {
+        self.current = i;
+        if let Some((ref shared, _)) = self.view {
+            shared.current.store(i, Ordering::Relaxed);
+        }
+        if let Some(ref mut j) = self.journal {
+            j.record("set", i);
+        }
+        self.record_ttfb();
+        self.tick();
+        self.current
+    }
+
+    /// Append every change of the bar to the journal at `path`, with a
+    /// timestamp, to reconstruct its exact state with `from_journal` after
+    /// a crash.
+    ///
+    /// The total and current value are recorded first, an error is returned
+    /// if they can't be written. A later failed write stops the journal
+    /// instead of the job, see `journal_error`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.journal_to("copy.journal").unwrap();
+    /// ```
+    pub fn journal_to<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let mut j = Journal::open(path)?;
+        j.record("total", self.total);
+        j.record("set", self.current);
+        self.journal = Some(j.into_result()?);
+        Ok(())
+    }
+
+    /// Returns the failed write that stopped the journal, see `journal_to`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.journal_to("copy.journal").unwrap();
+    /// // ...
+    /// if let Some(e) = pb.journal_error() {
+    ///     eprintln!("the journal stopped: {}", e);
+    /// }
+    /// pb.finish();
+    /// ```
+    pub fn journal_error(&self) -> Option<&io::Error> {
+        self.journal.as_ref().and_then(|j| j.error())
+    }
+
+    /// Returns an `Adder`, a handle that adds to this bar from another
+    /// thread, or wraps a reader or writer.
+    ///
+    /// The counts of all the adders are added to the bar when it draws,
+    /// call `tick` periodically to keep it drawing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io::{copy, sink};
+    /// use std::thread;
+    /// use pbr::{ProgressBar, Units};
+    ///
+    /// # let ranges: Vec<&[u8]> = vec![b"abc", b"def"];
+    /// let mut pb = ProgressBar::new(6);
+    /// pb.set_units(Units::Bytes);
+    /// let threads: Vec<_> = ranges
+    ///     .into_iter()
+    ///     .map(|range| {
+    ///         let mut reader = pb.split_handle().wrap_read(range);
+    ///         thread::spawn(move || copy(&mut reader, &mut sink()))
+    ///     })
+    ///     .collect();
+    /// for t in threads {
+    ///     t.join().unwrap().unwrap();
+    /// }
+    /// pb.finish();
+    /// ```
+    pub fn split_handle(&mut self) -> Adder {
+        let count = self
+            .adder
+            .get_or_insert_with(|| Arc::new(AtomicU64::new(0)));
+        Adder::new(count.clone())
+    }
+
+    /// Wrap a reader, adding the number of bytes read to the bar on every
+    /// `read`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::io::copy;
+    /// use pbr::{ProgressBar, Units};
+    ///
+    /// let mut file = File::open("/usr/share/dict/words").unwrap();
+    /// let mut pb = ProgressBar::new(file.metadata().unwrap().len());
+    /// pb.set_units(Units::Bytes);
+    /// let mut copy_file = File::create("copy-words").unwrap();
+    /// copy(&mut pb.wrap_read(file), &mut copy_file).unwrap();
+    /// pb.finish_print("done");
+    /// ```
+    pub fn wrap_read<R: Read>(&mut self, inner: R) -> ProgressReader<'_, R, T> {
+        ProgressReader::new(inner, self)
+    }
+
+    /// Wrap a writer, adding the number of bytes written to the bar on
+    /// every `write`, e.g. when piping into a compressor or a socket.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io::{copy, stdin};
+    /// use std::net::TcpStream;
+    /// use pbr::{ProgressBar, Units};
+    ///
+    /// # let size = 1 << 20;
+    /// let mut pb = ProgressBar::new(size);
+    /// pb.set_units(Units::Bytes);
+    /// let socket = TcpStream::connect("127.0.0.1:9000").unwrap();
+    /// copy(&mut stdin(), &mut pb.wrap_write(socket)).unwrap();
+    /// pb.finish();
+    /// ```
+    pub fn wrap_write<W: Write>(&mut self, inner: W) -> ProgressWriter<'_, W, T> {
+        ProgressWriter::new(inner, self)
+    }
+
+    /// Wrap a tokio `AsyncRead`, adding the number of bytes read to the bar
+    /// on every poll, see `wrap_read`. Requires the `tokio` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let file = tokio::fs::File::open("/usr/share/dict/words").await?;
+    /// let mut pb = ProgressBar::new(file.metadata().await?.len());
+    /// pb.set_units(Units::Bytes);
+    /// let mut out = tokio::fs::File::create("copy-words").await?;
+    /// tokio::io::copy(&mut pb.wrap_async_read(file), &mut out).await?;
+    /// pb.finish();
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn wrap_async_read<R>(&mut self, inner: R) -> ProgressAsyncReader<'_, R, T>
+    where
+        R: ::tokio::io::AsyncRead + Unpin,
+    {
+        ProgressAsyncReader::new(inner, self)
+    }
+
+    /// Wrap a tokio `AsyncWrite`, adding the number of bytes written to the
+    /// bar on every poll, see `wrap_write`. Requires the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub fn wrap_async_write<W>(&mut self, inner: W) -> ProgressAsyncWriter<'_, W, T>
+    where
+        W: ::tokio::io::AsyncWrite + Unpin,
+    {
+        ProgressAsyncWriter::new(inner, self)
+    }
+
+    /// Returns a handle to cancel the bar from another thread, e.g. from a
+    /// Ctrl-C handler.
+    pub fn cancel_handle(&mut self) -> CancelHandle {
+        let cancelled = self
+            .cancelled
+            .get_or_insert_with(|| Arc::new(AtomicBool::new(false)));
+        CancelHandle::new(cancelled.clone())
+    }
+
+    /// Returns whether the bar was cancelled with a `CancelHandle`.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+            .as_ref()
+            .is_some_and(|c| c.load(Ordering::Relaxed))
+    }
+
+    /// Sleep for `d`, while the bar keeps ticking, e.g. between polls or
+    /// retries. Returns `false` if the bar was cancelled before `d` is
+    /// over, see `cancel_handle`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// while !job.done() {
+    ///     if !pb.sleep(Duration::from_secs(5)) {
+    ///         break;
+    ///     }
+    ///     pb.set(job.progress());
+    /// }
+    /// ```
+    pub fn sleep(&mut self, d: Duration) -> bool {
+        let deadline = Instant::now() + d;
+        let step = self
+            .max_refresh_rate
+            .map_or(SLEEP_TICK, time_to_std)
+            .max(Duration::from_millis(1));
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            thread::sleep(step.min(deadline - now));
+            self.tick();
+        }
+    }
+
+    /// Create a view of the bar on `handle`: the view and the bar share the
+    /// position and the total, but each has its own message and settings,
+    /// e.g. a detailed bar on stderr and a compact one in a log.
+    ///
+    /// The units and the format are copied. Each view draws on its own
+    /// `tick`, and may change the progress too.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::on(std::io::stderr(), 1000);
+    /// let mut log = pb.clone_view(File::create("progress.log").unwrap());
+    /// log.set_max_refresh_rate(Some(std::time::Duration::from_secs(10)));
+    /// log.show_speed = false;
+    /// pb.add(10);
+    /// log.tick();
+    /// ```
+    pub fn clone_view<W: Write>(&mut self, handle: W) -> ProgressBar<W> {
+        let (current, total) = (self.current, self.total);
+        let shared = &self
+            .view
+            .get_or_insert_with(|| {
+                let shared = Shared {
+                    current: AtomicU64::new(current),
+                    total: AtomicU64::new(total),
+                };
+                (Arc::new(shared), total)
+            })
+            .0;
+        let mut view = ProgressBar::const_new();
+        view.view = Some((shared.clone(), total));
+        view.current = current;
+        view.units = self.units;
+        view.bar_start = self.bar_start.clone();
+        view.bar_current = self.bar_current.clone();
+        view.bar_current_n = self.bar_current_n.clone();
+        view.bar_remain = self.bar_remain.clone();
+        view.bar_end = self.bar_end.clone();
+        view.init(handle, total);
+        view
+    }
+
+    // collect adds the counts of the adders to the bar.
+    fn collect(&mut self) {
+        if let Some(ref count) = self.adder {
+            let n = count.swap(0, Ordering::Relaxed);
+            self.current += n;
+            if let Some((ref shared, _)) = self.view {
+                shared.current.fetch_add(n, Ordering::Relaxed);
+            }
+            match self.journal {
+                Some(ref mut j) if n > 0 => j.record("add", n),
+                _ => {}
+            }
+        }
+        if let Some((ref shared, ref mut seen_total)) = self.view {
+            self.current = shared.current.load(Ordering::Relaxed);
+            // the total is a public field, a change is noticed here
+            if self.total != *seen_total {
+                shared.total.store(self.total, Ordering::Relaxed);
+            } else {
+                self.total = shared.total.load(Ordering::Relaxed);
+            }
+            *seen_total = self.total;
+        }
+        self.record_ttfb();
+    }
+
+    /// Mark the start of the operation, from which the time to first byte
+    /// is measured, instead of the creation of the bar.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// let conn = connect()?;
+    /// pb.mark_started();
+    /// // ...
+    /// ```
+    pub fn mark_started(&mut self) {
+        self.marked_start = Some(SteadyTime::now());
+        self.ttfb = None;
+    }
+
+    /// Returns the time to first byte: the time from the creation of the
+    /// bar, or `mark_started`, to the first increment.
+    pub fn ttfb(&self) -> Option<Duration> {
+        self.ttfb.map(time_to_std)
+    }
+
+    /// Stop the elapsed clock until `resume`, the paused time is left out
+    /// of the elapsed time, the rate and the ETA.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// // ...
+    /// pb.pause();
+    /// let overwrite = confirm("overwrite?");
+    /// pb.resume();
+    /// ```
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(SteadyTime::now());
+        }
+    }
+
+    /// Restart the elapsed clock stopped by `pause`.
+    pub fn resume(&mut self) {
+        if let Some(at) = self.paused_at.take() {
+            let paused = SteadyTime::now() - at;
+            for t in [&mut self.start_time, &mut self.marked_start] {
+                if let Some(ref mut t) = *t {
+                    *t = *t + paused;
+                }
+            }
+            if let Some((ref mut t, _)) = self.trend_base {
+                *t = *t + paused;
+            }
+        }
+    }
+
+    /// Returns whether the elapsed clock is stopped, see `pause`.
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    // clock returns the time on the elapsed clock: now, unless paused.
+    fn clock(&self) -> SteadyTime {
+        self.paused_at.unwrap_or_else(SteadyTime::now)
+    }
+
+    // rate returns the rate of the bar, leaving out the warmup once it's over.
+    fn rate(&mut self, elapsed: Duration) -> f64 {
+        if self.warmup_end.is_none() {
+            let over = match self.warmup {
+                Some(Warmup::Time(d)) => elapsed >= d,
+                Some(Warmup::Items(n)) => self.current >= n,
+                None => false,
+            };
+            if over {
+                self.warmup_end = Some((elapsed, self.current));
+            }
+        }
+        let (mut elapsed, mut current) = (elapsed, self.current);
+        let mut origin = self.rate_origin;
+        if let (Some(_), Some((t0, n0))) = (self.warmup, self.warmup_end) {
+            if elapsed <= t0 && t0 > Duration::new(0, 0) {
+                // the warmup just ended, keep its average until there is more
+                return n0 as f64 / fract_dur(t0);
+            }
+            origin = origin.max(Some((t0, n0)));
+        }
+        if let Some((t0, n0)) = origin {
+            elapsed = elapsed.saturating_sub(t0);
+            current = current.saturating_sub(n0);
+        }
+        match self.estimator {
+            Some(ref mut e) => {
+                e.record(current, elapsed);
+                e.rate()
+            }
+            None => current as f64 / fract_dur(elapsed),
+        }
+    }
+
+    // notify_progress notifies the quarters passed since the last draw.
+    fn notify_progress(&mut self, line: &str) {
+        if self.notifier.is_none() {
+            return;
+        }
+        self.notify_line = glyph::strip(line).trim_end().to_owned();
+        let quarter = ((self.percent_value() / 25.) as u64).min(3);
+        if quarter > self.notified {
+            self.notified = quarter;
+            self.notify(Milestone::Percent(quarter as u8 * 25));
+        }
+    }
+
+    fn notify(&mut self, milestone: Milestone) {
+        if let Some(ref mut n) = self.notifier {
+            n.notify(milestone, &self.notify_line);
+        }
+    }
+
+    // record_ttfb records the time to first byte, on the first increment.
+    fn record_ttfb(&mut self) {
+        if self.ttfb.is_some() || self.current == 0 {
+            return;
+        }
+        if let Some(start) = self.marked_start.or(self.start_time) {
+            self.ttfb = Some(self.clock() - start);
+        }
+    }
+
+    /// Increment current value
+    pub fn inc(&mut self) -> u64 {
+        self.add(1)
+    }
+
+    /// Turn the bar into a `SharedBar` drawn every `interval` from a
+    /// background thread, so a long step doesn't look hung, see
+    /// `SharedBar::enable_steady_tick`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use pbr::ProgressBar;
+    ///
+    /// let pb = ProgressBar::new_spinner().enable_steady_tick(Duration::from_millis(100));
+    /// // a long step, without progress
+    /// pb.inc();
+    /// pb.finish();
+    /// ```
+    pub fn enable_steady_tick(self, interval: Duration) -> SharedBar<T>
+    where
+        T: Send + 'static,
+    {
+        let pb = SharedBar::new(self);
+        pb.enable_steady_tick(interval);
+        pb
+    }
+
+    /// Returns `true` if the bar was finished.
+    pub fn is_finished(&self) -> bool {
+        self.is_finish
+    }
+
+    /// Returns the completed fraction of the total, between `0.0` and `1.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(4);
+    /// pb.inc();
+    /// assert_eq!(pb.fraction(), 0.25);
+    /// assert_eq!(pb.percent(), 25);
+    /// ```
+    ///
+    /// A bar with a total of 0 is complete, unless its `ZeroTotalPolicy` is
+    /// not `Complete`, and an unbounded bar is at 0.
+    pub fn fraction(&self) -> f64 {
+        if self.is_unbounded() {
+            return 0.;
+        }
+        if self.total == 0 {
+            return match self.zero_total {
+                ZeroTotalPolicy::Complete => 1.,
+                _ => 0.,
+            };
+        }
+        (self.current as f64 / self.total as f64).min(1.)
+    }
+
+    // spinner is true when the total is unknown, and a spinner is drawn
+    // instead of the bar.
+    fn spinner(&self) -> bool {
+        self.is_unbounded() || self.total == 0 && self.zero_total == ZeroTotalPolicy::Spinner
+    }
+
+    // is_unbounded is true when the total is `UNBOUNDED`, see `unbounded`.
+    fn is_unbounded(&self) -> bool {
+        self.total == UNBOUNDED
+    }
+
+    // remaining returns the items left, from which the ETA is computed, if
+    // the total is known and some progress was made.
+    fn remaining(&self) -> Option<u64> {
+        if self.current > 0 && self.total > self.current && !self.is_unbounded() {
+            Some(self.total - self.current)
+        } else {
+            None
+        }
+    }
+
+    // percent_value is the percent shown by the bar, above 100 if current
+    // went past the total.
+    fn percent_value(&self) -> f64 {
+        if self.total == 0 || self.is_unbounded() {
+            self.fraction() * 100.
+        } else {
+            self.current as f64 / (self.total as f64 / 100f64)
+        }
+    }
+
+    /// Returns the completed percent of the total, between `0` and `100`.
+    pub fn percent(&self) -> u8 {
+        (self.fraction() * 100.) as u8
+    }
+
+    /// Returns a plain rendition of the bar in `max_cols` columns, e.g.
+    /// `[####.......] 42% 3m`, to embed in a tmux status line or a `watch`
+    /// pane. It has no escape codes and doesn't depend on the style of the
+    /// bar. The ETA, then the bar, are left out if there is no room.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::on(Vec::new(), 100);
+    /// pb.add(42);
+    /// std::fs::write("/tmp/job.status", pb.render_compact(20)).unwrap();
+    /// ```
+    pub fn render_compact(&self, max_cols: usize) -> String {
+        let percent = if self.is_unbounded() {
+            self.count(self.current)
+        } else {
+            format!("{}%", self.percent())
+        };
+        let elapsed = self
+            .start_time
+            .map_or(0., |t| fract_dur(time_to_std(self.clock() - t)));
+        let eta = self
+            .remaining()
+            .and_then(|n| eta(n, self.current as f64 / elapsed))
+            .map(|left| fmt_secs(fract_dur(left)));
+        let short = format!(" {}", percent);
+        let long = eta.map_or(short.clone(), |eta| format!("{} {}", short, eta));
+        // the bar takes its brackets and at least 2 cells
+        let tail = if max_cols >= long.len() + 4 {
+            long
+        } else if max_cols >= short.len() + 4 {
+            short
+        } else if max_cols >= percent.len() {
+            return percent;
+        } else {
+            return String::new();
+        };
+        let cells = max_cols - tail.len() - 2;
+        let filled = ((self.fraction() * cells as f64) as usize).min(cells);
+        format!(
+            "[{}{}]{}",
+            "#".repeat(filled),
+            ".".repeat(cells - filled),
+            tail
+        )
     }
 
     fn draw(&mut self) {
+        self.collect();
         let now = SteadyTime::now();
-        if let Some(mrr) = self.max_refresh_rate {
-            if now - self.last_refresh_time < mrr {
+        let start_time = match self.start_time {
+            Some(t) => t,
+            None => return,
+        };
+        if let (Some(mrr), Some(last)) = (self.max_refresh_rate, self.last_refresh_time) {
+            if now - last < mrr {
+                return;
+            }
+        }
+
+        if let Some(delay) = self.show_after {
+            if now - start_time < delay {
+                return;
+            }
+        }
+
+        if !self.is_visible {
+            self.last_frame.clear();
+            self.print(format_args!(""));
+            return;
+        }
+
+        let milestone = self.milestone();
+        if self.milestone_mode && self.last_milestone == Some(milestone) {
+            return;
+        }
+
+        let clock = self.paused_at.unwrap_or(now);
+        let time_elapsed = time_to_std(clock - start_time);
+        let speed_value = self.rate(time_elapsed);
+        self.update_trend(clock, speed_value);
+
+        if let Some(step) = self.percent_step {
+            let visible = self.visible_state(step, speed_value);
+            if self.last_visible == Some(visible) {
                 return;
             }
+            self.last_visible = Some(visible);
+        }
+
+        let width = self.width();
+        let filled = match self.direction {
+            Direction::Fill => self.current,
+            Direction::Drain => self.total.saturating_sub(self.current),
+        };
+
+        let mut out = match self.template {
+            _ if self.total == 0 && self.zero_total == ZeroTotalPolicy::Error => {
+                "error: total is 0".to_owned()
+            }
+            Some(ref t) => self.template_line(t, width, filled, speed_value, time_elapsed),
+            None => match self.layout {
+                Layout::Bar => self.bar_line(width, filled, speed_value, time_elapsed),
+                Layout::Job(ref verb) => self.job_line(verb, width, filled),
+            },
+        };
+
+        self.notify_progress(&out);
+
+        if self.milestone_mode {
+            self.last_milestone = Some(milestone);
+            self.print(format_args!("{}\n", out.trim_end()));
+            self.is_drawn = true;
+            self.last_refresh_time = Some(SteadyTime::now());
+            return;
+        }
+
+        // pad
+        let out_width = glyph::width(&out);
+        if out_width < width {
+            out += repeat!(" ", width - out_width);
+        }
+        // skip a frame identical to the one on screen
+        if out != self.last_frame {
+            self.print_frame(&out);
+            self.last_frame = out;
+        }
+
+        self.is_drawn = true;
+        self.last_refresh_time = Some(SteadyTime::now());
+    }
+
+    // bar_line builds the default line: message, counter, tick, bar, percent,
+    // speed and time left, or the elapsed time for a spinner.
+    fn bar_line(&self, width: usize, filled: u64, speed_value: f64, elapsed: Duration) -> String {
+        let mut len = 0;
+        let mut percent = String::new();
+        let mut speed = String::new();
+        let mut time_left = String::new();
+        let mut message = String::new();
+        let mut counter = String::new();
+        let mut tick = String::new();
+        let mut bar = String::new();
+        let mut ttfb = String::new();
+        let mut failure = String::new();
+
+        // verb box
+        let verb = self.verb_box();
+        len += glyph::width(&verb);
+        // percent
+        if self.show_percent && !self.spinner() && self.bar_label != BarLabel::Percent {
+            percent = format!(" {:.*} % ", 2, self.percent_value());
+            len += glyph::width(&percent);
+        }
+        // failure box, instead of the speed and time left
+        if let Some(ref error) = self.failure {
+            let sep = if percent.is_empty() { " " } else { "" };
+            failure = match error.as_str() {
+                "" => format!("{}{} ", sep, self.glyphs("✗")),
+                e => format!("{}{} {} ", sep, self.glyphs("✗"), e),
+            };
+            if len + glyph::width(&failure) > width {
+                failure = truncate(&failure, width.saturating_sub(len)).to_owned();
+            }
+            len += glyph::width(&failure);
+        }
+        let running = self.failure.is_none();
+        // speed box
+        let units = self.units();
+        let unit = self.unit_suffix();
+        if self.show_speed && running {
+            speed = match units {
+                Units::Default if self.show_remaining && unit.is_empty() => {
+                    format!("{:.*} items/s ", 2, speed_value)
+                }
+                Units::Default => format!("{:.*}{}/s ", 2, speed_value, unit),
+                Units::Bytes => format!("{}/s ", self.size(speed_value)),
+                Units::Auto(_) => format!("{}{}/s ", scaled(speed_value, speed_value), unit),
+            };
+            if let Some(arrow) = self.trend_arrow() {
+                speed = speed + &arrow + " ";
+            }
+            // a spinner has no percent box to space it from the tick
+            if self.spinner() {
+                speed.insert(0, ' ');
+            }
+            if len + glyph::width(&speed) > width {
+                speed = String::new();
+            }
+            len += glyph::width(&speed);
+        }
+        // time left box
+        let left = self.remaining().and_then(|n| eta(n, speed_value));
+        if let Some(left) = left.filter(|_| running && self.show_time_left) {
+            time_left = fmt_secs(fract_dur(left));
+            len += glyph::width(&time_left);
+        } else if running && self.show_time_left && self.spinner() {
+            let sep = if speed.is_empty() { " " } else { "" };
+            time_left = format!("{}{}", sep, fmt_secs(fract_dur(elapsed)));
+            len += glyph::width(&time_left);
+        }
+        // time to first byte box
+        if let (true, Some(d)) = (self.show_ttfb, self.ttfb()) {
+            ttfb = format!(" ttfb {}", fmt_latency(d));
+            len += glyph::width(&ttfb);
+        }
+        // counter box
+        let total = self.total as f64;
+        let skipped = match (self.skipped, units) {
+            (0, _) => String::new(),
+            (n, Units::Default) => {
+                format!(
+                    "({} skipped) ",
+                    group_digits(n, self.separator.unwrap_or(' '))
+                )
+            }
+            (n, Units::Bytes) => format!("({} skipped) ", self.bytes(n)),
+            (n, Units::Auto(_)) => format!("({} skipped) ", scaled(n as f64, total)),
+        };
+        if self.show_counter && self.show_remaining && !self.is_unbounded() {
+            let r = self.total.saturating_sub(self.current);
+            let rf = r as f64;
+            counter = match units {
+                Units::Default => {
+                    format!(
+                        "remaining: {}{} ",
+                        group_digits(r, self.separator.unwrap_or(' ')),
+                        unit
+                    )
+                }
+                Units::Bytes => format!("remaining: {} ", self.bytes(r)),
+                Units::Auto(_) => format!("remaining: {}{} ", scaled(rf, total), unit),
+            } + &skipped;
+            len += glyph::width(&counter);
+        } else if self.show_counter && self.spinner() {
+            counter = format!("{} ", self.amount(self.current)) + &skipped;
+            len += glyph::width(&counter);
+        } else if self.show_counter && self.bar_label != BarLabel::Counter {
+            counter = format!("{} ", self.counter(filled)) + &skipped;
+            len += glyph::width(&counter);
+        }
+        // tick box
+        if self.show_tick {
+            tick = format!("{} ", self.glyphs(&self.tick[self.tick_state]));
+            len += glyph::width(&tick);
+        }
+        // message box
+        if self.show_message && len + 4 < width {
+            message = self.glyphs(&self.message).into_owned();
+            if len + glyph::width(&message) > width {
+                message = format!("{}...", truncate(&message, width - len - 3));
+            }
+            len += glyph::width(&message);
+        }
+        // bar box
+        let caps =
+            glyph::width(&self.glyphs(&self.bar_start)) + glyph::width(&self.glyphs(&self.bar_end));
+        if self.show_bar && self.spinner() && !self.show_tick {
+            bar = self.glyphs(&self.tick[self.tick_state]).into_owned();
+            len += glyph::width(&bar);
+        } else if self.show_bar && len + caps + 1 < width {
+            bar = self.bar_box(width - (len + caps + 1), filled);
+            len += glyph::width(&bar);
+        }
+
+        let message = self.paint(self.message_paint, message);
+        let percent = self.paint(self.percent_paint, percent);
+        let failure = self.paint(Some(error_paint()), failure);
+        let out = verb
+            + &message
+            + &counter
+            + &tick
+            + &bar
+            + &percent
+            + &failure
+            + &speed
+            + &time_left
+            + &ttfb;
+        debug_assert_eq!(len, glyph::width(&out));
+        out
+    }
+
+    // template_line builds the line from the template, the bars share the
+    // width left by the other fields.
+    fn template_line(
+        &self,
+        t: &Template,
+        width: usize,
+        filled: u64,
+        speed_value: f64,
+        elapsed: Duration,
+    ) -> String {
+        let units = self.units();
+        let caps =
+            glyph::width(&self.glyphs(&self.bar_start)) + glyph::width(&self.glyphs(&self.bar_end));
+        let amount = |n: u64| match units {
+            Units::Default => self.count(n),
+            Units::Bytes => self.bytes(n),
+            Units::Auto(_) => scaled(n as f64, self.total as f64),
+        };
+        let mut value = |name: &str| match name {
+            "verb" if !self.verb.is_empty() => Some(self.verb_box()),
+            "msg" | "prefix" if !self.message.is_empty() => {
+                Some(self.paint(self.message_paint, self.glyphs(&self.message).into_owned()))
+            }
+            "pos" => Some(amount(self.current)),
+            "total" if !self.is_unbounded() => Some(amount(self.total)),
+            "bytes" => Some(self.bytes(self.current)),
+            "total_bytes" if !self.is_unbounded() => Some(self.bytes(self.total)),
+            "skipped" if self.skipped > 0 => Some(amount(self.skipped)),
+            "inflight" if self.inflight > 0 => Some(self.count(self.inflight)),
+            "percent" if !self.spinner() => {
+                Some(self.paint(self.percent_paint, format!("{}%", self.percent())))
+            }
+            "eta" => self
+                .remaining()
+                .and_then(|n| eta(n, speed_value))
+                .map(|left| fmt_secs(fract_dur(left))),
+            "eta_avg" => {
+                let avg = self.current as f64 / fract_dur(elapsed);
+                self.remaining()
+                    .and_then(|n| eta(n, avg))
+                    .map(|left| fmt_secs(fract_dur(left)))
+            }
+            "speed" => Some(match units {
+                Units::Default => format!("{:.*}{}/s", 2, speed_value, self.unit_suffix()),
+                Units::Bytes => format!("{}/s", self.size(speed_value)),
+                Units::Auto(_) => format!(
+                    "{}{}/s",
+                    scaled(speed_value, speed_value),
+                    self.unit_suffix()
+                ),
+            }),
+            "elapsed" => Some(fmt_secs(fract_dur(elapsed + self.chained))),
+            "ttfb" => self.ttfb().map(fmt_latency),
+            "trend" => self.trend_arrow().map(Cow::into_owned),
+            "tick" => self
+                .tick
+                .get(self.tick_state)
+                .map(|t| self.glyphs(t).into_owned()),
+            "finished" if self.is_done() => Some(String::new()),
+            _ => None,
+        };
+        let chunks = t.expand(&mut value);
+
+        let text_width: usize = chunks
+            .iter()
+            .map(|c| match *c {
+                Chunk::Text(ref s) => glyph::width(s),
+                Chunk::Bar(Some(size)) => size + caps,
+                Chunk::Bar(None) => 0,
+            })
+            .sum();
+        let nbars = chunks.iter().filter(|c| **c == Chunk::Bar(None)).count();
+        let size = match width.checked_sub(text_width + 1) {
+            Some(room) if nbars > 0 => (room / nbars).saturating_sub(caps),
+            _ => 0,
+        };
+        let mut out = String::new();
+        for c in chunks {
+            match c {
+                Chunk::Text(s) => out += &s,
+                Chunk::Bar(Some(size)) => out += &self.bar_box(size, filled),
+                Chunk::Bar(None) if size > 0 => out += &self.bar_box(size, filled),
+                Chunk::Bar(None) => {}
+            }
+        }
+        truncate(&out, width).to_owned()
+    }
+
+    // job_line builds a cargo-like line: spinner, bold verb, message and a
+    // compact bar with the counter, aligned to the right.
+    fn job_line(&self, verb: &str, width: usize, filled: u64) -> String {
+        let tick = self.glyphs(self.tick.get(self.tick_state).map_or("", |t| t.as_str()));
+        let paint = self.verb_paint.unwrap_or_else(|| Paint::new().bold());
+        let verb = self.paint(Some(paint), format!("{:<12}", verb));
+        let left = format!(" {} {}", tick, verb);
+        let left_len = glyph::width(&left);
+        let right = if self.is_unbounded() {
+            format!(" {}", self.amount(self.current))
+        } else {
+            format!(
+                " {} {}",
+                self.bar_box(JOB_BAR_SIZE, filled),
+                self.counter(filled)
+            )
+        };
+        let right_len = glyph::width(&right);
+        if left_len + right_len > width {
+            return left;
+        }
+        let room = width - (left_len + right_len);
+        let message = self.glyphs(&self.message);
+        let message = truncate(&message, room);
+        let gap = room - glyph::width(message);
+        left + message + repeat!(" ", gap) + &right
+    }
+
+    // bar_box builds a bar of `size` glyphs between the start and end glyphs,
+    // filled up to `filled`. Skipped items are dimmed, at the start of a
+    // filling bar.
+    fn bar_box(&self, size: usize, filled: u64) -> String {
+        let ratio = match self.direction {
+            _ if self.total > 0 => filled as f64 / self.total as f64,
+            Direction::Fill => self.fraction(),
+            Direction::Drain => 1. - self.fraction(),
+        };
+        let curr_count = (ratio * size as f64).ceil() as usize;
+        if size < curr_count {
+            return String::new();
+        }
+        let rema_count = size - curr_count;
+        let colors = self.colors();
+        let block = self.block.filter(|_| colors);
+        let (current, tip, remain) = match block {
+            Some(_) => (Cow::Borrowed(" "), Cow::Borrowed(" "), Cow::Borrowed(" ")),
+            // the tip is the part of the last filled cell
+            None if self.smooth && !self.ascii => {
+                let part = ratio * size as f64 - curr_count.saturating_sub(1) as f64;
+                let eighths = ((part * 8.).ceil() as usize).clamp(1, 8);
+                (
+                    Cow::Borrowed("█"),
+                    Cow::Borrowed(SMOOTH_BLOCKS[eighths - 1]),
+                    Cow::Borrowed(" "),
+                )
+            }
+            None => (
+                self.glyphs(&self.bar_current),
+                self.glyphs(&self.bar_current_n),
+                self.glyphs(&self.bar_remain),
+            ),
+        };
+        let tip_width = glyph::width(&tip);
+        let mut fill_style = block.map_or(String::new(), |c| format!("\x1B[48;5;{}m", c));
+        let paint = match self.failure {
+            Some(_) => Some(error_paint()),
+            None => self.bar_paint,
+        };
+        if let Some(p) = paint.filter(|_| colors) {
+            fill_style += &p.codes();
         }
+        let mut done = curr_count;
+        if rema_count > 0 && curr_count >= tip_width.max(1) {
+            done -= tip_width;
+        }
+        let skip_count = match self.direction {
+            Direction::Fill if self.total > 0 => {
+                let ratio = self.skipped.min(self.total) as f64 / self.total as f64;
+                ((ratio * size as f64) as usize).min(done)
+            }
+            _ => 0,
+        };
+        let marker = self.schedule_marker(size);
+        let label: Vec<char> = match self.label(filled) {
+            Some(l) if l.chars().count() + 2 <= size => l.chars().collect(),
+            _ => Vec::new(),
+        };
+        let label_start = (size - label.len()) / 2;
+        let label_end = label_start + label.len();
 
-        if !self.is_visible {
-            printfl!(self.handle, "");
+        let mut bar = self.glyphs(&self.bar_start).into_owned();
+        // the escape sequences in effect
+        let mut style = String::new();
+        let mut i = 0;
+        while i < size {
+            let (cell, w) = if i < done {
+                (&*current, 1)
+            } else if i == done && done < curr_count {
+                (&*tip, tip_width)
+            } else {
+                (&*remain, 1)
+            };
+            let in_label = label_start <= i && i < label_end;
+            // skipped items are dimmed, and the label over the filled cells
+            // is in inverse video
+            let mut want = String::new();
+            if i < skip_count && colors {
+                want += "\x1B[2m";
+            }
+            if i < curr_count {
+                want += &fill_style;
+                if in_label && colors {
+                    want += "\x1B[7m";
+                }
+            }
+            if want != style {
+                if !style.is_empty() {
+                    bar += "\x1B[0m";
+                }
+                bar += &want;
+                style = want;
+            }
+            if in_label {
+                bar.push(label[i - label_start]);
+                i += 1;
+            } else if i < label_start && label_start < i + w {
+                // a wide tip would overlap the label
+                bar.push(' ');
+                i += 1;
+            } else {
+                match marker {
+                    Some(m) if i <= m && m < i + w => {
+                        bar = bar + SCHEDULE_MARKER + repeat!(" ", w - 1);
+                    }
+                    _ => bar += cell,
+                }
+                i += w;
+            }
+        }
+        if !style.is_empty() {
+            bar += "\x1B[0m";
+        }
+        bar + &self.glyphs(&self.bar_end)
+    }
+
+    // update_trend compares the rate with the one at the start of the trend
+    // window, once the window is over, see `show_trend`.
+    fn update_trend(&mut self, now: SteadyTime, rate: f64) {
+        if !self.show_trend || !rate.is_finite() {
             return;
         }
+        match self.trend_base {
+            Some((at, _)) if now - at < time::Duration::seconds(TREND_WINDOW_SECS) => {}
+            Some((_, base)) => {
+                self.trend = Some(if rate > base * (1. + TREND_THRESHOLD) {
+                    Trend::Greater
+                } else if rate < base * (1. - TREND_THRESHOLD) {
+                    Trend::Less
+                } else {
+                    Trend::Equal
+                });
+                self.trend_base = Some((now, rate));
+            }
+            None => self.trend_base = Some((now, rate)),
+        }
+    }
 
-        let time_elapsed = time_to_std(now - self.start_time);
-        let speed_value = self.current as f64 / fract_dur(time_elapsed);
-        let width = self.width();
+    // trend_arrow returns the arrow of the trend, once known.
+    fn trend_arrow(&self) -> Option<Cow<'static, str>> {
+        let arrow = match self.trend? {
+            Trend::Greater => "↑",
+            Trend::Less => "↓",
+            Trend::Equal => "→",
+        };
+        Some(self.glyphs(arrow))
+    }
 
-        let mut len = 0;
-        let mut percent = String::new();
-        let mut speed = String::new();
-        let mut time_left = String::new();
-        let mut message = String::new();
-        let mut counter = String::new();
-        let mut tick = String::new();
-        let mut bar = String::new();
+    // colors returns whether colors are drawn.
+    fn colors(&self) -> bool {
+        self.colors && !self.milestone_mode && colors_enabled()
+    }
 
-        // percent
-        if self.show_percent {
-            let value = self.current as f64 / (self.total as f64 / 100f64);
-            percent = format!(" {:.*} % ", 2, if value.is_nan() { 0.0 } else { value });
-            len += percent.len();
+    // paint returns `s` in `paint`, if colors are drawn.
+    fn paint(&self, paint: Option<Paint>, s: String) -> String {
+        match paint {
+            Some(p) if self.colors() => p.paint(&s),
+            _ => s,
         }
-        // speed box
-        if self.show_speed {
-            speed = match self.units {
-                Units::Default => format!("{:.*}/s ", 2, speed_value),
-                Units::Bytes => format!("{}/s ", kb_fmt!(speed_value)),
-            };
-            if len + speed.len() > width {
-                speed = String::new();
+    }
+
+    // verb_box returns the verb right-aligned and painted, with a space
+    // after it, or "" without a verb.
+    fn verb_box(&self) -> String {
+        if self.verb.is_empty() {
+            return String::new();
+        }
+        let verb = self.glyphs(&self.verb);
+        let width = match self.verb_width {
+            Some(ref w) => w.load(Ordering::Relaxed),
+            None => 0,
+        };
+        let pad = width.saturating_sub(glyph::width(&verb));
+        let paint = self
+            .verb_paint
+            .unwrap_or_else(|| Paint::new().fg(Color::Green).bold());
+        format!(
+            "{}{} ",
+            repeat!(" ", pad),
+            self.paint(Some(paint), verb.into_owned())
+        )
+    }
+
+    // label returns the text drawn inside the bar, if any.
+    fn label(&self, filled: u64) -> Option<String> {
+        match self.bar_label {
+            BarLabel::Off => None,
+            BarLabel::Percent => Some(format!("{}%", self.percent())),
+            BarLabel::Counter => {
+                let (c, t) = (filled as f64, self.total as f64);
+                Some(match self.units() {
+                    Units::Default => format!("{}/{}", self.count(filled), self.count(self.total)),
+                    Units::Bytes => format!("{}/{}", self.bytes(filled), self.bytes(self.total)),
+                    Units::Auto(_) => format!("{}/{}", scaled(c, t), scaled(t, t)),
+                })
             }
-            len += speed.len();
         }
-        // time left box
-        if self.show_time_left && self.current > 0 && self.total > self.current {
-            let left = 1. / speed_value * (self.total - self.current) as f64;
-            time_left = if left < 60. {
-                format!("{:.0}s", left)
-            } else {
-                format!("{:.0}m", left / 60.)
-            };
-            len += time_left.len();
+    }
+
+    // schedule_marker returns the cell of a bar of `size` cells where the
+    // progress should be, according to the expected duration.
+    fn schedule_marker(&self, size: usize) -> Option<usize> {
+        let expected = self.expected_duration?;
+        let start = self.start_time?;
+        if size == 0 || self.current >= self.total || expected == Duration::new(0, 0) {
+            return None;
         }
-        // counter box
-        if self.show_counter {
-            let (c, t) = (self.current as f64, self.total as f64);
-            counter = match self.units {
-                Units::Default => format!("{} / {} ", c, t),
-                Units::Bytes => format!("{} / {} ", kb_fmt!(c), kb_fmt!(t)),
-            };
-            len += counter.len();
+        let elapsed = fract_dur(time_to_std(self.clock() - start));
+        let cell = (elapsed / fract_dur(expected) * size as f64) as usize;
+        Some(cell.min(size - 1))
+    }
+
+    // glyphs returns `s` with ASCII fallbacks, if enabled.
+    fn glyphs<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        if self.ascii {
+            to_ascii(s)
+        } else {
+            Cow::Borrowed(s)
         }
-        // tick box
-        if self.show_tick {
-            tick = format!("{} ", self.tick[self.tick_state]);
-            len += tick.len();
+    }
+
+    // print_frame writes a full-width frame over the current line, or on the
+    // pinned row.
+    fn print_frame(&mut self, out: &str) {
+        match self.row() {
+            Some(row) => self.print(format_args!("{}", draw_at_row(row, out))),
+            None => self.print(format_args!("\r{}", out)),
         }
-        // message box
-        if self.show_message && len + 4 < width {
-            // TODO: better support unicode messages
-            message = self.message.clone();
-            if len + message.len() > width {
-                message = format!("{}...", &message[0..(width - len - 3)]);
-            }
-            len += message.len();
+    }
+
+    // row resolves the pinned row, if any, to a row counted from the top.
+    fn row(&self) -> Option<usize> {
+        if self.multibar.is_some() {
+            return None;
         }
-        // bar box
-        if self.show_bar && len + 3 < width {
-            let size = width - (len + 3);
-            let curr_count =
-                ((self.current as f64 / self.total as f64) * size as f64).ceil() as usize;
-            if size >= curr_count {
-                let rema_count = size - curr_count;
-                bar = self.bar_start.clone();
-                if rema_count > 0 && curr_count > 0 {
-                    bar = bar
-                        + repeat!(self.bar_current.to_string(), curr_count - 1)
-                        + &self.bar_current_n;
-                } else {
-                    bar = bar + repeat!(self.bar_current.to_string(), curr_count);
-                }
-                bar = bar + repeat!(self.bar_remain.to_string(), rema_count) + &self.bar_end;
+        match self.pinned_row? {
+            Row::Top(n) => Some(n as usize),
+            Row::Bottom(n) => {
+                terminal_size().map(|(_, Height(h))| h.saturating_sub(n + 1) as usize)
             }
-            len += bar.len();
         }
+    }
 
-        let mut out = message + &counter + &tick + &bar + &percent + &speed + &time_left;
-        assert_eq!(len, out.len());
-
-        // pad
-        if out.len() < width {
-            let gap = width - out.len();
-            out = out + repeat!(" ", gap);
+    // print writes to the handle, if any.
+    fn print(&mut self, args: fmt::Arguments) {
+        if let Some(ref mut handle) = self.handle {
+            printfl!(handle, "{}", args);
         }
-        // print
-        printfl!(self.handle, "\r{}", out);
+    }
+
+    // visible_state buckets everything that may change between two draws:
+    // the percent step, the speed (~25% buckets), the time left as displayed
+    // and the tick.
+    fn visible_state(&self, step: f64, speed: f64) -> (u64, i64, u64, usize) {
+        let percent = (self.percent_value() / step) as u64;
+        let rate = if !self.show_speed {
+            0
+        } else if speed > 0. {
+            (speed.log10() * 10.).floor() as i64
+        } else {
+            i64::MIN
+        };
+        let left = if let (true, Some(n)) = (self.show_time_left, self.remaining()) {
+            let left = eta(n, speed).map_or(0., fract_dur);
+            if left < 60. {
+                left.round() as u64
+            } else {
+                (left / 60.).round() as u64 * 60
+            }
+        } else {
+            0
+        };
+        let tick = if self.show_tick { self.tick_state } else { 0 };
+        (percent, rate, left, tick)
+    }
 
-        self.last_refresh_time = SteadyTime::now();
+    // milestone returns the heartbeat step the progress is in.
+    fn milestone(&self) -> u64 {
+        let percent = self.fraction() * 100.;
+        match self.heartbeat {
+            Heartbeat::Percent(step) => (percent / step) as u64,
+            Heartbeat::Adaptive => {
+                ADAPTIVE_HEARTBEAT.iter().filter(|&&p| p <= percent).count() as u64
+            }
+        }
     }
 
     // finish_draw ensure that the progress bar is reached to its end, and do the
     // last drawing if needed.
     fn finish_draw(&mut self) {
+        self.collect();
         let mut redraw = false;
 
-        if let Some(mrr) = self.max_refresh_rate {
-            if SteadyTime::now() - self.last_refresh_time < mrr {
+        if let (Some(mrr), Some(last)) = (self.max_refresh_rate, self.last_refresh_time) {
+            if SteadyTime::now() - last < mrr {
                 self.max_refresh_rate = None;
                 redraw = true;
             }
         }
 
-        if self.current < self.total {
+        if self.current < self.total && !self.is_unbounded() {
             self.current = self.total;
+            if let Some((ref shared, _)) = self.view {
+                shared.current.store(self.total, Ordering::Relaxed);
+            }
+            redraw = true;
+        }
+
+        // a delayed bar that was never drawn, is drawn now only if the
+        // operation outlasted the delay.
+        if self.show_after.is_some() && !self.is_drawn {
             redraw = true;
         }
 
         if redraw {
             self.draw();
         }
+        if !self.is_finish {
+            if let Some(ref link) = self.multibar {
+                link.finish();
+            }
+            self.notify(Milestone::Finished);
+        }
         self.is_finish = true;
     }
 
+    /// Start the next phase of a job on the same line, instead of finishing
+    /// the bar and creating another one: the counters restart from 0 with
+    /// the new `total` and `message`, the speed and the ETA are the ones of
+    /// the new phase, but `{elapsed}` goes on from the first phase.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.message("download ");
+    /// pb.set_template(Some("{msg}{bar} {pos}/{total} {elapsed}")).unwrap();
+    /// for _ in 0..100 {
+    ///     pb.inc();
+    /// }
+    /// pb.chain(20, "unpack ");
+    /// for _ in 0..20 {
+    ///     pb.inc();
+    /// }
+    /// pb.finish();
+    /// ```
+    pub fn chain<S: Into<Cow<'static, str>>>(&mut self, total: u64, message: S) {
+        self.collect();
+        let now = self.clock();
+        if let Some(start) = self.start_time {
+            self.chained += time_to_std(now - start);
+            self.start_time = Some(now);
+        }
+        self.current = 0;
+        self.total = total;
+        self.skipped = 0;
+        if let Some((ref shared, ref mut seen_total)) = self.view {
+            shared.current.store(0, Ordering::Relaxed);
+            shared.total.store(total, Ordering::Relaxed);
+            *seen_total = total;
+        }
+        if let Some(ref mut j) = self.journal {
+            j.record("total", total);
+            j.record("set", 0);
+        }
+        self.ttfb = None;
+        self.warmup_end = None;
+        self.rate_origin = None;
+        self.clear_rate();
+        self.notified = 0;
+        self.message(message);
+        // the new phase is drawn whatever the refresh rate
+        self.last_refresh_time = None;
+        self.draw();
+    }
+
+    /// Start another pass over the same total, e.g. to verify a download:
+    /// the position, the elapsed time and the rate restart from 0, the
+    /// total, the message and the configuration are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.message("download ");
+    /// for _ in 0..100 {
+    ///     pb.inc();
+    /// }
+    /// pb.message("verify ");
+    /// pb.reset();
+    /// for _ in 0..100 {
+    ///     pb.inc();
+    /// }
+    /// pb.finish();
+    /// ```
+    pub fn reset(&mut self) {
+        self.collect();
+        if self.start_time.is_some() {
+            self.start_time = Some(SteadyTime::now());
+        }
+        self.marked_start = None;
+        self.chained = Duration::new(0, 0);
+        self.paused_at = None;
+        self.current = 0;
+        self.skipped = 0;
+        if let Some((ref shared, _)) = self.view {
+            shared.current.store(0, Ordering::Relaxed);
+        }
+        if let Some(ref mut j) = self.journal {
+            j.record("set", 0);
+        }
+        self.ttfb = None;
+        self.warmup_end = None;
+        self.rate_origin = None;
+        self.clear_rate();
+        self.notified = 0;
+        self.last_milestone = None;
+        self.last_refresh_time = None;
+        self.draw();
+    }
+
+    /// Forget the rate measured so far, from which the speed and the ETA
+    /// are computed, e.g. once a job changed pace for good. The position
+    /// and the elapsed time are kept.
+    pub fn reset_eta(&mut self) {
+        self.collect();
+        self.rate_origin = self
+            .start_time
+            .map(|t| (time_to_std(self.clock() - t), self.current));
+        self.clear_rate();
+    }
+
+    // clear_rate drops the history of the rate, see `reset_eta`.
+    fn clear_rate(&mut self) {
+        if let Some(ref mut e) = self.estimator {
+            e.reset();
+        }
+        self.trend_base = None;
+        self.trend = None;
+        self.last_visible = None;
+    }
+
     /// Calling finish manually will set current to total and draw
-    /// the last time
+    /// the last time, see `set_finish_behavior`.
     pub fn finish(mut self) {
-        self.finish_draw();
-        printfl!(self.handle, "");
+        let behavior = self.finish_behavior.take();
+        self.finish_as(behavior.unwrap_or(FinishBehavior::Keep));
+    }
+
+    /// Call finish with `behavior`, instead of the one set with
+    /// `set_finish_behavior`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{FinishBehavior, ProgressBar};
+    ///
+    /// let pb = ProgressBar::new(100);
+    /// // ...
+    /// pb.finish_with(FinishBehavior::Clear);
+    /// ```
+    pub fn finish_with(mut self, behavior: FinishBehavior) {
+        self.finish_as(behavior);
+    }
+
+    /// Call finish and erase the bar, the cursor is left at the start of its
+    /// line so that the next output takes its place, as if the bar was never
+    /// drawn. A bar of a `MultiBar` is removed from its lines.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// // ...
+    /// pb.finish_and_clear();
+    /// println!("step 1 done");
+    /// ```
+    pub fn finish_and_clear(self) {
+        self.finish_with(FinishBehavior::Clear);
+    }
+
+    /// Call finish_with_error when the job failed: the bar stays where it
+    /// was, instead of being completed, with a `✗` and `error` in place of
+    /// the speed and time left. The bar and the error are drawn in red.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(1000);
+    /// pb.message("upload ");
+    /// // ...
+    /// pb.add(420);
+    /// pb.finish_with_error("connection reset");
+    /// // upload 420 / 1000 [=====>-------] 42.00 % ✗ connection reset
+    /// ```
+    pub fn finish_with_error(mut self, error: &str) {
+        self.fail(error.to_owned());
+    }
+
+    /// Call abandon to stop the bar where it was, e.g. on a cancelled job,
+    /// marked with a `✗`, see `finish_with_error`.
+    pub fn abandon(mut self) {
+        self.fail(String::new());
+    }
+
+    // fail draws the bar a last time with the failure, once.
+    fn fail(&mut self, error: String) {
+        if self.is_finish {
+            return;
+        }
+        self.collect();
+        self.failure = Some(error);
+        // the failure is drawn whatever the refresh rate
+        self.last_refresh_time = None;
+        self.draw();
+        self.is_finish = true;
+        self.notify(Milestone::Abandoned);
+        match self.multibar {
+            Some(ref link) => link.fail(),
+            None => self.print(format_args!("")),
+        }
     }
 
     /// Call finish and write string `s` that will replace the progress bar.
     pub fn finish_print(mut self, s: &str) {
+        self.finish_as(FinishBehavior::ReplaceWith(s.to_owned()));
+    }
+
+    // finish_in_place finishes the bar without consuming it, once, see
+    // `ProgressSink`.
+    pub(crate) fn finish_in_place(&mut self) {
+        if !self.is_finish {
+            let behavior = self.finish_behavior.take();
+            self.finish_as(behavior.unwrap_or(FinishBehavior::Keep));
+        }
+    }
+
+    fn finish_as(&mut self, behavior: FinishBehavior) {
         self.finish_draw();
+        match behavior {
+            FinishBehavior::Keep => {}
+            // a line of a MultiBar is cleared by the empty write below
+            FinishBehavior::Clear if self.multibar.is_some() || self.milestone_mode => {}
+            FinishBehavior::Clear => {
+                let width = self.width();
+                self.print_frame(repeat!(" ", width));
+                if self.row().is_none() {
+                    self.print(format_args!("\r"));
+                }
+            }
+            FinishBehavior::ReplaceWith(s) => self.replace_with(&s),
+            FinishBehavior::Summary => {
+                let s = self.summary();
+                self.replace_with(&s);
+            }
+        }
+        self.print(format_args!(""));
+    }
+
+    // replace_with draws `s` over the bar.
+    fn replace_with(&mut self, s: &str) {
+        if self.milestone_mode {
+            return self.print(format_args!("{}\n", s));
+        }
         let width = self.width();
-        let mut out = format!("{}", s);
-        if s.len() < width {
-            out += repeat!(" ", width - s.len());
+        let mut out = s.to_owned();
+        let s_width = glyph::width(s);
+        if s_width < width {
+            out += repeat!(" ", width - s_width);
+        };
+        self.print_frame(&out);
+    }
+
+    // summary returns the line left by `FinishBehavior::Summary`.
+    fn summary(&self) -> String {
+        let elapsed = self
+            .start_time
+            .map_or(Duration::new(0, 0), |t| time_to_std(self.clock() - t));
+        let secs = fract_dur(elapsed);
+        let speed = if secs > 0. {
+            self.current as f64 / secs
+        } else {
+            0.
+        };
+        let unit = self.unit_suffix();
+        let (amount, speed) = match self.units() {
+            Units::Default => (
+                self.count(self.current) + &unit,
+                format!("{:.*}{}/s", 2, speed, unit),
+            ),
+            Units::Bytes => (self.bytes(self.current), format!("{}/s", self.size(speed))),
+            Units::Auto(_) => {
+                let t = self.total as f64;
+                (
+                    scaled(self.current as f64, t) + &unit,
+                    format!("{}{}/s", scaled(speed, speed), unit),
+                )
+            }
         };
-        printfl!(self.handle, "\r{}", out);
-        self.finish();
+        format!(
+            "{}{} in {} ({})",
+            self.glyphs(&self.message),
+            amount,
+            fmt_secs(secs),
+            speed
+        )
+    }
+
+    /// Print a line of text above the bar, e.g. a log line: the bar is
+    /// replaced with the text, and drawn again on the next line.
+    ///
+    /// A bar of a `MultiBar` prints above all the lines of the `MultiBar`,
+    /// see `MultiBarHandle::println_above`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(3);
+    /// for f in &["a.txt", "b.txt", "c.txt"] {
+    ///     pb.println(&format!("warning: {} is empty", f));
+    ///     pb.inc();
+    /// }
+    /// pb.finish();
+    /// ```
+    pub fn println(&mut self, s: &str) {
+        if let Some(ref link) = self.multibar {
+            return link.println_above(s.to_owned());
+        }
+        if self.milestone_mode || !self.is_drawn || self.last_frame.is_empty() {
+            self.print(format_args!("{}\n", s));
+            return;
+        }
+        if self.row().is_some() {
+            // the pinned bar is drawn elsewhere, the text goes at the cursor
+            self.print(format_args!("{}\n", s));
+            return;
+        }
+        // cover the bar, then draw it again below
+        let pad = glyph::width(&self.last_frame).saturating_sub(glyph::width(s));
+        self.print(format_args!("\r{}{}\n", s, repeat!(" ", pad)));
+        let frame = mem::take(&mut self.last_frame);
+        self.print_frame(&frame);
+        self.last_frame = frame;
     }
 
     /// Call finish and write string `s` below the progress bar.
@@ -471,31 +3032,133 @@ impl<T: Write> ProgressBar<T> {
     pub fn finish_println(mut self, s: &str) {
         // `finish_println` does not allow in MultiBar mode, because printing
         // new line will break the multiBar output.
-        if self.is_multibar {
+        if self.multibar.is_some() {
             return self.finish_print(s);
         }
         self.finish_draw();
-        printfl!(self.handle, "\n{}", s);
+        if self.milestone_mode {
+            self.print(format_args!("{}\n", s));
+            return;
+        }
+        if !self.is_drawn || self.row().is_some() {
+            self.print(format_args!("{}", s));
+            return;
+        }
+        self.print(format_args!("\n{}", s));
     }
 
-    /// Get terminal width, from configuration, terminal size, or default(80)
+    /// Get terminal width, see `width_source`.
     fn width(&mut self) -> usize {
+        self.probe_width().0
+    }
+
+    // probe_width resolves the width and its source. A width that did not
+    // come from the terminal is cached for a while; the terminal size is
+    // checked on every call to follow resizes.
+    fn probe_width(&mut self) -> (usize, WidthSource) {
         if let Some(w) = self.width {
-            w
-        } else if let Some((Width(w), _)) = terminal_size() {
-            w as usize
+            return (w, WidthSource::Configured);
+        }
+        if let Some((w, source, at)) = self.width_probe {
+            if SteadyTime::now() - at < time::Duration::seconds(WIDTH_PROBE_SECS) {
+                return (w, source);
+            }
+        }
+        let (w, source) = if let Some((Width(w), _)) = terminal_size() {
+            (w as usize, WidthSource::Terminal)
+        } else if let Some(w) = columns() {
+            (w, WidthSource::Columns)
+        } else if let Some(w) = self.default_width {
+            (w, WidthSource::Default)
         } else {
-            80
+            (FALLBACK_WIDTH, WidthSource::Fallback)
+        };
+        if source != WidthSource::Terminal {
+            self.width_probe = Some((w, source, SteadyTime::now()));
         }
+        (w, source)
     }
 }
 impl<T: Write> Drop for ProgressBar<T> {
     fn drop(&mut self) {
         if !self.is_finish {
-            printfl!(self.handle, "");
+            if let Some(behavior) = self.finish_behavior.take() {
+                return self.finish_as(behavior);
+            }
+            self.notify(Milestone::Abandoned);
+            // a bar of a MultiBar that is dropped without `finish()`,
+            // is marked as aborted.
+            if let Some(ref link) = self.multibar {
+                return link.abandon();
+            }
+            self.print(format_args!(""));
+        }
+    }
+}
+impl<T: Write> fmt::Debug for ProgressBar<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProgressBar")
+            .field("current", &self.current)
+            .field("total", &self.total)
+            .field("finished", &self.is_finish)
+            .field(
+                "format",
+                &format!(
+                    "{}{}{}{}{}",
+                    self.bar_start,
+                    self.bar_current,
+                    self.bar_current_n,
+                    self.bar_remain,
+                    self.bar_end
+                ),
+            )
+            .field("units", &self.units)
+            .field("target", &type_name::<T>())
+            .finish()
+    }
+}
+
+/// FormatError is returned by `ProgressBar::try_format` for an invalid format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    /// The format has this number of segments instead of 5.
+    Segments(usize),
+    /// The named segment is not a single glyph.
+    Glyph(&'static str),
+    /// The named segment has a control character, or an escape sequence
+    /// other than colors, which would break the line.
+    Control(&'static str),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FormatError::Segments(n) => write!(f, "bar format has {} segments, expected 5", n),
+            FormatError::Glyph(name) => write!(f, "bar format {} must be a single glyph", name),
+            FormatError::Control(name) => {
+                write!(f, "bar format {} has a control character", name)
+            }
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+// Display a short summary, e.g. `42 / 100 (42%)`.
+impl<T: Write> fmt::Display for ProgressBar<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_unbounded() {
+            write!(f, "{}", self.current)?;
+        } else {
+            write!(f, "{} / {} ({}%)", self.current, self.total, self.percent())?;
+        }
+        if self.is_finish {
+            write!(f, " finished")?;
         }
+        Ok(())
     }
 }
+
 // Implement io::Writer
 impl<T: Write> Write for ProgressBar<T> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
@@ -508,6 +3171,91 @@ impl<T: Write> Write for ProgressBar<T> {
     }
 }
 
+// fmt_secs formats a duration in seconds, or minutes above a minute.
+fn fmt_secs(secs: f64) -> String {
+    if secs < 60. {
+        format!("{:.0}s", secs)
+    } else {
+        format!("{:.0}m", secs / 60.)
+    }
+}
+
+// error_paint is the paint of a failed bar, see
+// `ProgressBar::finish_with_error`.
+fn error_paint() -> Paint {
+    Paint::new().fg(Color::Red)
+}
+
+// fmt_latency formats a short duration in milliseconds, or seconds above a
+// second.
+fn fmt_latency(d: Duration) -> String {
+    if d < Duration::from_secs(1) {
+        format!("{}ms", d.as_millis())
+    } else {
+        format!("{:.2}s", fract_dur(d))
+    }
+}
+
+// parse_format splits a bar format in its 5 segments, see
+// `ProgressBar::format`. Segments may be colored, but other escape sequences
+// and control characters (e.g. a line break) are rejected.
+pub(crate) fn parse_format(fmt: &str) -> Result<Vec<String>, FormatError> {
+    let v: Vec<String> = if fmt.contains(',') {
+        fmt.split(',').map(str::to_owned).collect()
+    } else {
+        fmt.chars().map(String::from).collect()
+    };
+    if v.len() != 5 {
+        return Err(FormatError::Segments(v.len()));
+    }
+    if glyph::strip_colors(&v[1]).chars().count() != 1 {
+        return Err(FormatError::Glyph("fill"));
+    }
+    if glyph::strip_colors(&v[3]).chars().count() != 1 {
+        return Err(FormatError::Glyph("remain"));
+    }
+    let names = ["start", "fill", "tip", "remain", "end"];
+    for (s, name) in v.iter().zip(names.iter()) {
+        if glyph::strip_colors(s).chars().any(char::is_control) {
+            return Err(FormatError::Control(name));
+        }
+    }
+    Ok(v)
+}
+
+// columns reads the width from the `COLUMNS` environment variable.
+fn columns() -> Option<usize> {
+    ::std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.trim().parse().ok())
+        .filter(|&c| c > 0)
+}
+
+// scaled formats `n` in thousands or millions, picked from `magnitude`.
+fn scaled(n: f64, magnitude: f64) -> String {
+    if magnitude >= 1e7 {
+        format!("{:.1}M", n / 1e6)
+    } else if magnitude >= AUTO_UNITS_MIN as f64 {
+        format!("{:.1}k", n / 1e3)
+    } else {
+        format!("{:.*}", 2, n)
+    }
+}
+
+// group_digits formats n with `sep` between groups of three digits, e.g.
+// `1 234 567`.
+fn group_digits(n: u64, sep: char) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(sep);
+        }
+        out.push(c);
+    }
+    out
+}
+
 fn time_to_std(d: time::Duration) -> Duration {
     if d > time::Duration::zero() {
         let secs = d.num_seconds();
@@ -526,7 +3274,16 @@ fn fract_dur(d: Duration) -> f64 {
 
 #[cfg(test)]
 mod test {
-    use pb::ProgressBar;
+    use pb::{
+        BarLabel, BytePrefix, Direction, ExactBytes, FinishBehavior, FormatError, Heartbeat,
+        Layout, ProgressBar, Row, Units, UnitsHint, Warmup, WidthSource, ZeroTotalPolicy,
+    };
+    use std::io::Write;
+    use testing::{Env, Terminal};
+
+    fn output(pb: &ProgressBar<Vec<u8>>) -> String {
+        String::from_utf8(pb.handle.clone().unwrap()).unwrap()
+    }
 
     #[test]
     fn add() {
@@ -561,6 +3318,1040 @@ mod test {
         );
     }
 
+    #[test]
+    fn format_extended() {
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_width(Some(40));
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.show_percent = false;
+        pb.format("⟦ ,=,=>>, , ⟧");
+        assert_eq!(pb.bar_current_n, "=>>");
+        pb.set(5);
+        let out = output(&pb);
+        let last = out.rsplit('\r').next().unwrap();
+        assert_eq!(last, "5 / 10 ⟦ ============>>               ⟧ ");
+
+        assert_eq!(pb.try_format("[=>-"), Err(FormatError::Segments(4)));
+        assert_eq!(pb.try_format("[,==,>,-,]"), Err(FormatError::Glyph("fill")));
+        assert_eq!(pb.try_format("[,=,>,,]"), Err(FormatError::Glyph("remain")));
+        assert_eq!(pb.try_format("[=>-\r"), Err(FormatError::Control("end")));
+        assert_eq!(
+            pb.try_format("[,=,\x1B[2K>,-,]"),
+            Err(FormatError::Control("tip"))
+        );
+        pb.format("[=>-");
+        assert_eq!(pb.bar_current_n, "=>>", "should keep the previous format");
+
+        // colors are allowed
+        assert_eq!(pb.try_format("[,\x1B[32m=\x1B[0m,\x1B[1m>,-,]"), Ok(()));
+        assert_eq!(pb.bar_current_n, "\x1B[1m>");
+    }
+
+    #[test]
+    fn detect_pager() {
+        let path = ::std::env::temp_dir().join(format!("pbr-pager-{}", ::std::process::id()));
+        let file = ::std::fs::File::create(&path).unwrap();
+        let mut pb = ProgressBar::on_terminal(file, 100);
+        assert!(pb.milestone_mode, "a file isn't a terminal");
+        assert!(!pb.colors);
+        assert!(pb.detect_pager());
+        drop(pb);
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn milestones() {
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.assume_pager();
+        pb.show_speed = false;
+        for _ in 0..25 {
+            pb.inc();
+        }
+        let out = output(&pb);
+        assert_eq!(out.lines().count(), 3, "should print one line per 10%");
+        assert!(!out.contains('\r'), "should not redraw in place");
+    }
+
+    #[test]
+    fn heartbeat() {
+        let lines = |heartbeat| {
+            let mut pb = ProgressBar::on(Vec::new(), 100);
+            pb.assume_pager();
+            pb.set_heartbeat(heartbeat);
+            pb.show_speed = false;
+            let mut counts = Vec::new();
+            for _ in 0..4 {
+                for _ in 0..25 {
+                    pb.inc();
+                }
+                counts.push(output(&pb).lines().count());
+            }
+            counts
+        };
+        assert_eq!(lines(Heartbeat::Percent(25.)), [2, 3, 4, 5]);
+        assert_eq!(lines(Heartbeat::Adaptive), [5, 6, 7, 12]);
+    }
+
+    #[test]
+    fn show_after() {
+        use std::time::Duration;
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_show_after(Some(Duration::from_secs(60)));
+        pb.add(5);
+        pb.finish_draw();
+        assert!(output(&pb).is_empty(), "should not draw before the delay");
+
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_show_after(Some(Duration::from_millis(1)));
+        ::std::thread::sleep(Duration::from_millis(5));
+        pb.add(5);
+        assert!(!output(&pb).is_empty(), "should draw after the delay");
+    }
+
+    #[test]
+    fn percent_step() {
+        let mut pb = ProgressBar::on(Vec::new(), 10_000);
+        pb.set_percent_step(Some(10.));
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.add(1);
+        let n = output(&pb).len();
+        for _ in 0..10 {
+            pb.inc();
+        }
+        assert_eq!(output(&pb).len(), n, "should not redraw within a step");
+        pb.add(1000);
+        assert!(output(&pb).len() > n, "should redraw on the next step");
+    }
+
+    #[test]
+    fn message() {
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.message("static");
+        assert!(matches!(pb.message, ::std::borrow::Cow::Borrowed(_)));
+        pb.message(String::from("a\nb"));
+        assert_eq!(pb.message, "a b");
+    }
+
+    #[test]
+    fn const_new() {
+        let mut pb = ProgressBar::const_new();
+        pb.inc();
+        pb.init(Vec::new(), 10);
+        pb.inc();
+        assert_eq!(pb.current, 2);
+        assert!(output(&pb).contains("2 / 10"));
+    }
+
+    #[test]
+    fn fraction() {
+        let mut pb = ProgressBar::on(Vec::new(), 3);
+        assert_eq!(pb.fraction(), 0.);
+        pb.inc();
+        assert_eq!(pb.percent(), 33);
+        pb.add(5);
+        assert_eq!(pb.fraction(), 1.);
+        assert!(!pb.is_finished());
+        pb.finish_draw();
+        assert!(pb.is_finished());
+        assert_eq!(ProgressBar::on(Vec::new(), 0).fraction(), 1.);
+    }
+
+    #[test]
+    fn debug_display() {
+        let mut pb = ProgressBar::on(Vec::new(), 4);
+        pb.inc();
+        assert_eq!(pb.to_string(), "1 / 4 (25%)");
+        let debug = format!("{:?}", pb);
+        assert!(debug.contains("current: 1"));
+        assert!(debug.contains("format: \"[=>-]\""));
+        assert!(debug.contains("Vec<u8>"));
+    }
+
+    #[test]
+    fn direction() {
+        let mut pb = ProgressBar::on(Vec::new(), 4);
+        pb.set_width(Some(80));
+        pb.set_direction(Direction::Drain);
+        pb.inc();
+        let out = output(&pb);
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.contains("3 / 4"), "should count the remaining items");
+        assert!(last.contains("[=="), "should start full");
+        assert!(last.contains("-]"), "should empty from the end");
+    }
+
+    #[test]
+    fn remaining() {
+        let mut pb = ProgressBar::on(Vec::new(), 2000);
+        pb.show_remaining = true;
+        pb.add(766);
+        let out = output(&pb);
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.contains("remaining: 1 234 "));
+        assert!(last.contains(" items/s "));
+        assert_eq!(super::group_digits(0, ' '), "0");
+        assert_eq!(super::group_digits(123456, ' '), "123 456");
+        assert_eq!(super::group_digits(1234567, ' '), "1 234 567");
+    }
+
+    #[test]
+    fn skipped() {
+        let mut env = Env::colors(true);
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_colors(true);
+        pb.set_width(Some(40));
+        pb.show_speed = false;
+        pb.show_percent = false;
+        pb.show_time_left = false;
+        pb.inc_skipped(4);
+        pb.add(2);
+        assert_eq!(pb.skipped(), 4);
+        let out = output(&pb);
+        let last = out.rsplit('\r').next().unwrap();
+        assert_eq!(
+            last.trim_end(),
+            "6 / 10 (4 skipped) [\x1B[2m=======\x1B[0m===>-------]"
+        );
+        env.set("NO_COLOR", Some("1"));
+        pb.tick();
+        assert_eq!(
+            pb.last_frame.trim_end(),
+            "6 / 10 (4 skipped) [==========>-------]"
+        );
+
+        pb.set_template(Some("{pos}{?skipped: ({skipped} skipped)}"))
+            .unwrap();
+        pb.inc();
+        assert!(output(&pb).trim_end().ends_with("7 (4 skipped)"));
+    }
+
+    #[test]
+    fn identical_frames() {
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.set(5);
+        let n = output(&pb).len();
+        pb.set(5);
+        assert_eq!(output(&pb).len(), n, "should not repeat the same frame");
+        pb.set(6);
+        assert!(output(&pb).len() > n, "should draw a changed frame");
+    }
+
+    #[test]
+    fn pin_to_row() {
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.pin_to_row(Some(Row::Top(2)));
+        pb.inc();
+        let out = output(&pb);
+        assert!(out.starts_with("\x1B7\x1B[3;1H\x1B[2K"));
+        assert!(out.ends_with("\x1B8"));
+        assert!(!out.contains('\r'));
+    }
+
+    #[test]
+    fn job_layout() {
+        let mut pb = ProgressBar::on(Vec::new(), 120);
+        pb.set_width(Some(60));
+        pb.set_layout(Layout::Job("Compiling".into()));
+        pb.message("serde v1.0");
+        pb.add(34);
+        let out = output(&pb);
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.starts_with(" ⠙ Compiling   serde v1.0 "));
+        assert!(last.ends_with("[=====>--------------] 34 / 120"));
+        assert_eq!(last.chars().count(), 60);
+
+        let _env = Env::colors(true);
+        pb.set_colors(true);
+        pb.set_thousands_separator(Some(','));
+        pb.set_unit_label("crates");
+        pb.total = 1200;
+        pb.add(1000);
+        let out = output(&pb);
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.starts_with(" ⠹ \x1B[1mCompiling   \x1B[0ms "));
+        assert!(last.ends_with("] 1,034 / 1,200 crates"));
+        assert_eq!(::glyph::width(last), 60);
+    }
+
+    #[test]
+    fn glyphs() {
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_width(Some(40));
+        pb.format("╢▌▌░╟");
+        pb.message("✅ très long message that does not fit");
+        pb.inc();
+        let out = output(&pb);
+        let last = out.rsplit('\r').next().unwrap();
+        assert_eq!(::glyph::width(last), 40);
+
+        pb.set_ascii(true);
+        pb.message("✅ ok ");
+        pb.inc();
+        let out = output(&pb);
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.starts_with("+ ok 2 / 10 [#"));
+        assert!(last.contains("-] "));
+    }
+
+    #[test]
+    fn width_source() {
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_default_width(Some(33));
+        match pb.width_source() {
+            WidthSource::Terminal | WidthSource::Columns => {}
+            source => {
+                assert_eq!(source, WidthSource::Default);
+                assert_eq!(pb.width(), 33);
+            }
+        }
+        pb.set_width(Some(50));
+        assert_eq!(pb.width_source(), WidthSource::Configured);
+        assert_eq!(pb.width(), 50);
+    }
+
+    #[test]
+    fn template() {
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_width(Some(30));
+        pb.set_template(Some("{?msg:{msg}: }{pos}/{total} {bar}{?finished: done!}"))
+            .unwrap();
+        pb.inc();
+        let out = output(&pb);
+        let last = out.rsplit('\r').next().unwrap();
+        assert_eq!(last, "1/10 [==>-------------------] ");
+
+        pb.message("dl");
+        pb.set(10);
+        let out = output(&pb);
+        let last = out.rsplit('\r').next().unwrap();
+        assert_eq!(last, "dl: 10/10 [===========] done! ");
+        assert!(pb.set_template(Some("{bar")).is_err());
+
+        let mut pb = ProgressBar::on(Vec::new(), 2048);
+        pb.set_width(Some(50));
+        pb.message("copy");
+        pb.set_template(Some("{prefix} {bar:10} {percent} {bytes}/{total_bytes}"))
+            .unwrap();
+        pb.add(1024);
+        assert_eq!(
+            pb.last_frame.trim_end(),
+            "copy [====>-----] 50% 1.00 KB/2.00 KB"
+        );
+    }
+
+    #[test]
+    fn split_handle() {
+        use std::io::{copy, sink};
+        use std::thread;
+        let mut pb = ProgressBar::on(Vec::new(), 30);
+        let threads: Vec<_> = vec![&[0u8; 10][..], &[0u8; 20][..]]
+            .into_iter()
+            .map(|range| {
+                let mut reader = pb.split_handle().wrap_read(range);
+                thread::spawn(move || copy(&mut reader, &mut sink()).unwrap())
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        pb.split_handle().inc();
+        pb.tick();
+        assert_eq!(pb.current, 31);
+    }
+
+    #[test]
+    fn zero_total() {
+        let last = |policy| {
+            let mut pb = ProgressBar::on(Vec::new(), 0);
+            pb.set_width(Some(30));
+            pb.show_speed = false;
+            pb.set_zero_total_policy(policy);
+            pb.tick();
+            let out = output(&pb);
+            out.rsplit('\r').next().unwrap().trim_end().to_owned()
+        };
+        assert_eq!(
+            last(ZeroTotalPolicy::Complete),
+            "0 / 0 [===========] 100.00 %"
+        );
+        assert_eq!(last(ZeroTotalPolicy::Spinner), "0 | 0s");
+        assert_eq!(last(ZeroTotalPolicy::Error), "error: total is 0");
+    }
+
+    #[test]
+    fn smooth() {
+        let mut pb = ProgressBar::on(Vec::new(), 80);
+        pb.set_width(Some(13));
+        pb.show_counter = false;
+        pb.show_speed = false;
+        pb.show_percent = false;
+        pb.show_time_left = false;
+        pb.set_smooth(true);
+        pb.add(25);
+        assert_eq!(pb.last_frame.trim_end(), "[███▏      ]");
+        pb.add(5);
+        assert_eq!(pb.last_frame.trim_end(), "[███▊      ]");
+        pb.add(2);
+        assert_eq!(pb.last_frame.trim_end(), "[████      ]");
+        pb.set_ascii(true);
+        pb.add(8);
+        assert_eq!(pb.last_frame.trim_end(), "[====>-----]");
+    }
+
+    #[test]
+    fn spinner() {
+        let mut pb = ProgressBar::on(Vec::new(), 0);
+        pb.set_width(Some(30));
+        pb.show_speed = false;
+        pb.set_zero_total_policy(ZeroTotalPolicy::Spinner);
+        let last = |pb: &ProgressBar<Vec<u8>>| {
+            let out = output(pb);
+            out.rsplit('\r').next().unwrap().trim_end().to_owned()
+        };
+        pb.add(3);
+        assert_eq!(last(&pb), "3 | 0s");
+        pb.set_total(6);
+        assert_eq!(last(&pb), "3 / 6 [====>-----] 50.00 % 0s");
+    }
+
+    #[test]
+    fn unbounded() {
+        use estimator::Estimator;
+        use std::time::Duration;
+        struct Fixed;
+        impl Estimator for Fixed {
+            fn record(&mut self, _: u64, _: Duration) {}
+            fn rate(&self) -> f64 {
+                2.
+            }
+        }
+        let term = Terminal::new(40, 5);
+        let mut pb = ProgressBar::on(term.clone(), u64::MAX);
+        pb.set_width(Some(40));
+        pb.show_tick = false;
+        pb.set_estimator(Some(Box::new(Fixed)));
+        pb.add(3);
+        assert_eq!(term.screen(), "3 | 2.00/s 0s");
+        assert_eq!(pb.percent(), 0);
+        assert_eq!(pb.render_compact(12), "[........] 3");
+        assert_eq!(pb.to_string(), "3");
+        pb.set_template(Some("{pos}/{total} {percent}{eta}"))
+            .unwrap();
+        pb.tick();
+        assert_eq!(pb.last_frame.trim_end(), "3/");
+        pb.finish();
+        assert_eq!(term.screen(), "3/");
+    }
+
+    #[test]
+    fn ttfb() {
+        use std::time::Duration;
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.show_ttfb = true;
+        pb.mark_started();
+        pb.tick();
+        assert!(pb.ttfb().is_none());
+        ::std::thread::sleep(Duration::from_millis(20));
+        pb.inc();
+        let ttfb = pb.ttfb().unwrap();
+        assert!(ttfb >= Duration::from_millis(20));
+        pb.inc();
+        assert_eq!(pb.ttfb(), Some(ttfb), "should keep the first increment");
+        assert!(output(&pb).contains(" ttfb "));
+    }
+
+    #[test]
+    fn estimator() {
+        use estimator::Estimator;
+        use std::time::Duration;
+        struct Fixed;
+        impl Estimator for Fixed {
+            fn record(&mut self, _: u64, _: Duration) {}
+            fn rate(&self) -> f64 {
+                0.5
+            }
+        }
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_width(Some(80));
+        pb.set_estimator(Some(Box::new(Fixed)));
+        pb.inc();
+        let out = output(&pb);
+        assert!(out.trim_end().ends_with("0.50/s 18s"));
+    }
+
+    #[test]
+    fn warmup() {
+        use std::time::Duration;
+        let secs = Duration::from_secs;
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.set_warmup(Some(Warmup::Items(10)));
+        pb.current = 10;
+        assert_eq!(pb.rate(secs(10)), 1.);
+        pb.current = 30;
+        assert_eq!(pb.rate(secs(12)), 10.);
+
+        pb.set_warmup(Some(Warmup::Time(secs(5))));
+        pb.current = 5;
+        assert_eq!(pb.rate(secs(1)), 5.);
+        pb.current = 20;
+        assert_eq!(pb.rate(secs(5)), 4.);
+        pb.current = 40;
+        assert_eq!(pb.rate(secs(7)), 10.);
+
+        pb.set_warmup(None);
+        assert_eq!(pb.rate(secs(8)), 5.);
+    }
+
+    #[test]
+    fn sleep() {
+        use std::thread;
+        use std::time::{Duration, Instant};
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_max_refresh_rate(Some(Duration::from_millis(10)));
+        assert!(pb.sleep(Duration::from_millis(50)));
+        assert!(pb.tick_state > 0, "should tick while sleeping");
+
+        let cancel = pb.cancel_handle();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(30));
+            cancel.cancel();
+        });
+        let start = Instant::now();
+        assert!(!pb.sleep(Duration::from_secs(10)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(pb.is_cancelled());
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn notifier() {
+        use notify::{Milestone, Notifier};
+        use std::sync::{Arc, Mutex};
+        struct Log(Arc<Mutex<Vec<(Milestone, String)>>>);
+        impl Notifier for Log {
+            fn notify(&mut self, milestone: Milestone, line: &str) {
+                self.0.lock().unwrap().push((milestone, line.to_owned()));
+            }
+        }
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.set_width(Some(20));
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.set_notifier(Some(Box::new(Log(log.clone()))));
+        pb.add(30);
+        pb.add(30);
+        pb.finish();
+        let milestones: Vec<Milestone> = log.lock().unwrap().iter().map(|e| e.0).collect();
+        assert_eq!(
+            milestones,
+            [
+                Milestone::Percent(25),
+                Milestone::Percent(50),
+                Milestone::Percent(75),
+                Milestone::Finished
+            ]
+        );
+        assert_eq!(log.lock().unwrap()[0].1, "30 / 100  30.00 %");
+
+        log.lock().unwrap().clear();
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.set_notifier(Some(Box::new(Log(log.clone()))));
+        drop(pb);
+        assert_eq!(log.lock().unwrap()[0].0, Milestone::Abandoned);
+    }
+
+    #[test]
+    fn expected_duration() {
+        use std::time::Duration;
+        use time::SteadyTime;
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.set_width(Some(14));
+        pb.show_counter = false;
+        pb.show_speed = false;
+        pb.show_percent = false;
+        pb.show_time_left = false;
+        pb.set_expected_duration(Some(Duration::from_secs(10)));
+        pb.start_time = Some(SteadyTime::now() - ::time::Duration::seconds(5));
+        // behind: half the time is gone, a fifth of the work is done
+        pb.add(20);
+        assert_eq!(pb.last_frame.trim_end(), "[==>--|-----]");
+        // ahead
+        pb.add(50);
+        assert_eq!(pb.last_frame.trim_end(), "[=====|=>---]");
+        pb.inc_skipped(30);
+        assert_eq!(
+            pb.last_frame.trim_end(),
+            "[===========]",
+            "should hide the marker once done"
+        );
+    }
+
+    #[test]
+    fn chain() {
+        use time::SteadyTime;
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_template(Some("{msg}{pos}/{total} {elapsed}"))
+            .unwrap();
+        pb.message("fetch ");
+        pb.start_time = Some(SteadyTime::now() - ::time::Duration::seconds(5));
+        pb.add(10);
+        assert_eq!(pb.last_frame.trim_end(), "fetch 10/10 5s");
+        pb.chain(4, "unpack ");
+        assert_eq!(pb.last_frame.trim_end(), "unpack 0/4 5s");
+        pb.start_time = Some(SteadyTime::now() - ::time::Duration::seconds(2));
+        pb.inc();
+        assert_eq!(pb.last_frame.trim_end(), "unpack 1/4 7s");
+    }
+
+    #[test]
+    fn reset() {
+        use time::{Duration, SteadyTime};
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.set_template(Some("{msg}{pos}/{total} {elapsed} {eta}"))
+            .unwrap();
+        pb.start_time = Some(SteadyTime::now() - Duration::seconds(5));
+        pb.add(50);
+        assert_eq!(pb.last_frame.trim_end(), "50/100 5s 5s");
+        pb.message("verify ");
+        pb.reset();
+        assert_eq!(pb.last_frame.trim_end(), "verify 0/100 0s");
+        pb.start_time = Some(SteadyTime::now() - Duration::seconds(2));
+        pb.add(50);
+        assert_eq!(pb.last_frame.trim_end(), "verify 50/100 2s 2s");
+    }
+
+    #[test]
+    fn reset_eta() {
+        use time::{Duration, SteadyTime};
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.set_template(Some("{pos}/{total} {elapsed} {eta}"))
+            .unwrap();
+        pb.start_time = Some(SteadyTime::now() - Duration::seconds(10));
+        pb.add(10);
+        assert_eq!(pb.last_frame.trim_end(), "10/100 10s 2m");
+        pb.reset_eta();
+        // 30 more in the next 2s
+        pb.start_time = Some(SteadyTime::now() - Duration::seconds(12));
+        pb.add(30);
+        assert_eq!(pb.last_frame.trim_end(), "40/100 12s 4s");
+    }
+
+    #[test]
+    fn pause() {
+        use time::{Duration, SteadyTime};
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.set_template(Some("{pos}/{total} {elapsed} {eta}"))
+            .unwrap();
+        pb.start_time = Some(SteadyTime::now() - Duration::seconds(5));
+        pb.add(50);
+        assert_eq!(pb.last_frame.trim_end(), "50/100 5s 5s");
+        pb.pause();
+        assert!(pb.is_paused());
+        // 20s waiting for the user
+        pb.paused_at = Some(SteadyTime::now() - Duration::seconds(20));
+        pb.start_time = Some(SteadyTime::now() - Duration::seconds(25));
+        pb.tick();
+        assert_eq!(pb.last_frame.trim_end(), "50/100 5s 5s");
+        pb.resume();
+        assert!(!pb.is_paused());
+        pb.add(25);
+        assert_eq!(pb.last_frame.trim_end(), "75/100 5s 2s");
+    }
+
+    #[test]
+    fn auto_units() {
+        let mut pb = ProgressBar::on(Vec::new(), 2_500_000);
+        pb.set_width(Some(80));
+        pb.show_bar = false;
+        pb.show_percent = false;
+        pb.show_time_left = false;
+        pb.set_units(Units::Auto(None));
+        pb.add(1_234_567);
+        assert!(pb.last_frame.starts_with("1234.6k / 2500.0k "));
+        pb.total = 25_000_000;
+        pb.inc();
+        assert!(pb.last_frame.starts_with("1.2M / 25.0M "));
+        pb.set_units(Units::Auto(Some(UnitsHint::Bytes)));
+        pb.inc();
+        assert!(pb.last_frame.starts_with("1.18 MB / 23.84 MB "));
+        pb.set_units(Units::Auto(Some(UnitsHint::Items)));
+        pb.total = 5_000_000;
+        pb.set(1_234);
+        assert!(pb.last_frame.starts_with("1.2k / 5000.0k "));
+        pb.total = 5_000;
+        pb.inc();
+        assert!(pb.last_frame.starts_with("1235 / 5000 "));
+        assert_eq!(super::scaled(12.345, 12.345), "12.35");
+    }
+
+    #[test]
+    fn println() {
+        let mut out = Vec::new();
+        {
+            let mut pb = ProgressBar::on(&mut out, 10);
+            pb.set_width(Some(20));
+            pb.show_speed = false;
+            pb.show_time_left = false;
+            pb.show_percent = false;
+            pb.println("before");
+            pb.add(2);
+            pb.println("log");
+            pb.inc();
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            "before\n\r2 / 10 [=>--------] \rlog                 \n\r2 / 10 [=>--------] \r3 / 10 [==>-------] "
+        );
+    }
+
+    #[test]
+    fn render_compact() {
+        use time::SteadyTime;
+
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.add(42);
+        pb.start_time = Some(SteadyTime::now() - ::time::Duration::seconds(120));
+        assert_eq!(pb.render_compact(20), "[####.......] 42% 3m");
+        assert_eq!(pb.render_compact(10), "[#...] 42%");
+        assert_eq!(pb.render_compact(3), "42%");
+        assert_eq!(pb.render_compact(2), "");
+        pb.set(100);
+        assert_eq!(pb.render_compact(12), "[#####] 100%");
+    }
+
+    #[test]
+    fn unit_label() {
+        let mut pb = ProgressBar::on(Vec::new(), 50);
+        pb.set_width(Some(80));
+        pb.show_bar = false;
+        pb.show_percent = false;
+        pb.show_time_left = false;
+        pb.set_unit_label("rows");
+        pb.add(12);
+        assert!(
+            pb.last_frame.starts_with("12 / 50 rows "),
+            "{:?}",
+            pb.last_frame
+        );
+        assert!(pb.last_frame.contains(" rows/s "));
+        pb.total = 50_000;
+        pb.add(1_200);
+        assert!(pb.last_frame.starts_with("1.2k / 50.0k rows "));
+        pb.set_units(Units::Bytes);
+        pb.inc();
+        assert!(!pb.last_frame.contains("rows"));
+    }
+
+    #[test]
+    fn non_ascii_unit_label() {
+        for &total in &[100, 100_000] {
+            let mut pb = ProgressBar::on(Vec::new(), total);
+            pb.set_width(Some(80));
+            pb.set_unit_label("файлы");
+            pb.add(12);
+            assert!(pb.last_frame.contains(" файлы "), "{:?}", pb.last_frame);
+            assert!(pb.last_frame.contains("файлы/s "), "{:?}", pb.last_frame);
+            assert_eq!(::glyph::width(&pb.last_frame), 80);
+        }
+    }
+
+    #[test]
+    fn thousands_separator() {
+        let mut pb = ProgressBar::on(Vec::new(), 250_000_000);
+        pb.set_width(Some(80));
+        pb.set_thousands_separator(Some(','));
+        pb.add(1_234_567);
+        assert!(pb.last_frame.starts_with("1,234,567 / 250,000,000 "));
+        pb.show_remaining = true;
+        pb.set_thousands_separator(Some('.'));
+        pb.inc();
+        assert!(pb.last_frame.starts_with("remaining: 248.765.432 "));
+    }
+
+    #[test]
+    fn non_ascii_separator() {
+        for &sep in &['\u{202f}', '’', '\u{a0}'] {
+            let mut pb = ProgressBar::on(Vec::new(), 250_000_000);
+            pb.set_width(Some(80));
+            pb.set_thousands_separator(Some(sep));
+            pb.add(1_234_567);
+            let counter = format!("1{0}234{0}567 / 250{0}000{0}000 ", sep);
+            assert!(pb.last_frame.starts_with(&counter), "{:?}", pb.last_frame);
+            assert_eq!(::glyph::width(&pb.last_frame), 80);
+            pb.inc_skipped(1_000);
+            pb.set_units(Units::Bytes);
+            pb.set_exact_bytes(ExactBytes::Always);
+            pb.set_width(Some(120));
+            pb.inc();
+            assert_eq!(::glyph::width(&pb.last_frame), 120);
+        }
+    }
+
+    #[test]
+    fn exact_bytes() {
+        let mut pb = ProgressBar::on(Vec::new(), 1_503_238_553);
+        pb.set_width(Some(100));
+        pb.set_units(Units::Bytes);
+        pb.add(1024);
+        assert!(pb.last_frame.starts_with("1.00 KB / 1.40 GB "));
+        pb.set_exact_bytes(ExactBytes::Always);
+        pb.inc();
+        assert!(pb
+            .last_frame
+            .starts_with("1.00 KB (1 025 B) / 1.40 GB (1 503 238 553 B) "));
+        pb.set_exact_bytes(ExactBytes::OnFinish);
+        pb.inc();
+        assert!(pb.last_frame.starts_with("1.00 KB / 1.40 GB "));
+        pb.set(1_503_238_553);
+        assert!(pb
+            .last_frame
+            .starts_with("1.40 GB (1 503 238 553 B) / 1.40 GB (1 503 238 553 B) "));
+    }
+
+    #[test]
+    fn bar_label() {
+        let mut env = Env::colors(true);
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.set_colors(true);
+        pb.set_width(Some(30));
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.set_bar_label(BarLabel::Percent);
+        pb.set(50);
+        assert_eq!(
+            pb.last_frame,
+            "50 / 100 [=======\x1B[7m50\x1B[0m%--------] "
+        );
+        env.set("NO_COLOR", Some("1"));
+        pb.tick();
+        assert_eq!(pb.last_frame, "50 / 100 [=======50%--------] ");
+        pb.set_bar_label(BarLabel::Counter);
+        pb.set(10);
+        assert_eq!(pb.last_frame, "[=>----10/100------] 10.00 %  ");
+    }
+
+    #[test]
+    fn block_style() {
+        let mut env = Env::colors(true);
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.set_colors(true);
+        pb.set_width(Some(30));
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.show_counter = false;
+        pb.set_block_style(Some(33));
+        pb.set(50);
+        assert_eq!(
+            pb.last_frame,
+            "[\x1B[48;5;33m         \x1B[0m         ] 50.00 %  "
+        );
+        env.set("NO_COLOR", Some("1"));
+        pb.set(60);
+        assert_eq!(pb.last_frame, "[==========>-------] 60.00 %  ");
+    }
+
+    #[test]
+    fn paint() {
+        use style::{Color, Paint, Style};
+        let _env = Env::colors(true);
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.set_width(Some(40));
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.show_counter = false;
+        pb.message("job ");
+        let mut style = Style::new();
+        style.set_width(Some(40));
+        style.show_speed = false;
+        style.show_time_left = false;
+        style.show_counter = false;
+        style.paint_bar(Paint::new().fg(Color::Green));
+        style.paint_percent(Paint::new().bold());
+        style.paint_message(Paint::new().fg(Color::Fixed(208)).bg(Color::Black));
+        pb.set_style(&style);
+        pb.set(50);
+        assert_eq!(pb.last_frame, "job [===========>------------] 50.00 %  ");
+        pb.set_colors(true);
+        pb.tick();
+        assert_eq!(
+            pb.last_frame,
+            "\x1B[38;5;208;40mjob \x1B[0m[\x1B[32m===========>\x1B[0m------------]\x1B[1m 50.00 % \x1B[0m "
+        );
+        pb.assume_pager();
+        assert!(!pb.colors());
+    }
+
+    #[test]
+    fn eta_avg() {
+        use std::time::Duration;
+        use template::Template;
+        let mut pb = ProgressBar::on(Vec::new(), 100);
+        pb.current = 50;
+        let t = Template::parse("{eta} {eta_avg}").unwrap();
+        // sped up to 10/s, after 50s at 1/s on average
+        let line = pb.template_line(&t, 40, 50, 10., Duration::from_secs(50));
+        assert_eq!(line, "5s 50s");
+    }
+
+    #[test]
+    fn inflight() {
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.set_width(Some(30));
+        pb.set_template(Some("{pos}/{total}{?inflight: ({inflight} running)}"))
+            .unwrap();
+        pb.begin_item();
+        assert_eq!(pb.begin_item(), 2);
+        assert_eq!(pb.last_frame.trim_end(), "0/10 (2 running)");
+        pb.end_item();
+        pb.inc();
+        assert_eq!(pb.last_frame.trim_end(), "1/10 (1 running)");
+        pb.end_item();
+        assert_eq!(pb.end_item(), 0);
+        assert_eq!(pb.last_frame.trim_end(), "1/10");
+    }
+
+    #[test]
+    fn trend() {
+        let mut pb = ProgressBar::on(Vec::new(), 1000);
+        pb.set_width(Some(60));
+        pb.show_trend = true;
+        pb.add(10);
+        // unknown until the end of the first window
+        assert!(pb.last_frame.contains("/s "), "{:?}", pb.last_frame);
+        assert!(!pb.last_frame.contains('→'), "{:?}", pb.last_frame);
+        let ago =
+            |pb: &ProgressBar<Vec<u8>>| pb.trend_base.unwrap().0 - ::time::Duration::seconds(11);
+        pb.trend_base = Some((ago(&pb), 0.));
+        pb.add(10);
+        assert!(pb.last_frame.contains("/s ↑ "), "{:?}", pb.last_frame);
+        // the new window hasn't passed yet
+        pb.add(10);
+        assert!(pb.last_frame.contains("/s ↑ "), "{:?}", pb.last_frame);
+        pb.trend_base = Some((ago(&pb), f64::MAX));
+        pb.set_ascii(true);
+        pb.add(10);
+        assert!(pb.last_frame.contains("/s v "), "{:?}", pb.last_frame);
+
+        pb.set_template(Some("{pos}{?trend: {trend}}")).unwrap();
+        pb.add(10);
+        assert_eq!(pb.last_frame.trim_end(), "50 v");
+    }
+
+    #[test]
+    fn finish_with_error() {
+        let mut env = Env::colors(false);
+        let fail = |error: Option<&str>| {
+            let mut out = Vec::new();
+            {
+                let mut pb = ProgressBar::on(&mut out, 10);
+                pb.set_colors(true);
+                pb.set_width(Some(50));
+                pb.message("copy ");
+                pb.add(4);
+                match error {
+                    Some(e) => pb.finish_with_error(e),
+                    None => pb.abandon(),
+                }
+            }
+            let out = String::from_utf8(out).unwrap();
+            out.trim_end_matches('\r')
+                .rsplit('\r')
+                .next()
+                .unwrap()
+                .to_owned()
+        };
+        // the bar stays at 4, without speed nor time left
+        assert_eq!(
+            fail(Some("disk full")),
+            "copy 4 / 10 [=====>--------] 40.00 % ✗ disk full  "
+        );
+        assert_eq!(
+            fail(None),
+            "copy 4 / 10 [=========>--------------] 40.00 % ✗  "
+        );
+        env.set("NO_COLOR", None);
+        assert_eq!(
+            fail(Some("disk full")),
+            "copy 4 / 10 [\x1B[31m=====>\x1B[0m--------] 40.00 % \x1B[31m✗ disk full \x1B[0m "
+        );
+    }
+
+    #[test]
+    fn finish_behavior() {
+        let finish = |behavior: FinishBehavior, drop_only: bool| {
+            let mut out = Vec::new();
+            {
+                let mut pb = ProgressBar::on(&mut out, 10);
+                pb.set_width(Some(20));
+                pb.message("copy ");
+                pb.inc();
+                if drop_only {
+                    pb.set_finish_behavior(behavior);
+                } else {
+                    pb.finish_with(behavior);
+                }
+            }
+            let out = String::from_utf8(out).unwrap();
+            let end = out.trim_end_matches('\r').rfind('\r').unwrap();
+            out[end..].to_owned()
+        };
+        assert_eq!(
+            finish(FinishBehavior::Clear, false),
+            format!("\r{}\r", " ".repeat(20))
+        );
+        assert_eq!(
+            finish(FinishBehavior::ReplaceWith("done".to_owned()), false),
+            format!("\rdone{}", " ".repeat(16))
+        );
+        let summary = finish(FinishBehavior::Summary, true);
+        assert!(summary.starts_with("\rcopy 10 in 0s ("), "{:?}", summary);
+        assert!(summary.trim_end().ends_with("/s)"));
+    }
+
+    #[test]
+    fn finish_and_clear() {
+        let mut term = Terminal::new(30, 5);
+        writeln!(term, "before").unwrap();
+        let mut pb = ProgressBar::on(term.clone(), 10);
+        pb.set_width(Some(30));
+        pb.add(4);
+        assert!(term.screen().starts_with("before\n4 / 10 "));
+        pb.finish_and_clear();
+        writeln!(term, "after").unwrap();
+        assert_eq!(term.screen(), "before\nafter");
+    }
+
+    #[test]
+    fn clone_view() {
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.format("[##.]");
+        let mut view = pb.clone_view(Vec::new());
+        view.set_width(Some(40));
+        view.show_speed = false;
+        view.show_time_left = false;
+        view.message("log ");
+        pb.add(5);
+        view.tick();
+        assert!(view.last_frame.starts_with("log 5 / 10 [#####"));
+        view.add(2);
+        pb.tick();
+        assert!(pb.last_frame.starts_with("7 / 10 "));
+
+        pb.total = 20;
+        pb.tick();
+        view.tick();
+        assert_eq!(view.total, 20);
+        view.finish();
+        pb.tick();
+        assert!(pb.last_frame.starts_with("20 / 20 "));
+    }
+
     #[test]
     fn kb_fmt() {
         let kb = 1024f64;
@@ -572,4 +4363,23 @@ mod test {
         assert_eq!(kb_fmt!(gb), "1.00 GB");
         assert_eq!(kb_fmt!(tb), "1.00 TB");
     }
+
+    #[test]
+    fn byte_prefix() {
+        let mut pb = ProgressBar::on(Vec::new(), 2_000_000);
+        pb.set_width(Some(80));
+        pb.set_units(Units::Bytes);
+        pb.add(1_500_000);
+        assert!(pb.last_frame.starts_with("1.43 MB / 1.91 MB "));
+        pb.set_byte_prefix(BytePrefix::Binary);
+        pb.tick();
+        assert!(pb.last_frame.starts_with("1.43 MiB / 1.91 MiB "));
+        assert!(pb.last_frame.contains("iB/s "), "{:?}", pb.last_frame);
+        pb.set_byte_prefix(BytePrefix::Decimal);
+        pb.tick();
+        assert!(pb.last_frame.starts_with("1.50 MB / 2.00 MB "));
+        assert_eq!(pb.size(999.), "999 B");
+        assert_eq!(pb.size(1000.), "1.00 kB");
+        assert_eq!(pb.size(2.5e12), "2.50 TB");
+    }
 }
@@ -0,0 +1,216 @@
+use adder::Adder;
+use pb::ProgressBar;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::thread;
+use std::time::Duration;
+
+/// SharedBar is a `ProgressBar` that can be cloned and used from several
+/// threads at once.
+///
+/// The position is an atomic counter, so `inc` never waits for another
+/// thread: the clone that finds the bar free draws it, the others only
+/// count.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::thread;
+/// use pbr::{ProgressBar, SharedBar};
+///
+/// let pb = SharedBar::new(ProgressBar::new(4000));
+/// let workers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let pb = pb.clone();
+///         thread::spawn(move || {
+///             for _ in 0..1000 {
+///                 pb.inc();
+///             }
+///         })
+///     })
+///     .collect();
+/// for w in workers {
+///     w.join().unwrap();
+/// }
+/// pb.finish();
+/// ```
+pub struct SharedBar<T: Write> {
+    adder: Adder,
+    bar: Arc<Mutex<Option<ProgressBar<T>>>>,
+}
+
+impl<T: Write> SharedBar<T> {
+    pub fn new(mut pb: ProgressBar<T>) -> SharedBar<T> {
+        SharedBar {
+            adder: pb.split_handle(),
+            bar: Arc::new(Mutex::new(Some(pb))),
+        }
+    }
+
+    /// Add `n` to the bar, and draw it unless another thread is drawing.
+    pub fn add(&self, n: u64) {
+        self.adder.add(n);
+        self.tick();
+    }
+
+    /// Add 1 to the bar, see `add`.
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Draw the bar with the counts added so far, unless another thread is
+    /// drawing, e.g. from a ticker thread.
+    pub fn tick(&self) {
+        let mut bar = match self.bar.try_lock() {
+            Ok(bar) => bar,
+            Err(TryLockError::WouldBlock) => return,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        };
+        if let Some(ref mut pb) = *bar {
+            pb.tick();
+        }
+    }
+
+    /// Set the total, see `ProgressBar::set_total`.
+    pub fn set_total(&self, total: u64) {
+        if let Some(ref mut pb) = *self.lock() {
+            pb.set_total(total);
+        }
+    }
+
+    /// Set the message, see `ProgressBar::message`.
+    pub fn message<S: Into<Cow<'static, str>>>(&self, message: S) {
+        if let Some(ref mut pb) = *self.lock() {
+            pb.message(message);
+        }
+    }
+
+    /// Stop the elapsed clock, see `ProgressBar::pause`.
+    pub fn pause(&self) {
+        if let Some(ref mut pb) = *self.lock() {
+            pb.pause();
+        }
+    }
+
+    /// Restart the elapsed clock, see `ProgressBar::resume`.
+    pub fn resume(&self) {
+        if let Some(ref mut pb) = *self.lock() {
+            pb.resume();
+        }
+    }
+
+    /// Print a line above the bar, see `ProgressBar::println`.
+    pub fn println(&self, s: &str) {
+        if let Some(ref mut pb) = *self.lock() {
+            pb.println(s);
+        }
+    }
+
+    /// Finish the bar, see `ProgressBar::finish`. The other clones don't
+    /// draw anymore.
+    pub fn finish(&self) {
+        let pb = self.lock().take();
+        if let Some(pb) = pb {
+            pb.finish();
+        }
+    }
+
+    // lock waits for the bar, a thread that panicked while drawing doesn't
+    // keep the others from using it.
+    fn lock(&self) -> MutexGuard<'_, Option<ProgressBar<T>>> {
+        self.bar.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T: Write + Send + 'static> SharedBar<T> {
+    /// Draw the bar every `interval` from a background thread, so the
+    /// elapsed time, the ETA and the spinner move on while no progress is
+    /// reported. The thread stops once the bar is finished or dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use pbr::{ProgressBar, SharedBar};
+    ///
+    /// let pb = SharedBar::new(ProgressBar::new(10));
+    /// pb.enable_steady_tick(Duration::from_millis(100));
+    /// ```
+    pub fn enable_steady_tick(&self, interval: Duration) {
+        let bar = Arc::downgrade(&self.bar);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let bar = match bar.upgrade() {
+                Some(bar) => bar,
+                None => return,
+            };
+            let mut bar = bar.lock().unwrap_or_else(|e| e.into_inner());
+            match *bar {
+                Some(ref mut pb) => pb.tick(),
+                None => return,
+            }
+        });
+    }
+}
+
+impl<T: Write> Clone for SharedBar<T> {
+    fn clone(&self) -> SharedBar<T> {
+        SharedBar {
+            adder: self.adder.clone(),
+            bar: self.bar.clone(),
+        }
+    }
+}
+
+impl<T: Write> fmt::Debug for SharedBar<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedBar").field("bar", &self.bar).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pb::{ProgressBar, ZeroTotalPolicy};
+    use shared::SharedBar;
+    use std::thread;
+    use std::time::Duration;
+    use testing::Output;
+
+    #[test]
+    fn steady_tick() {
+        let out = Output::default();
+        let mut pb = ProgressBar::on(out.clone(), 0);
+        pb.set_zero_total_policy(ZeroTotalPolicy::Spinner);
+        let pb = pb.enable_steady_tick(Duration::from_millis(5));
+        thread::sleep(Duration::from_millis(100));
+        pb.finish();
+        let frames = out.text().split('\r').count();
+        assert!(frames > 5, "should tick, {} frames", frames);
+    }
+
+    #[test]
+    fn threads() {
+        let mut out = Vec::new();
+        {
+            let mut pb = ProgressBar::on(&mut out, 1000);
+            pb.set_width(Some(40));
+            let pb = SharedBar::new(pb);
+            thread::scope(|s| {
+                for _ in 0..4 {
+                    let pb = pb.clone();
+                    s.spawn(move || {
+                        for _ in 0..250 {
+                            pb.inc();
+                        }
+                    });
+                }
+            });
+            pb.finish();
+        }
+        let out = String::from_utf8(out).unwrap();
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.starts_with("1000 / 1000 "), "{:?}", last);
+    }
+}
@@ -0,0 +1,214 @@
+//! A progress interface for libraries, see `ProgressSink`.
+
+use pb::ProgressBar;
+use shared::SharedBar;
+use std::fmt;
+use std::io::Write;
+
+/// ProgressSink is the progress of a job, as reported by a library that
+/// leaves the rendering to the application: it takes a
+/// `&mut dyn ProgressSink`, and the application passes a `ProgressBar`, a
+/// bar of a `MultiBar`, a `SharedBar`, or `NoProgress` to show nothing.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pbr::{NoProgress, ProgressBar, ProgressSink};
+///
+/// fn index(files: &[&str], progress: &mut dyn ProgressSink) {
+///     progress.set_len(files.len() as u64);
+///     for f in files {
+///         progress.set_message(f);
+///         // ...
+///         progress.inc(1);
+///     }
+///     progress.finish();
+/// }
+///
+/// index(&["a.txt", "b.txt"], &mut ProgressBar::new(0));
+/// index(&["a.txt", "b.txt"], &mut NoProgress);
+/// ```
+pub trait ProgressSink {
+    /// Set the number of units of the job.
+    fn set_len(&mut self, len: u64);
+
+    /// Add `n` units done.
+    fn inc(&mut self, n: u64);
+
+    /// Set the message describing the current step.
+    fn set_message(&mut self, message: &str);
+
+    /// The job is done.
+    fn finish(&mut self);
+}
+
+impl dyn ProgressSink {
+    /// Create a `ProgressSink` calling `f` with each event, to route the
+    /// progress somewhere else than a terminal, e.g. a GUI.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pbr::{ProgressEvent, ProgressSink};
+    ///
+    /// let mut done = 0;
+    /// {
+    ///     let mut progress = <dyn ProgressSink>::from_fn(|event| {
+    ///         if let ProgressEvent::Inc(n) = event {
+    ///             done += n;
+    ///         }
+    ///     });
+    ///     progress.inc(2);
+    /// }
+    /// assert_eq!(done, 2);
+    /// ```
+    pub fn from_fn<F: FnMut(ProgressEvent)>(f: F) -> FnSink<F> {
+        FnSink { f }
+    }
+}
+
+/// ProgressEvent is a call on a `ProgressSink`, as passed to the closure
+/// of `ProgressSink::from_fn`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressEvent<'a> {
+    /// See `ProgressSink::set_len`.
+    SetLen(u64),
+    /// See `ProgressSink::inc`.
+    Inc(u64),
+    /// See `ProgressSink::set_message`.
+    Message(&'a str),
+    /// See `ProgressSink::finish`.
+    Finish,
+}
+
+/// FnSink is a `ProgressSink` over a closure, see `ProgressSink::from_fn`.
+pub struct FnSink<F> {
+    f: F,
+}
+
+impl<F: FnMut(ProgressEvent)> ProgressSink for FnSink<F> {
+    fn set_len(&mut self, len: u64) {
+        (self.f)(ProgressEvent::SetLen(len));
+    }
+
+    fn inc(&mut self, n: u64) {
+        (self.f)(ProgressEvent::Inc(n));
+    }
+
+    fn set_message(&mut self, message: &str) {
+        (self.f)(ProgressEvent::Message(message));
+    }
+
+    fn finish(&mut self) {
+        (self.f)(ProgressEvent::Finish);
+    }
+}
+
+impl<F> fmt::Debug for FnSink<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FnSink").finish()
+    }
+}
+
+/// NoProgress is a `ProgressSink` that ignores the progress.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn set_len(&mut self, _: u64) {}
+
+    fn inc(&mut self, _: u64) {}
+
+    fn set_message(&mut self, _: &str) {}
+
+    fn finish(&mut self) {}
+}
+
+impl<T: Write> ProgressSink for ProgressBar<T> {
+    fn set_len(&mut self, len: u64) {
+        self.set_total(len);
+    }
+
+    fn inc(&mut self, n: u64) {
+        self.add(n);
+    }
+
+    fn set_message(&mut self, message: &str) {
+        self.message(message.to_owned());
+    }
+
+    fn finish(&mut self) {
+        self.finish_in_place();
+    }
+}
+
+impl<T: Write> ProgressSink for SharedBar<T> {
+    fn set_len(&mut self, len: u64) {
+        self.set_total(len);
+    }
+
+    fn inc(&mut self, n: u64) {
+        self.add(n);
+    }
+
+    fn set_message(&mut self, message: &str) {
+        self.message(message.to_owned());
+    }
+
+    fn finish(&mut self) {
+        SharedBar::finish(self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pb::ProgressBar;
+    use shared::SharedBar;
+    use sink::{NoProgress, ProgressSink};
+
+    fn work(progress: &mut dyn ProgressSink) {
+        progress.set_len(4);
+        progress.set_message("step ");
+        progress.inc(3);
+        progress.finish();
+        progress.finish();
+    }
+
+    #[test]
+    fn sinks() {
+        work(&mut NoProgress);
+
+        let mut out = Vec::new();
+        {
+            let mut pb = ProgressBar::on(&mut out, 0);
+            pb.set_width(Some(40));
+            work(&mut pb);
+            assert!(pb.is_finished());
+            assert_eq!(pb.add(0), 4);
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("step 4 / 4"), "{:?}", out);
+
+        let mut out = Vec::new();
+        work(&mut SharedBar::new(ProgressBar::on(&mut out, 0)));
+        assert!(String::from_utf8(out).unwrap().contains("step 4 / 4"));
+    }
+
+    #[test]
+    fn from_fn() {
+        let mut events = Vec::new();
+        work(&mut <dyn ProgressSink>::from_fn(|e| {
+            events.push(format!("{:?}", e))
+        }));
+        assert_eq!(
+            events,
+            [
+                "SetLen(4)",
+                "Message(\"step \")",
+                "Inc(3)",
+                "Finish",
+                "Finish"
+            ]
+        );
+    }
+}
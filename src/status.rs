@@ -0,0 +1,109 @@
+use pb::ProgressBar;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{Stdout, Write};
+use std::time::Duration;
+
+/// A continuously updated single line, without total or percent.
+///
+/// It is drawn, throttled and cleared like a `ProgressBar`, for programs that
+/// need a live status but not a bar.
+pub struct StatusLine<T: Write> {
+    pb: ProgressBar<T>,
+}
+
+impl StatusLine<Stdout> {
+    /// Create a new StatusLine with stdout as a writer.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::StatusLine;
+    ///
+    /// let mut status = StatusLine::new();
+    /// status.set("connecting to replica 3…");
+    /// status.finish_print("connected");
+    /// ```
+    pub fn new() -> StatusLine<Stdout> {
+        StatusLine::on(::std::io::stdout())
+    }
+}
+
+impl Default for StatusLine<Stdout> {
+    fn default() -> Self {
+        StatusLine::new()
+    }
+}
+
+impl<T: Write> StatusLine<T> {
+    /// Create a new StatusLine and pass an arbitrary writer.
+    pub fn on(handle: T) -> StatusLine<T> {
+        let mut pb = ProgressBar::on(handle, 0);
+        pb.show_bar = false;
+        pb.show_speed = false;
+        pb.show_percent = false;
+        pb.show_counter = false;
+        pb.show_time_left = false;
+        StatusLine { pb }
+    }
+
+    /// Replace the status and redraw the line.
+    pub fn set<S: Into<Cow<'static, str>>>(&mut self, status: S) {
+        self.pb.message(status);
+        self.pb.tick();
+    }
+
+    /// Set the width of the line, default is the terminal width, see
+    /// `ProgressBar::set_width`.
+    pub fn set_width(&mut self, w: Option<usize>) {
+        self.pb.set_width(w);
+    }
+
+    /// Set max refresh rate, see `ProgressBar::set_max_refresh_rate`.
+    pub fn set_max_refresh_rate(&mut self, w: Option<Duration>) {
+        self.pb.set_max_refresh_rate(w);
+    }
+
+    /// Clear the line.
+    pub fn clear(self) {
+        self.finish_print("");
+    }
+
+    /// Write string `s` that will replace the status.
+    pub fn finish_print(self, s: &str) {
+        self.pb.finish_print(s);
+    }
+}
+
+impl<T: Write> fmt::Debug for StatusLine<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StatusLine").field("pb", &self.pb).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use status::StatusLine;
+    use testing::Output;
+
+    #[test]
+    fn set() {
+        let out = Output::default();
+        let mut status = StatusLine::on(out.clone());
+        status.set_width(Some(20));
+        status.set("connecting");
+        status.set("connected");
+        status.clear();
+        let out = out.text();
+        let frames: Vec<&str> = out.split('\r').collect();
+        assert_eq!(
+            frames,
+            [
+                "",
+                "connecting          ",
+                "connected           ",
+                "                    "
+            ]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Progress of a futures `Stream`, see `ProgressStream`.
+
+use futures::Stream;
+use pb::ProgressBar;
+use std::io::Write;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Adds `progress_with` to streams, the `Stream` version of
+/// `ProgressIterator`. Requires the `futures` feature.
+///
+/// # Examples
+///
+/// ```ignore
+/// use pbr::{ProgressBar, ProgressStream};
+///
+/// let pb = ProgressBar::new(urls.len() as u64);
+/// let mut pages = stream::iter(urls).then(fetch).progress_with(pb);
+/// while let Some(page) = pages.next().await {
+///     // ...
+/// }
+/// ```
+pub trait ProgressStream: Stream + Sized {
+    /// Wrap the stream in a `PbStream`, which increments the bar for every
+    /// item, and finishes it when the stream ends.
+    fn progress_with<T: Write>(self, pb: ProgressBar<T>) -> PbStream<T, Self> {
+        PbStream {
+            stream: self,
+            progress_bar: Some(pb),
+        }
+    }
+}
+
+impl<S: Stream> ProgressStream for S {}
+
+/// PbStream is a stream with a bar, see `ProgressStream`.
+#[derive(Debug)]
+pub struct PbStream<T: Write, S> {
+    stream: S,
+    // taken when the stream ends
+    progress_bar: Option<ProgressBar<T>>,
+}
+
+impl<T: Write, S> PbStream<T, S> {
+    /// Returns the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<T, S> Stream for PbStream<T, S>
+where
+    T: Write + Unpin,
+    S: Stream + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.stream).poll_next(cx);
+        match res {
+            Poll::Ready(Some(_)) => {
+                if let Some(ref mut pb) = this.progress_bar {
+                    pb.inc();
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(pb) = this.progress_bar.take() {
+                    pb.finish();
+                }
+            }
+            Poll::Pending => {}
+        }
+        res
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream::iter;
+    use futures::Stream;
+    use pb::ProgressBar;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+    use stream::ProgressStream;
+
+    #[test]
+    fn progress_with() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let pb = ProgressBar::on(Vec::new(), 2);
+        let mut s = iter(vec!["a", "b"]).progress_with(pb);
+        let mut poll = || Pin::new(&mut s).poll_next(&mut cx);
+        assert_eq!(poll(), Poll::Ready(Some("a")));
+        assert_eq!(poll(), Poll::Ready(Some("b")));
+        assert_eq!(poll(), Poll::Ready(None));
+        assert!(s.progress_bar.is_none(), "should finish the bar");
+    }
+}
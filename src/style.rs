@@ -0,0 +1,296 @@
+use pb::{parse_format, FinishBehavior, FormatError, ProgressBar, Units};
+use std::io::Write;
+use std::time::Duration;
+
+/// Style holds the appearance settings of a `ProgressBar`, to configure
+/// several bars the same way, see `MultiBar::set_default_bar_style`.
+///
+/// Settings left unset keep the bar defaults.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pbr::{Color, MultiBar, Paint, Style, Units};
+///
+/// let mut style = Style::new();
+/// style.format("╢▌▌░╟").unwrap();
+/// style.set_units(Units::Bytes);
+/// style.show_speed = false;
+/// style.paint_bar(Paint::new().fg(Color::Green));
+///
+/// let mut mb = MultiBar::new();
+/// mb.set_default_bar_style(style);
+/// let p1 = mb.create_bar(100);
+/// let mut p2 = mb.create_bar(100);
+/// // per-bar override
+/// p2.show_speed = true;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Style {
+    format: Option<String>,
+    tick_format: Option<String>,
+    units: Option<Units>,
+    separator: Option<char>,
+    width: Option<usize>,
+    max_refresh_rate: Option<Duration>,
+    finish_behavior: Option<FinishBehavior>,
+    bar_paint: Option<Paint>,
+    percent_paint: Option<Paint>,
+    message_paint: Option<Paint>,
+    verb_paint: Option<Paint>,
+    pub show_bar: bool,
+    pub show_speed: bool,
+    pub show_percent: bool,
+    pub show_counter: bool,
+    pub show_time_left: bool,
+    pub show_tick: bool,
+    pub show_message: bool,
+}
+
+impl Style {
+    /// Create a new Style with the default settings of a `ProgressBar`.
+    pub fn new() -> Style {
+        Style {
+            format: None,
+            tick_format: None,
+            units: None,
+            separator: None,
+            width: None,
+            max_refresh_rate: None,
+            finish_behavior: None,
+            bar_paint: None,
+            percent_paint: None,
+            message_paint: None,
+            verb_paint: None,
+            show_bar: true,
+            show_speed: true,
+            show_percent: true,
+            show_counter: true,
+            show_time_left: true,
+            show_tick: false,
+            show_message: true,
+        }
+    }
+
+    /// Set the bar format, see `ProgressBar::format`.
+    pub fn format(&mut self, fmt: &str) -> Result<(), FormatError> {
+        parse_format(fmt)?;
+        self.format = Some(fmt.to_owned());
+        Ok(())
+    }
+
+    /// Set the tick format, see `ProgressBar::tick_format`.
+    pub fn tick_format(&mut self, tick_fmt: &str) {
+        self.tick_format = Some(tick_fmt.to_owned());
+        self.show_tick = true;
+    }
+
+    /// Set units, see `ProgressBar::set_units`.
+    pub fn set_units(&mut self, u: Units) {
+        self.units = Some(u);
+    }
+
+    /// Set a separator between groups of three digits in counts, see
+    /// `ProgressBar::set_thousands_separator`, e.g. to keep the columns of
+    /// a `MultiBar` readable.
+    pub fn set_thousands_separator(&mut self, sep: Option<char>) {
+        self.separator = sep;
+    }
+
+    /// Set width, see `ProgressBar::set_width`.
+    pub fn set_width(&mut self, w: Option<usize>) {
+        self.width = w;
+    }
+
+    /// Set max refresh rate, see `ProgressBar::set_max_refresh_rate`.
+    pub fn set_max_refresh_rate(&mut self, w: Option<Duration>) {
+        self.max_refresh_rate = w;
+    }
+
+    /// Set what is left of the bar when it finishes, see
+    /// `ProgressBar::set_finish_behavior`.
+    pub fn set_finish_behavior(&mut self, behavior: FinishBehavior) {
+        self.finish_behavior = Some(behavior);
+    }
+
+    /// Paint the filled part of the bar.
+    ///
+    /// Colors and attributes are left out when `NO_COLOR` is set, when the
+    /// writer is not a terminal, see `ProgressBar::set_colors`, or in
+    /// milestone-line mode, see `ProgressBar::assume_pager`.
+    pub fn paint_bar(&mut self, paint: Paint) {
+        self.bar_paint = Some(paint);
+    }
+
+    /// Paint the percent, see `paint_bar`.
+    pub fn paint_percent(&mut self, paint: Paint) {
+        self.percent_paint = Some(paint);
+    }
+
+    /// Paint the message, see `paint_bar`.
+    pub fn paint_message(&mut self, paint: Paint) {
+        self.message_paint = Some(paint);
+    }
+
+    /// Paint the verb, see `paint_bar` and `ProgressBar::set_verb`, default
+    /// is bold green.
+    pub fn paint_verb(&mut self, paint: Paint) {
+        self.verb_paint = Some(paint);
+    }
+
+    // apply configures `pb` with this style.
+    pub(crate) fn apply<T: Write>(&self, pb: &mut ProgressBar<T>) {
+        if let Some(ref fmt) = self.format {
+            pb.format(fmt);
+        }
+        if let Some(ref tick_fmt) = self.tick_format {
+            pb.tick_format(tick_fmt);
+        }
+        if let Some(u) = self.units {
+            pb.set_units(u);
+        }
+        if self.separator.is_some() {
+            pb.set_thousands_separator(self.separator);
+        }
+        pb.set_width(self.width);
+        pb.set_max_refresh_rate(self.max_refresh_rate);
+        if let Some(ref behavior) = self.finish_behavior {
+            pb.set_finish_behavior(behavior.clone());
+        }
+        if self.bar_paint.is_some() {
+            pb.bar_paint = self.bar_paint;
+        }
+        if self.percent_paint.is_some() {
+            pb.percent_paint = self.percent_paint;
+        }
+        if self.message_paint.is_some() {
+            pb.message_paint = self.message_paint;
+        }
+        if self.verb_paint.is_some() {
+            pb.verb_paint = self.verb_paint;
+        }
+        pb.show_bar = self.show_bar;
+        pb.show_speed = self.show_speed;
+        pb.show_percent = self.show_percent;
+        pb.show_counter = self.show_counter;
+        pb.show_time_left = self.show_time_left;
+        pb.show_tick = self.show_tick;
+        pb.show_message = self.show_message;
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style::new()
+    }
+}
+
+/// Color of the terminal palette, one of the 8 basic colors or an index in
+/// the 256 colors palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Fixed(u8),
+}
+
+impl Color {
+    // code returns the SGR parameter of the color, from the code of black
+    // (30 for the foreground, 40 for the background).
+    fn code(self, base: u8) -> String {
+        let basic = match self {
+            Color::Black => 0,
+            Color::Red => 1,
+            Color::Green => 2,
+            Color::Yellow => 3,
+            Color::Blue => 4,
+            Color::Magenta => 5,
+            Color::Cyan => 6,
+            Color::White => 7,
+            Color::Fixed(n) => return format!("{};5;{}", base + 8, n),
+        };
+        (base + basic).to_string()
+    }
+}
+
+/// Paint is the colors and attributes of a part of a bar, see
+/// `Style::paint_bar`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pbr::{Color, Paint};
+///
+/// let paint = Paint::new().fg(Color::Fixed(208)).bold();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Paint {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    dim: bool,
+}
+
+impl Paint {
+    pub fn new() -> Paint {
+        Paint::default()
+    }
+
+    /// Set the foreground color.
+    pub fn fg(mut self, color: Color) -> Paint {
+        self.fg = Some(color);
+        self
+    }
+
+    /// Set the background color.
+    pub fn bg(mut self, color: Color) -> Paint {
+        self.bg = Some(color);
+        self
+    }
+
+    pub fn bold(mut self) -> Paint {
+        self.bold = true;
+        self
+    }
+
+    pub fn dim(mut self) -> Paint {
+        self.dim = true;
+        self
+    }
+
+    // codes returns the escape sequence setting this paint, empty for the
+    // default paint.
+    pub(crate) fn codes(&self) -> String {
+        let mut params = Vec::new();
+        if self.bold {
+            params.push("1".to_owned());
+        }
+        if self.dim {
+            params.push("2".to_owned());
+        }
+        if let Some(c) = self.fg {
+            params.push(c.code(30));
+        }
+        if let Some(c) = self.bg {
+            params.push(c.code(40));
+        }
+        if params.is_empty() {
+            return String::new();
+        }
+        format!("\x1B[{}m", params.join(";"))
+    }
+
+    // paint returns `s` in this paint, the attributes are reset after it.
+    pub(crate) fn paint(&self, s: &str) -> String {
+        match self.codes() {
+            ref c if c.is_empty() || s.is_empty() => s.to_owned(),
+            c => c + s + "\x1B[0m",
+        }
+    }
+}
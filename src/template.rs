@@ -0,0 +1,304 @@
+//! Line templates, see `ProgressBar::set_template`.
+//!
+//! A template is text with fields, `{name}`, replaced by their value, and
+//! conditional sections, `{?name:body}`, where `body` is only rendered if
+//! the field is known. `{{` and `}}` are literal braces, outside of a
+//! conditional section. The bar can be given a width, `{bar:40}`.
+//!
+//! Templates may come from users, e.g. a config file: a malformed one is an
+//! error, never a panic, and the widths are clamped so that the line stays
+//! bounded.
+
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+// Fields a template can refer to.
+pub(crate) const FIELDS: [&str; 19] = [
+    "verb",
+    "msg",
+    "prefix",
+    "pos",
+    "total",
+    "bytes",
+    "total_bytes",
+    "percent",
+    "eta",
+    "eta_avg",
+    "speed",
+    "trend",
+    "elapsed",
+    "ttfb",
+    "tick",
+    "bar",
+    "finished",
+    "skipped",
+    "inflight",
+];
+
+// Widest fixed bar, larger widths are clamped.
+pub(crate) const MAX_BAR_WIDTH: usize = 1000;
+// Deepest nesting of conditional sections.
+const MAX_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Field(String),
+    Bar(Option<usize>),
+    Cond(String, Vec<Piece>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Template {
+    pieces: Vec<Piece>,
+}
+
+// Chunk is a part of an expanded template, a bar without a width is sized
+// afterwards to fill the line.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Chunk {
+    Text(String),
+    Bar(Option<usize>),
+}
+
+impl Template {
+    pub(crate) fn parse(s: &str) -> Result<Template, TemplateError> {
+        let mut chars = s.char_indices().peekable();
+        let pieces = parse_pieces(&mut chars, None, 0)?;
+        Ok(Template { pieces })
+    }
+
+    // expand replaces the fields with their value, `None` for an unknown
+    // field. The bars are left as `Chunk::Bar`.
+    pub(crate) fn expand<F>(&self, value: &mut F) -> Vec<Chunk>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let mut out = Vec::new();
+        expand_pieces(&self.pieces, value, &mut out);
+        out
+    }
+}
+
+// parse_pieces parses text and tags, up to the `}` closing the conditional
+// section opened at `open`, `depth` sections deep.
+fn parse_pieces(
+    chars: &mut Peekable<CharIndices>,
+    open: Option<usize>,
+    depth: usize,
+) -> Result<Vec<Piece>, TemplateError> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    loop {
+        let (i, c) = match chars.next() {
+            Some(next) => next,
+            None => match open {
+                Some(at) => return Err(TemplateError::Unclosed(at)),
+                None => break,
+            },
+        };
+        match c {
+            '{' if chars.peek().map(|&(_, c)| c) == Some('{') => {
+                chars.next();
+                text.push('{');
+            }
+            '{' => {
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(text.split_off(0)));
+                }
+                pieces.push(parse_tag(chars, i, depth)?);
+            }
+            '}' if open.is_some() => break,
+            '}' if chars.peek().map(|&(_, c)| c) == Some('}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => return Err(TemplateError::Unmatched(i)),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+// parse_tag parses a field or a conditional section, after its `{` at `at`.
+fn parse_tag(
+    chars: &mut Peekable<CharIndices>,
+    at: usize,
+    depth: usize,
+) -> Result<Piece, TemplateError> {
+    let cond = chars.peek().map(|&(_, c)| c) == Some('?');
+    if cond {
+        chars.next();
+    }
+    let mut name = String::new();
+    let mut width = None;
+    loop {
+        match chars.next() {
+            Some((_, '}')) if !cond => break,
+            Some((_, ':')) if cond => break,
+            Some((i, ':')) if name == "bar" => {
+                width = Some(parse_width(chars, i)?);
+                break;
+            }
+            Some((_, c)) if c.is_ascii_alphanumeric() || c == '_' => name.push(c),
+            Some((i, _)) => return Err(TemplateError::Unexpected(i)),
+            None => return Err(TemplateError::Unclosed(at)),
+        }
+    }
+    if !FIELDS.contains(&name.as_str()) {
+        return Err(TemplateError::UnknownField(name));
+    }
+    if cond {
+        if depth >= MAX_DEPTH {
+            return Err(TemplateError::Nested(at));
+        }
+        let body = parse_pieces(chars, Some(at), depth + 1)?;
+        Ok(Piece::Cond(name, body))
+    } else if name == "bar" {
+        Ok(Piece::Bar(width))
+    } else {
+        Ok(Piece::Field(name))
+    }
+}
+
+// parse_width parses the width of a bar and its closing `}`, after the `:`
+// at `at`, clamped to `MAX_BAR_WIDTH`.
+fn parse_width(chars: &mut Peekable<CharIndices>, at: usize) -> Result<usize, TemplateError> {
+    let mut width = None;
+    loop {
+        match chars.next() {
+            Some((_, '}')) => break,
+            Some((_, c)) if c.is_ascii_digit() => {
+                let digit = c as usize - '0' as usize;
+                let w = width.unwrap_or(0) * 10 + digit;
+                width = Some(w.min(MAX_BAR_WIDTH));
+            }
+            Some((i, _)) => return Err(TemplateError::Unexpected(i)),
+            None => return Err(TemplateError::Unclosed(at)),
+        }
+    }
+    width.ok_or(TemplateError::Unexpected(at))
+}
+
+fn expand_pieces<F>(pieces: &[Piece], value: &mut F, out: &mut Vec<Chunk>)
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    for piece in pieces {
+        match *piece {
+            Piece::Text(ref s) => out.push(Chunk::Text(s.clone())),
+            Piece::Bar(width) => out.push(Chunk::Bar(width)),
+            Piece::Field(ref name) => {
+                if let Some(v) = value(name) {
+                    out.push(Chunk::Text(v));
+                }
+            }
+            Piece::Cond(ref name, ref body) => {
+                if name == "bar" || value(name).is_some() {
+                    expand_pieces(body, value, out);
+                }
+            }
+        }
+    }
+}
+
+/// TemplateError is returned by `ProgressBar::set_template` for an invalid
+/// template. Positions are byte offsets in the template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// The `{` at this position is never closed.
+    Unclosed(usize),
+    /// The `}` at this position has no matching `{`, use `}}` for a brace.
+    Unmatched(usize),
+    /// Unexpected character in a field name at this position.
+    Unexpected(usize),
+    /// There is no field with this name.
+    UnknownField(String),
+    /// The conditional section at this position is nested too deep.
+    Nested(usize),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TemplateError::Unclosed(i) => write!(f, "unclosed `{{` at {}", i),
+            TemplateError::Unmatched(i) => write!(f, "unmatched `}}` at {}", i),
+            TemplateError::Unexpected(i) => write!(f, "unexpected character at {}", i),
+            TemplateError::UnknownField(ref name) => write!(f, "unknown field `{}`", name),
+            TemplateError::Nested(i) => write!(f, "conditional at {} is nested too deep", i),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+#[cfg(test)]
+mod test {
+    use template::{Chunk, Template, TemplateError};
+
+    fn render(t: &str, eta: Option<&str>) -> String {
+        let t = Template::parse(t).unwrap();
+        let mut value = |name: &str| match name {
+            "eta" => eta.map(str::to_owned),
+            "pos" => Some("3".to_owned()),
+            _ => None,
+        };
+        t.expand(&mut value)
+            .into_iter()
+            .map(|c| match c {
+                Chunk::Text(s) => s,
+                Chunk::Bar(None) => "[bar]".to_owned(),
+                Chunk::Bar(Some(w)) => format!("[bar:{}]", w),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn conditionals() {
+        let t = "{pos} {bar}{?eta: eta {eta}}{?finished:done!}";
+        assert_eq!(render(t, Some("5s")), "3 [bar] eta 5s");
+        assert_eq!(render(t, None), "3 [bar]");
+        assert_eq!(render("{{{pos}}} {msg}.", None), "{3} .");
+        assert_eq!(render("{bar:40} {?bar:{bar}}", None), "[bar:40] [bar]");
+    }
+
+    #[test]
+    fn clamped() {
+        assert_eq!(render("{bar:99999999999999999999999}", None), "[bar:1000]");
+        let t = "{?eta:".repeat(8) + &"}".repeat(8);
+        assert_eq!(render(&t, None), "");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Template::parse("{pos"), Err(TemplateError::Unclosed(0)));
+        assert_eq!(
+            Template::parse("a {?eta: b"),
+            Err(TemplateError::Unclosed(2))
+        );
+        assert_eq!(Template::parse("a }"), Err(TemplateError::Unmatched(2)));
+        assert_eq!(Template::parse("{p s}"), Err(TemplateError::Unexpected(2)));
+        assert_eq!(
+            Template::parse("{pos:4}"),
+            Err(TemplateError::Unexpected(4))
+        );
+        assert_eq!(
+            Template::parse("{bar:4x}"),
+            Err(TemplateError::Unexpected(6))
+        );
+        assert_eq!(Template::parse("{bar:}"), Err(TemplateError::Unexpected(4)));
+        assert_eq!(
+            Template::parse("{?eta:".repeat(9).as_str()),
+            Err(TemplateError::Nested(48))
+        );
+        assert_eq!(
+            Template::parse("{nope}"),
+            Err(TemplateError::UnknownField("nope".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,715 @@
+//! Simulated terminals, to test a progress UI against terminals of various
+//! sizes without a real one.
+//!
+//! A `Terminal` interprets the output of the bars like a terminal would:
+//! it moves the cursor, wraps the lines wider than the screen, and scrolls.
+//! A `Script` replays a sequence of updates of a `MultiBar` on a
+//! `Terminal`, and snapshots the screens.
+//!
+//! # Examples
+//!
+//! ```
+//! use pbr::testing::Script;
+//!
+//! let script = Script::new()
+//!     .println("downloads:")
+//!     .bar(10)
+//!     .inc(0, 5)
+//!     .snapshot()
+//!     .resize(24, 4)
+//!     .snapshot()
+//!     .finish(0);
+//! let screens = script.run(40, 4);
+//! assert_eq!(screens[0], "downloads:\n5 / 10 [==========>----------] 50.00 %");
+//! assert_eq!(screens[1], "downloads:\n5 / 10 [==>--] 50.00 %");
+//! // a finished bar is cleared
+//! assert_eq!(screens[2], "downloads:");
+//! ```
+
+use multi::MultiBar;
+use pb::ProgressBar;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Write};
+use std::mem;
+use std::str::from_utf8;
+use std::sync::{Arc, Mutex};
+use style::Style;
+use tty::{Height, Width};
+use unicode_width::UnicodeWidthChar;
+
+/// Terminal is a simulated terminal of a given size, to use as the writer
+/// of a bar. The clones of a `Terminal` share the same screen, so one can
+/// be given to a bar and the other kept to look at the screen.
+///
+/// The cursor moves, line erasing and wrapping of a terminal are
+/// simulated, colors and other attributes are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use pbr::testing::Terminal;
+/// use pbr::ProgressBar;
+///
+/// let term = Terminal::new(30, 5);
+/// let mut pb = ProgressBar::on(term.clone(), 10);
+/// pb.set_width(Some(30));
+/// pb.show_speed = false;
+/// pb.show_time_left = false;
+/// pb.add(4);
+/// pb.finish_print("done");
+/// assert_eq!(term.screen(), "done");
+/// ```
+#[derive(Clone)]
+pub struct Terminal {
+    screen: Arc<Mutex<Screen>>,
+}
+
+impl Terminal {
+    pub fn new(width: u16, height: u16) -> Terminal {
+        Terminal {
+            screen: Arc::new(Mutex::new(Screen::new(width, height))),
+        }
+    }
+
+    /// multibar creates a `MultiBar` drawing on this terminal, and
+    /// wrapping and paginating its lines for its size.
+    ///
+    /// Its bars are as wide as the terminal, through the default bar style,
+    /// see `MultiBar::set_default_bar_style`. They don't follow a resize,
+    /// set their width again with `ProgressBar::set_width`.
+    pub fn multibar(&self) -> MultiBar<Terminal> {
+        let mut mb = MultiBar::on(self.clone());
+        let mut style = Style::new();
+        style.set_width(Some(self.size().0 as usize));
+        mb.set_default_bar_style(style);
+        let term = self.clone();
+        mb.set_terminal_size(Box::new(move || {
+            let (w, h) = term.size();
+            Some((Width(w), Height(h)))
+        }));
+        mb
+    }
+
+    /// Get the size of the terminal, in columns and rows.
+    pub fn size(&self) -> (u16, u16) {
+        let screen = self.lock();
+        (screen.width as u16, screen.height as u16)
+    }
+
+    /// resize changes the size of the terminal. The lines are cut, not
+    /// reflowed, and the rows above the cursor scroll away when the
+    /// terminal gets shorter.
+    pub fn resize(&self, width: u16, height: u16) {
+        self.lock().resize(width, height);
+    }
+
+    /// rows returns the rows of the screen, without trailing spaces.
+    pub fn rows(&self) -> Vec<String> {
+        self.lock().rows.iter().map(|r| row_text(r)).collect()
+    }
+
+    /// screen returns the rows of the screen joined with newlines, without
+    /// the trailing blank rows.
+    pub fn screen(&self) -> String {
+        let mut rows = self.rows();
+        while rows.last().is_some_and(|r| r.is_empty()) {
+            rows.pop();
+        }
+        rows.join("\n")
+    }
+
+    /// scrollback returns the rows that scrolled off the top of the screen,
+    /// the oldest first.
+    pub fn scrollback(&self) -> Vec<String> {
+        self.lock().scrollback.clone()
+    }
+
+    /// cursor returns the position of the cursor, as a row and a column.
+    pub fn cursor(&self) -> (usize, usize) {
+        let screen = self.lock();
+        (screen.row, screen.col.min(screen.width - 1))
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<'_, Screen> {
+        self.screen.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Terminal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (width, height) = self.size();
+        f.debug_struct("Terminal")
+            .field("width", &width)
+            .field("height", &height)
+            .finish()
+    }
+}
+
+// The second cell of a double width character.
+const WIDE: char = '\0';
+
+fn row_text(row: &[char]) -> String {
+    let s: String = row.iter().filter(|&&c| c != WIDE).collect();
+    s.trim_end().to_owned()
+}
+
+// State of the escape sequence parser.
+enum Escape {
+    None,
+    // after ESC
+    Start,
+    // a control sequence, ESC [ with its parameters so far
+    Csi(String),
+    // an operating system command, ESC ], ignored until BEL or ST
+    Osc,
+}
+
+// Screen is the state of a simulated terminal.
+struct Screen {
+    width: usize,
+    height: usize,
+    rows: Vec<Vec<char>>,
+    scrollback: Vec<String>,
+    row: usize,
+    // `width` after writing the last column, the next character wraps
+    col: usize,
+    saved: (usize, usize),
+    escape: Escape,
+    // the start of a character split between two writes
+    partial: Vec<u8>,
+}
+
+impl Screen {
+    fn new(width: u16, height: u16) -> Screen {
+        let (width, height) = (width.max(1) as usize, height.max(1) as usize);
+        Screen {
+            width,
+            height,
+            rows: vec![vec![' '; width]; height],
+            scrollback: Vec::new(),
+            row: 0,
+            col: 0,
+            saved: (0, 0),
+            escape: Escape::None,
+            partial: Vec::new(),
+        }
+    }
+
+    fn write(&mut self, buf: &[u8]) {
+        let mut bytes = mem::take(&mut self.partial);
+        bytes.extend_from_slice(buf);
+        let mut rest = &bytes[..];
+        while !rest.is_empty() {
+            let (valid, invalid) = match from_utf8(rest) {
+                Ok(s) => (s, &[][..]),
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    (from_utf8(valid).unwrap(), invalid)
+                }
+            };
+            for c in valid.chars() {
+                self.put(c);
+            }
+            rest = match invalid.len() {
+                0 => break,
+                // an incomplete character, completed by the next write
+                n if n < 4 && from_utf8(invalid).unwrap_err().error_len().is_none() => {
+                    self.partial = invalid.to_vec();
+                    break;
+                }
+                _ => {
+                    self.put(char::REPLACEMENT_CHARACTER);
+                    &invalid[1..]
+                }
+            };
+        }
+    }
+
+    fn put(&mut self, c: char) {
+        match mem::replace(&mut self.escape, Escape::None) {
+            Escape::Start => match c {
+                '[' => self.escape = Escape::Csi(String::new()),
+                ']' => self.escape = Escape::Osc,
+                '7' => self.saved = (self.row, self.col),
+                '8' => {
+                    let (row, col) = self.saved;
+                    self.row = row.min(self.height - 1);
+                    self.col = col.min(self.width - 1);
+                }
+                _ => {}
+            },
+            Escape::Csi(mut params) => match c {
+                '\x30'..='\x3F' | ' ' => {
+                    params.push(c);
+                    self.escape = Escape::Csi(params);
+                }
+                _ => self.csi(&params, c),
+            },
+            Escape::Osc => match c {
+                '\x07' | '\\' => {}
+                _ => self.escape = Escape::Osc,
+            },
+            Escape::None => match c {
+                '\x1B' => self.escape = Escape::Start,
+                '\r' => self.col = 0,
+                '\n' => {
+                    self.col = 0;
+                    self.line_feed();
+                }
+                '\x08' => self.col = self.col.min(self.width - 1).saturating_sub(1),
+                '\t' => self.col = ((self.col / 8 + 1) * 8).min(self.width - 1),
+                c if c.is_control() => {}
+                c => self.print(c),
+            },
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        let w = match c.width() {
+            Some(w) if w > 0 => w.min(self.width),
+            _ => return,
+        };
+        if self.col + w > self.width {
+            self.col = 0;
+            self.line_feed();
+        }
+        let row = &mut self.rows[self.row];
+        row[self.col] = c;
+        if w == 2 {
+            row[self.col + 1] = WIDE;
+        }
+        self.col += w;
+    }
+
+    fn line_feed(&mut self) {
+        if self.row + 1 < self.height {
+            self.row += 1;
+            return;
+        }
+        let top = self.rows.remove(0);
+        self.scrollback.push(row_text(&top));
+        self.rows.push(vec![' '; self.width]);
+    }
+
+    // csi runs the control sequence ending with `end`.
+    fn csi(&mut self, params: &str, end: char) {
+        let args: Vec<usize> = params.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+        let arg = |i: usize| args.get(i).cloned().unwrap_or(0);
+        let n = arg(0).max(1);
+        let col = self.col.min(self.width - 1);
+        match end {
+            'A' => self.row = self.row.saturating_sub(n),
+            'B' => self.row = (self.row + n).min(self.height - 1),
+            'C' => self.col = (col + n).min(self.width - 1),
+            'D' => self.col = col.saturating_sub(n),
+            'G' => self.col = (n - 1).min(self.width - 1),
+            'H' | 'f' => {
+                self.row = (n - 1).min(self.height - 1);
+                self.col = (arg(1).max(1) - 1).min(self.width - 1);
+            }
+            'K' => {
+                let row = &mut self.rows[self.row];
+                let range = match arg(0) {
+                    0 => col..row.len(),
+                    1 => 0..col + 1,
+                    _ => 0..row.len(),
+                };
+                for cell in &mut row[range] {
+                    *cell = ' ';
+                }
+            }
+            'J' => {
+                let (from, to) = match arg(0) {
+                    0 => (self.row + 1, self.height),
+                    1 => (0, self.row),
+                    _ => (0, self.height),
+                };
+                for row in &mut self.rows[from..to] {
+                    *row = vec![' '; self.width];
+                }
+                if arg(0) < 2 {
+                    self.csi(params, 'K');
+                }
+            }
+            _ => {}
+        }
+        if matches!(end, 'A' | 'B') {
+            self.col = col;
+        }
+    }
+
+    fn resize(&mut self, width: u16, height: u16) {
+        let (width, height) = (width.max(1) as usize, height.max(1) as usize);
+        for row in &mut self.rows {
+            row.resize(width, ' ');
+            // half of a wide character is cut
+            if row[width - 1].width() == Some(2) {
+                row[width - 1] = ' ';
+            }
+        }
+        // rows below the cursor go first, then the top ones scroll away
+        while self.rows.len() > height {
+            if self.row + 1 < self.rows.len() {
+                self.rows.pop();
+            } else {
+                let top = self.rows.remove(0);
+                self.scrollback.push(row_text(&top));
+                self.row -= 1;
+            }
+        }
+        while self.rows.len() < height {
+            self.rows.push(vec![' '; width]);
+        }
+        self.width = width;
+        self.height = height;
+        self.col = self.col.min(width);
+    }
+}
+
+// A step of a `Script`.
+enum Step {
+    Println(Cow<'static, str>),
+    Bar(u64),
+    Inc(usize, u64),
+    Message(usize, Cow<'static, str>),
+    Finish(usize),
+    Abandon(usize),
+    PrintAbove(Cow<'static, str>),
+    Resize(u16, u16),
+    Snapshot,
+}
+
+/// Script is a sequence of updates of a `MultiBar`, replayed on simulated
+/// terminals to snapshot the screens, e.g. to compare them to the expected
+/// ones in a test.
+///
+/// Bars are numbered in the order they are created by the script. Their
+/// width is the width of the terminal, and they don't show the speed and
+/// time left, which would change from one run to the next.
+///
+/// # Examples
+///
+/// ```
+/// use pbr::testing::Script;
+///
+/// let script = Script::new()
+///     .bar(3)
+///     .bar(3)
+///     .inc(1, 3)
+///     .finish(1)
+///     .print_above("second done");
+/// println!("{}", script.report(&[(80, 24), (20, 2)]));
+/// ```
+#[derive(Default)]
+pub struct Script {
+    steps: Vec<Step>,
+}
+
+impl Script {
+    pub fn new() -> Script {
+        Script::default()
+    }
+
+    /// Add a text line after the last line, see `MultiBar::println`.
+    pub fn println<S: Into<Cow<'static, str>>>(mut self, s: S) -> Script {
+        self.steps.push(Step::Println(s.into()));
+        self
+    }
+
+    /// Add a bar of `total` units after the last line.
+    pub fn bar(mut self, total: u64) -> Script {
+        self.steps.push(Step::Bar(total));
+        self
+    }
+
+    /// Add `n` units done to the bar number `bar`.
+    pub fn inc(mut self, bar: usize, n: u64) -> Script {
+        self.steps.push(Step::Inc(bar, n));
+        self
+    }
+
+    /// Set the message of the bar number `bar`.
+    pub fn message<S: Into<Cow<'static, str>>>(mut self, bar: usize, s: S) -> Script {
+        self.steps.push(Step::Message(bar, s.into()));
+        self
+    }
+
+    /// Finish the bar number `bar`.
+    pub fn finish(mut self, bar: usize) -> Script {
+        self.steps.push(Step::Finish(bar));
+        self
+    }
+
+    /// Drop the bar number `bar` without finishing it.
+    pub fn abandon(mut self, bar: usize) -> Script {
+        self.steps.push(Step::Abandon(bar));
+        self
+    }
+
+    /// Print `s` above the lines, see `MultiBarHandle::println_above`.
+    pub fn print_above<S: Into<Cow<'static, str>>>(mut self, s: S) -> Script {
+        self.steps.push(Step::PrintAbove(s.into()));
+        self
+    }
+
+    /// Resize the terminal, the bars are fitted to the new width.
+    pub fn resize(mut self, width: u16, height: u16) -> Script {
+        self.steps.push(Step::Resize(width, height));
+        self
+    }
+
+    /// Snapshot the screen.
+    pub fn snapshot(mut self) -> Script {
+        self.steps.push(Step::Snapshot);
+        self
+    }
+
+    /// run replays the script on a terminal of `width` columns and
+    /// `height` rows, and returns the screens snapshotted, the screen at
+    /// the end of the script last. A bar number that doesn't exist is
+    /// ignored.
+    pub fn run(&self, width: u16, height: u16) -> Vec<String> {
+        let term = Terminal::new(width, height);
+        let mut pump = term.multibar().into_pump();
+        let mut bars: Vec<Option<ProgressBar<_>>> = Vec::new();
+        let mut screens = Vec::new();
+        for step in &self.steps {
+            match *step {
+                Step::Println(ref s) => pump.println(s),
+                Step::Bar(total) => {
+                    let mut pb = pump.handle().create_bar(total);
+                    pb.set_width(Some(term.size().0 as usize));
+                    pb.show_speed = false;
+                    pb.show_time_left = false;
+                    pb.add(0);
+                    bars.push(Some(pb));
+                }
+                Step::Inc(i, n) => {
+                    if let Some(&mut Some(ref mut pb)) = bars.get_mut(i) {
+                        pb.add(n);
+                    }
+                }
+                Step::Message(i, ref s) => {
+                    if let Some(&mut Some(ref mut pb)) = bars.get_mut(i) {
+                        pb.message(s.clone());
+                        pb.add(0);
+                    }
+                }
+                Step::Finish(i) => {
+                    if let Some(pb) = bars.get_mut(i).and_then(Option::take) {
+                        pb.finish();
+                    }
+                }
+                Step::Abandon(i) => {
+                    drop(bars.get_mut(i).and_then(Option::take));
+                }
+                Step::PrintAbove(ref s) => pump.handle().println_above(s.clone()),
+                Step::Resize(w, h) => {
+                    pump.step();
+                    term.resize(w, h);
+                    // the lines are drawn again once all the bars fit
+                    pump.handle().pause();
+                    for pb in bars.iter_mut().flatten() {
+                        pb.set_width(Some(w as usize));
+                        pb.add(0);
+                    }
+                    pump.handle().resized();
+                    pump.handle().resume();
+                }
+                Step::Snapshot => {
+                    pump.step();
+                    screens.push(term.screen());
+                }
+            }
+        }
+        pump.step();
+        screens.push(term.screen());
+        screens
+    }
+
+    /// report runs the script on terminals of each of the `sizes`, as
+    /// columns and rows, and returns the screens one after the other under
+    /// a header, e.g. to compare them to a snapshot file.
+    pub fn report(&self, sizes: &[(u16, u16)]) -> String {
+        let mut out = String::new();
+        for &(w, h) in sizes {
+            for (i, screen) in self.run(w, h).iter().enumerate() {
+                out += &format!("--- {}x{} #{} ---\n{}\n", w, h, i, screen);
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Script")
+            .field("steps", &self.steps.len())
+            .finish()
+    }
+}
+
+// Taken by the tests setting environment variables.
+#[cfg(test)]
+static ENV: Mutex<()> = Mutex::new(());
+
+// Env sets environment variables for a test, e.g. `NO_COLOR`, and restores
+// them once dropped. The other tests using it wait meanwhile.
+#[cfg(test)]
+pub(crate) struct Env {
+    saved: Vec<(&'static str, Option<::std::ffi::OsString>)>,
+    _lock: ::std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl Env {
+    pub(crate) fn lock() -> Env {
+        Env {
+            saved: Vec::new(),
+            _lock: ENV.lock().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    // colors sets the variables so that colors are drawn, or not.
+    pub(crate) fn colors(on: bool) -> Env {
+        let mut env = Env::lock();
+        env.set("TERM", Some("xterm"));
+        env.set("NO_COLOR", if on { None } else { Some("1") });
+        env
+    }
+
+    // set sets `key`, or removes it.
+    pub(crate) fn set(&mut self, key: &'static str, value: Option<&str>) {
+        if self.saved.iter().all(|&(k, _)| k != key) {
+            self.saved.push((key, ::std::env::var_os(key)));
+        }
+        match value {
+            Some(v) => ::std::env::set_var(key, v),
+            None => ::std::env::remove_var(key),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Drop for Env {
+    fn drop(&mut self) {
+        for (key, value) in self.saved.drain(..).rev() {
+            match value {
+                Some(v) => ::std::env::set_var(key, v),
+                None => ::std::env::remove_var(key),
+            }
+        }
+    }
+}
+
+// Output keeps the bytes written to it, escape sequences included, shared
+// by its clones: one is given to a bar, which may move it to another
+// thread, and the test reads the other.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct Output(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Output {
+    // text returns what was written so far.
+    pub(crate) fn text(&self) -> String {
+        String::from_utf8(self.lock().clone()).unwrap()
+    }
+
+    // lock holds the writes back until the guard is dropped, like a
+    // stalled terminal.
+    pub(crate) fn lock(&self) -> ::std::sync::MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+    use testing::{Script, Terminal};
+
+    #[test]
+    fn terminal() {
+        let mut term = Terminal::new(10, 3);
+        write!(term, "one\ntwo\x1B[1A\rONE\n\n\n").unwrap();
+        assert_eq!(term.rows(), ["two", "", ""]);
+        assert_eq!(term.scrollback(), ["ONE"]);
+        // a line wider than the terminal wraps
+        write!(term, "\r0123456789ab\x1B[K").unwrap();
+        assert_eq!(term.rows(), ["", "0123456789", "ab"]);
+        assert_eq!(term.cursor(), (2, 2));
+        // a character split between two writes
+        term.write_all(&"é".as_bytes()[..1]).unwrap();
+        term.write_all(&"é".as_bytes()[1..]).unwrap();
+        write!(term, "\x1B[38;5;208m界\x1B[0m").unwrap();
+        assert_eq!(term.rows()[2], "abé界");
+        term.resize(4, 2);
+        assert_eq!(term.rows(), ["0123", "abé"]);
+    }
+
+    #[test]
+    fn script() {
+        let script = Script::new()
+            .println("header")
+            .bar(10)
+            .bar(4)
+            .inc(0, 5)
+            .message(1, "b ")
+            .snapshot()
+            .print_above("log")
+            .resize(24, 10)
+            .inc(1, 4)
+            .finish(1)
+            .abandon(0);
+        assert_eq!(
+            script.report(&[(40, 10)]),
+            "--- 40x10 #0 ---\n\
+             header\n\
+             5 / 10 [==========>----------] 50.00 %\n\
+             b 0 / 4 [---------------------] 0.00 %\n\
+             --- 40x10 #1 ---\n\
+             log\n\
+             header\n\
+             5 / 10 [==>--] (aborted)\n"
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        let script = Script::new()
+            .println("a line too long for the terminal")
+            .bar(2);
+        for &(w, h) in &[(80, 24), (20, 5), (8, 3), (1, 1)] {
+            let screens = script.run(w, h);
+            let rows: Vec<&str> = screens[0].lines().collect();
+            assert!(rows.len() <= h as usize, "{}x{}: {:?}", w, h, rows);
+            for row in rows {
+                assert!(row.chars().count() <= w as usize, "{}x{}: {:?}", w, h, row);
+            }
+        }
+    }
+}
@@ -26,3 +26,11 @@ pub use self::windows::*;
 mod redox;
 #[cfg(target_os = "redox")]
 pub use self::redox::*;
+
+/// Returns `false` if colors are turned off, by `NO_COLOR` or a dumb
+/// terminal.
+pub fn colors_enabled() -> bool {
+    let no_color = ::std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let dumb = ::std::env::var("TERM").is_ok_and(|t| t == "dumb");
+    !no_color && !dumb
+}
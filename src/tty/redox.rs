@@ -11,3 +11,12 @@ pub fn terminal_size() -> Option<(Width, Height)> {
 pub fn move_cursor_up(n: usize) -> String {
     format!("{}", termion::cursor::Up(n as u16))
 }
+
+pub fn draw_at_row(row: usize, s: &str) -> String {
+    format!("{}{}{}{}{}",
+            termion::cursor::Save,
+            termion::cursor::Goto(1, row as u16 + 1),
+            termion::clear::CurrentLine,
+            s,
+            termion::cursor::Restore)
+}
@@ -38,7 +38,7 @@ pub fn terminal_size() -> Option<(Width, Height)> {
         } else {
             0
         };
-        (rows as u16, cols as u16)
+        (rows, cols)
     };
 
     if rows > 0 && cols > 0 {
@@ -53,6 +53,12 @@ pub fn move_cursor_up(n: usize) -> String {
     format!("\x1B[{}A", n)
 }
 
+/// Return string that draws `s` on the `row` (0-based) of the terminal,
+/// and puts the cursor back where it was.
+pub fn draw_at_row(row: usize, s: &str) -> String {
+    format!("\x1B7\x1B[{};1H\x1B[2K{}\x1B8", row + 1, s)
+}
+
 #[test]
 /// Compare with the output of `stty size`
 fn compare_with_stty() {
@@ -72,8 +78,8 @@ fn compare_with_stty() {
 
     // stdout is "rows cols"
     let mut data = stdout.split_whitespace();
-    let rows = u16::from_str_radix(data.next().unwrap(), 10).unwrap();
-    let cols = u16::from_str_radix(data.next().unwrap(), 10).unwrap();
+    let rows = data.next().unwrap().parse::<u16>().unwrap();
+    let cols = data.next().unwrap().parse::<u16>().unwrap();
     println!("{}", stdout);
     println!("{} {}", rows, cols);
 
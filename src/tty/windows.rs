@@ -34,6 +34,13 @@ pub fn move_cursor_up(n: usize) -> String {
     "".to_string()
 }
 
+/// Return string that draws `s` on the `row` (0-based) of the terminal,
+/// and puts the cursor back where it was; this relies on the console
+/// supporting ANSI escape sequences (Windows 10 and later).
+pub fn draw_at_row(row: usize, s: &str) -> String {
+    format!("\x1B7\x1B[{};1H\x1B[2K{}\x1B8", row + 1, s)
+}
+
 fn get_csbi() -> Option<(self::winapi::HANDLE, self::winapi::CONSOLE_SCREEN_BUFFER_INFO)> {
     use self::winapi::HANDLE;
     use self::kernel32::{GetStdHandle, GetConsoleScreenBufferInfo};
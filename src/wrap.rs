@@ -0,0 +1,209 @@
+//! Readers and writers that move a bar as the bytes go through, see
+//! `ProgressBar::wrap_read` and `ProgressBar::wrap_write`, and their async
+//! versions with the `tokio` feature.
+
+use pb::ProgressBar;
+use std::io::{self, Read, Write};
+
+/// ProgressReader is a reader that adds the bytes it reads to a bar, and
+/// draws it, see `ProgressBar::wrap_read`.
+#[derive(Debug)]
+pub struct ProgressReader<'a, R, T: Write + 'a> {
+    inner: R,
+    pb: &'a mut ProgressBar<T>,
+}
+
+impl<'a, R, T: Write> ProgressReader<'a, R, T> {
+    pub(crate) fn new(inner: R, pb: &'a mut ProgressBar<T>) -> ProgressReader<'a, R, T> {
+        ProgressReader { inner, pb }
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<'a, R: Read, T: Write> Read for ProgressReader<'a, R, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.pb.add(n as u64);
+        Ok(n)
+    }
+}
+
+/// ProgressWriter is a writer that adds the bytes it writes to a bar, and
+/// draws it, see `ProgressBar::wrap_write`.
+#[derive(Debug)]
+pub struct ProgressWriter<'a, W, T: Write + 'a> {
+    inner: W,
+    pb: &'a mut ProgressBar<T>,
+}
+
+impl<'a, W, T: Write> ProgressWriter<'a, W, T> {
+    pub(crate) fn new(inner: W, pb: &'a mut ProgressBar<T>) -> ProgressWriter<'a, W, T> {
+        ProgressWriter { inner, pb }
+    }
+
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<'a, W: Write, T: Write> Write for ProgressWriter<'a, W, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.pb.add(n as u64);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::async_io::{ProgressAsyncReader, ProgressAsyncWriter};
+
+#[cfg(feature = "tokio")]
+mod async_io {
+    use pb::ProgressBar;
+    use std::io::{self, Write};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    /// ProgressAsyncReader is an `AsyncRead` that adds the bytes it reads
+    /// to a bar, and draws it, see `ProgressBar::wrap_async_read`.
+    #[derive(Debug)]
+    pub struct ProgressAsyncReader<'a, R, T: Write + 'a> {
+        inner: R,
+        pb: &'a mut ProgressBar<T>,
+    }
+
+    impl<'a, R, T: Write> ProgressAsyncReader<'a, R, T> {
+        pub(crate) fn new(inner: R, pb: &'a mut ProgressBar<T>) -> ProgressAsyncReader<'a, R, T> {
+            ProgressAsyncReader { inner, pb }
+        }
+
+        /// Returns the wrapped reader.
+        pub fn into_inner(self) -> R {
+            self.inner
+        }
+    }
+
+    impl<'a, R: AsyncRead + Unpin, T: Write> AsyncRead for ProgressAsyncReader<'a, R, T> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            let before = buf.filled().len();
+            let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+            if let Poll::Ready(Ok(())) = res {
+                this.pb.add((buf.filled().len() - before) as u64);
+            }
+            res
+        }
+    }
+
+    /// ProgressAsyncWriter is an `AsyncWrite` that adds the bytes it writes
+    /// to a bar, and draws it, see `ProgressBar::wrap_async_write`.
+    #[derive(Debug)]
+    pub struct ProgressAsyncWriter<'a, W, T: Write + 'a> {
+        inner: W,
+        pb: &'a mut ProgressBar<T>,
+    }
+
+    impl<'a, W, T: Write> ProgressAsyncWriter<'a, W, T> {
+        pub(crate) fn new(inner: W, pb: &'a mut ProgressBar<T>) -> ProgressAsyncWriter<'a, W, T> {
+            ProgressAsyncWriter { inner, pb }
+        }
+
+        /// Returns the wrapped writer.
+        pub fn into_inner(self) -> W {
+            self.inner
+        }
+    }
+
+    impl<'a, W: AsyncWrite + Unpin, T: Write> AsyncWrite for ProgressAsyncWriter<'a, W, T> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+            if let Poll::Ready(Ok(n)) = res {
+                this.pb.add(n as u64);
+            }
+            res
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use pb::ProgressBar;
+        use std::io::sink;
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+        use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+        #[test]
+        fn poll() {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut pb = ProgressBar::on(sink(), 10);
+            {
+                let mut r = pb.wrap_async_read(&b"0123"[..]);
+                let mut buf = [0; 8];
+                let mut buf = ReadBuf::new(&mut buf);
+                let res = Pin::new(&mut r).poll_read(&mut cx, &mut buf);
+                assert!(matches!(res, Poll::Ready(Ok(()))));
+                assert_eq!(buf.filled(), b"0123");
+            }
+            let mut w = pb.wrap_async_write(Vec::new());
+            let res = Pin::new(&mut w).poll_write(&mut cx, b"456789");
+            assert!(matches!(res, Poll::Ready(Ok(6))));
+            assert_eq!(w.into_inner(), b"456789");
+            assert_eq!(pb.add(0), 10);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pb::ProgressBar;
+    use std::io::{copy, sink, Read, Write};
+
+    #[test]
+    fn read() {
+        let mut pb = ProgressBar::on(sink(), 10);
+        let mut r = pb.wrap_read(&b"0123456789"[..]);
+        let mut buf = [0; 4];
+        assert_eq!(r.read(&mut buf).unwrap(), 4);
+        assert_eq!(copy(&mut r, &mut sink()).unwrap(), 6);
+        assert!(r.into_inner().is_empty());
+        assert_eq!(pb.add(0), 10);
+    }
+
+    #[test]
+    fn write() {
+        let mut pb = ProgressBar::on(sink(), 10);
+        let mut w = pb.wrap_write(Vec::new());
+        w.write_all(b"0123").unwrap();
+        copy(&mut &b"456789"[..], &mut w).unwrap();
+        w.flush().unwrap();
+        assert_eq!(w.into_inner(), b"0123456789");
+        assert_eq!(pb.add(0), 10);
+    }
+}
@@ -1,6 +1,7 @@
 extern crate pbr;
 
-use pbr::{ProgressBar, PbIter};
+use pbr::{MultiBar, ProgressBar, ProgressIterator, ProgressRange, PbIter};
+use std::io::sink;
 use std::time::Duration;
 use std::thread;
 
@@ -36,6 +37,30 @@ fn simple_iter_example() {
     }
 }
 
+#[test]
+fn progress_iterator() {
+    let words = ["a", "b", "c"];
+    let n = words.iter().progress().filter(|w| **w != "b").count();
+    assert_eq!(n, 2);
+
+    let mut pb = ProgressBar::on(sink(), words.len() as u64);
+    pb.message("words ");
+    let upper: Vec<String> = words.iter().map(|w| w.to_uppercase()).progress_with(pb).collect();
+    assert_eq!(upper, ["A", "B", "C"]);
+}
+
+#[test]
+fn range_example() {
+    assert_eq!((0..10u8).total(), 10);
+    assert_eq!((-5..=5i64).total(), 11);
+    assert_eq!((0..=u64::MAX).total(), u64::MAX);
+    let (start, end) = (5, 0);
+    assert_eq!((start..end).total(), 0);
+    for _ in pbr::range(0..2000) {
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
 #[test]
 fn timeout_example() {
     let count = 10;
@@ -107,3 +132,62 @@ fn npm_bar() {
     }
     pb.finish_println("done!");
 }
+
+#[test]
+fn multibar_scope() {
+    let mb = MultiBar::on(sink());
+    let mut kept = mb.scope(|scope| {
+        scope.println("header");
+        let mut p1 = scope.bar(10);
+        let t = thread::spawn(move || {
+            for _ in 0..10 {
+                p1.inc();
+            }
+            p1.finish();
+        });
+        t.join().unwrap();
+        // not finished, and still alive after the scope.
+        scope.bar(10)
+    });
+    kept.inc();
+}
+
+#[test]
+fn multibar_listen_timeout() {
+    let mut mb = MultiBar::on(sink());
+    let mut p1 = mb.create_bar(10);
+    let p2 = mb.create_bar(10);
+    p1.inc();
+    p1.finish();
+    let err = mb.listen_with_timeout(Duration::from_millis(50)).unwrap_err();
+    assert_eq!(err.unfinished(), &[1]);
+    drop(p2);
+}
+
+#[test]
+fn multibar_abandoned_bar() {
+    let mut mb = MultiBar::on(sink());
+    let mut p1 = mb.create_bar(10);
+    thread::spawn(move || {
+        p1.inc();
+        // dropped without finish
+    });
+    mb.listen_with_timeout(Duration::from_secs(5)).unwrap();
+}
+
+#[test]
+fn multibar_handle() {
+    let mut mb = MultiBar::on(sink());
+    let handle = mb.handle();
+    let mut p1 = mb.create_bar(10);
+    let t = thread::spawn(move || mb.listen());
+    handle.pause();
+    p1.inc();
+    handle.resume();
+    handle.clear();
+    handle.redraw();
+    handle.resized();
+    p1.finish();
+    drop(handle);
+    t.join().unwrap();
+}
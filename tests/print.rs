@@ -0,0 +1,30 @@
+#[macro_use]
+extern crate pbr;
+
+use pbr::testing::Terminal;
+use pbr::StdStream;
+
+#[test]
+fn pb_println_above_bars() {
+    let term = Terminal::new(40, 5);
+    let mut mb = term.multibar();
+    mb.set_leave_rendered(true);
+    // the terminal stands for stdout
+    mb.set_std_stream(Some(StdStream::Stdout));
+    mb.scope(|scope| {
+        let mut pb = scope.bar(2);
+        pb.inc();
+        pb_println!("step {} done", 1);
+        pb_eprintln!();
+        pb.finish();
+    });
+    let screen = term.screen();
+    let lines: Vec<&str> = screen.lines().collect();
+    assert_eq!(lines.len(), 2, "{:?}", screen);
+    assert_eq!(lines[0], "step 1 done");
+    // the empty line went to stderr
+    assert!(lines[1].starts_with("2 / 2"), "{:?}", screen);
+
+    // nothing is listening anymore
+    pb_println!("after the bars");
+}